@@ -0,0 +1,55 @@
+//! Benchmarks the base64 decoding of adversarial encoded words, to make sure
+//! they are decoded (or rejected) in time linear to their length.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rfc2047_decoder::{Decoder, RecoverStrategy};
+
+/// Returns an encoded word of the given length whose encoded text consists of
+/// chars which aren't part of the base64 alphabet.
+fn invalid_base64_word(length: usize) -> String {
+    let prefix = "=?UTF-8?B?";
+    let suffix = "?=";
+    let encoded_text = "!".repeat(length - prefix.len() - suffix.len());
+
+    format!("{}{}{}", prefix, encoded_text, suffix)
+}
+
+fn adversarial_base64(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adversarial_base64");
+
+    // the longest encoded word which is allowed by the RFC
+    let message = invalid_base64_word(75);
+    let decoders = [
+        ("abort", Decoder::new()),
+        ("skip", Decoder::new().recover_all(RecoverStrategy::Skip)),
+        (
+            "decode",
+            Decoder::new().recover_all(RecoverStrategy::Decode),
+        ),
+    ];
+
+    for (name, decoder) in &decoders {
+        group.bench_with_input(
+            BenchmarkId::new("max_length", name),
+            &message,
+            |b, message| b.iter(|| decoder.decode(black_box(message))),
+        );
+    }
+
+    // too long encoded words only reach the base64 decoder if they are decoded anyway
+    let decoder = Decoder::new().recover_all(RecoverStrategy::Decode);
+    for length in [1 << 10, 1 << 14, 1 << 18] {
+        let message = invalid_base64_word(length);
+
+        group.bench_with_input(
+            BenchmarkId::new("too_long", length),
+            &message,
+            |b, message| b.iter(|| decoder.decode(black_box(message))),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, adversarial_base64);
+criterion_main!(benches);
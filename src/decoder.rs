@@ -1,10 +1,33 @@
-use std::result;
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{fmt, ops::Range, result};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
 use thiserror::Error;
 
-use crate::{evaluator, lexer, parser};
+use crate::{
+    evaluator, lexer,
+    lexer::{
+        encoded_word::{self, EncodedWord},
+        Token, QUESTION_MARK,
+    },
+    parser, DecodeOutcome, DecodeReport, DecodedSegment, DecodedWord, Encoding, RecoveredWord,
+    Segment, SegmentKind,
+};
 
 /// The possible errors which can occur while parsing the string.
+///
+/// More variants may be added (like `Error::Io` with the `std` feature), so
+/// a `match` on it needs a wildcard arm.
 #[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// Symbolises that an error occured in the lexer.
     #[error(transparent)]
@@ -17,11 +40,66 @@ pub enum Error {
     /// Symbolises that an error occured in the evaluator.
     #[error(transparent)]
     Evaluator(#[from] evaluator::Error),
+
+    /// Symbolises that the delimiter of [Decoder::decode_split] isn't an
+    /// ASCII byte.
+    #[error("cannot split on the delimiter {0:#04x}: delimiter isn't ASCII")]
+    NonAsciiDelimiter(u8),
+
+    /// Symbolises that the input couldn't be read, see
+    /// [Decoder::decode_stream].
+    #[cfg(feature = "std")]
+    #[error("cannot read the input: {message}")]
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Error {
+    /// Returns the class of the error if a [RecoverStrategy] can be set for
+    /// it.
+    fn class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::Lexer(lexer::Error::ParseEncodedWordTooLongError(_)) => {
+                Some(ErrorClass::TooLongEncodedWord)
+            }
+            Self::Parser(parser::Error::UnknownCharset(_) | parser::Error::EmptyCharset) => {
+                Some(ErrorClass::UnknownCharset)
+            }
+            Self::Parser(_) => Some(ErrorClass::InvalidEncoding),
+            Self::Evaluator(evaluator::Error::DecodeUtf8Error(_)) => Some(ErrorClass::InvalidUtf8),
+            Self::Evaluator(evaluator::Error::DecodeBase64Error(_)) => {
+                Some(ErrorClass::InvalidBase64)
+            }
+            Self::Evaluator(evaluator::Error::DecodeQuotedPrintableError(_)) => {
+                Some(ErrorClass::InvalidQuotedPrintable)
+            }
+            _ => None,
+        }
+    }
 }
 
+/// The minimum amount of inputs which [Decoder::decode_batch] decodes in
+/// parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_BATCH_THRESHOLD: usize = 64;
+
 /// Determines which strategy should be used if an encoded word isn't encoded as
-/// described in the RFC.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// described in the RFC. [RecoverStrategy::Replace] replaces the encoded word
+/// wherever [RecoverStrategy::Skip] would keep it verbatim.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum RecoverStrategy {
     /// Decode the encoded word although it's incorrectly encoded.
     ///
@@ -29,12 +107,31 @@ pub enum RecoverStrategy {
     /// Take a look to [Decoder#RecoveryStrategy::Decode](Decoder#recoverstrategydecode).
     Decode,
 
-    /// Skip the incorrectly encoded encoded word.
+    /// Skip the incorrectly encoded encoded word. It's kept verbatim, or
+    /// replaced with a placeholder if [Decoder::replacement_for_undecodable]
+    /// is set. The invalid bytes of the clear text
+    /// ([ErrorClass::InvalidUtf8]) are dropped instead.
     ///
     /// # Example
     /// Take a look to [Decoder#RecoveryStrategy::Skip](Decoder#recoverstrategyskip).
     Skip,
 
+    /// Replace the incorrectly encoded encoded word with the given text, like
+    /// a placeholder for previews. For [ErrorClass::InvalidUtf8] each run of
+    /// invalid bytes of the clear text is replaced with it.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, ErrorClass, RecoverStrategy};
+    ///
+    /// let placeholder = RecoverStrategy::Replace("[unreadable]".to_string());
+    /// let decoder = Decoder::new().recover_for(ErrorClass::InvalidBase64, placeholder);
+    ///
+    /// let decoded_str = decoder.decode("=?UTF-8?B?!!!?= =?UTF-8?Q?ok?=").unwrap();
+    /// assert_eq!(decoded_str, "[unreadable]ok");
+    /// ```
+    Replace(String),
+
     /// Abort the string-parsing and return an error.
     ///
     /// # Example
@@ -42,6 +139,229 @@ pub enum RecoverStrategy {
     Abort,
 }
 
+/// The classes of errors for which a separate [RecoverStrategy] can be set.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, ErrorClass, RecoverStrategy};
+///
+/// let decoder = Decoder::new()
+///     .recover_all(RecoverStrategy::Skip)
+///     .recover_for(ErrorClass::InvalidUtf8, RecoverStrategy::Decode);
+///
+/// assert_eq!(decoder.recover_strategy(ErrorClass::InvalidBase64), RecoverStrategy::Skip);
+/// assert_eq!(decoder.recover_strategy(ErrorClass::InvalidUtf8), RecoverStrategy::Decode);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ErrorClass {
+    /// The encoded word is longer than [Decoder::max_encoded_word_length]
+    /// (75 chars by default).
+    ///
+    /// - `Decode`: decode it anyway
+    /// - `Skip`: keep it verbatim as clear text
+    /// - `Abort` (default): return an error
+    TooLongEncodedWord,
+
+    /// The encoded text of a B encoded word isn't valid base64.
+    ///
+    /// - `Decode`: ignore the invalid characters and the padding
+    /// - `Skip`: keep the encoded word verbatim as clear text
+    /// - `Abort` (default): return an error
+    InvalidBase64,
+
+    /// The encoded text of a Q encoded word isn't valid quoted-printable.
+    ///
+    /// - `Decode` (default): decode it robustly, malformed escapes are kept
+    /// - `Skip`: keep the encoded word verbatim as clear text
+    /// - `Abort`: return an error
+    InvalidQuotedPrintable,
+
+    /// The charset of the encoded word is unknown.
+    ///
+    /// - `Decode` (default): decode it as ASCII, or as UTF-8 if the charset
+    ///   is empty
+    /// - `Skip`: keep the encoded word verbatim as clear text
+    /// - `Abort`: return an error
+    UnknownCharset,
+
+    /// The clear text isn't valid UTF-8.
+    ///
+    /// - `Decode`: replace the invalid bytes with `U+FFFD`
+    /// - `Skip`: drop the invalid bytes
+    /// - `Replace`: replace each run of invalid bytes with the given text
+    /// - `Abort` (default): return an error
+    InvalidUtf8,
+
+    /// The encoding of the encoded word is neither `B` nor `Q`.
+    ///
+    /// - `Decode` and `Skip`: keep the encoded word verbatim as clear text
+    /// - `Abort` (default): return an error
+    InvalidEncoding,
+}
+
+/// Determines what happens to the control characters in the decoded string.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{ControlCharPolicy, Decoder};
+///
+/// let message = "=?UTF-8?Q?a=07b=09c?=";
+///
+/// let decoder = Decoder::new().control_char_policy(ControlCharPolicy::Strip);
+/// assert_eq!(decoder.decode(message).unwrap(), "ab\tc");
+///
+/// let decoder = Decoder::new().control_char_policy(ControlCharPolicy::Escape);
+/// assert_eq!(decoder.decode(message).unwrap(), "a^Gb\tc");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ControlCharPolicy {
+    /// Keep the control characters (default).
+    Keep,
+
+    /// Remove all C0 and C1 control characters (and `DEL`), except tabs.
+    Strip,
+
+    /// Replace the C0 control characters and `DEL` with their caret notation
+    /// (for example `^G` for `\x07`) and the C1 control characters with their
+    /// escaped code point (for example `\u{85}`). Tabs are kept.
+    Escape,
+}
+
+/// Determines what happens to a malformed escape in the encoded text of a Q
+/// encoded word: a `=` which isn't followed by two hex digits. It only
+/// applies if such encoded words are decoded anyway (see
+/// [ErrorClass::InvalidQuotedPrintable]).
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, Error, EvaluatorError, MalformedEscapePolicy};
+///
+/// let message = "=?UTF-8?Q?100=_=3D_100=25?=";
+///
+/// let decoder = Decoder::new();
+/// assert_eq!(decoder.decode(message).unwrap(), "100= = 100%");
+///
+/// let decoder = Decoder::new().malformed_quoted_printable_strategy(MalformedEscapePolicy::Drop);
+/// assert_eq!(decoder.decode(message).unwrap(), "100 = 100%");
+///
+/// let decoder = Decoder::new().malformed_quoted_printable_strategy(MalformedEscapePolicy::Error);
+/// assert_eq!(
+///     decoder.decode(message),
+///     Err(Error::Evaluator(EvaluatorError::MalformedEscape { position: 3 }))
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum MalformedEscapePolicy {
+    /// Keep the `=` as it is (default).
+    Keep,
+
+    /// Remove the `=`, the chars after it are kept.
+    Drop,
+
+    /// Return [EvaluatorError::MalformedEscape](crate::EvaluatorError::MalformedEscape).
+    Error,
+}
+
+/// A transfer decoder for an encoding other than B and Q, registered with
+/// [Decoder::with_transfer_decoder].
+///
+/// Two transfer decoders are only equal if they are clones of each other.
+#[derive(Clone)]
+pub struct TransferDecoder(Arc<TransferDecodeFn>);
+
+type TransferDecodeFn = dyn Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync;
+
+impl TransferDecoder {
+    /// Decodes the encoded text, returns `None` if it's invalid.
+    pub fn decode(&self, encoded_text: &[u8]) -> Option<Vec<u8>> {
+        (self.0)(encoded_text)
+    }
+}
+
+impl fmt::Debug for TransferDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TransferDecoder(..)")
+    }
+}
+
+impl PartialEq for TransferDecoder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for TransferDecoder {}
+
+/// A decoder for charsets which aren't supported (or should be decoded
+/// differently), registered with [Decoder::with_charset_decoder].
+///
+/// Two charset decoders are only equal if they are clones of each other.
+#[derive(Clone)]
+pub struct CharsetDecoder(Arc<CharsetDecodeFn>);
+
+type CharsetDecodeFn = dyn Fn(&str, &[u8]) -> Option<String> + Send + Sync;
+
+impl CharsetDecoder {
+    /// Decodes the transfer decoded bytes with the charset of the given label,
+    /// returns `None` if the charset should be decoded as usual.
+    pub fn decode(&self, label: &str, decoded_bytes: &[u8]) -> Option<String> {
+        (self.0)(label, decoded_bytes)
+    }
+
+    /// Returns `true` if the charset decoder handles the charset of the given
+    /// label, which is checked by decoding no bytes with it.
+    pub fn claims(&self, label: &str) -> bool {
+        self.decode(label, &[]).is_some()
+    }
+}
+
+impl fmt::Debug for CharsetDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CharsetDecoder(..)")
+    }
+}
+
+impl PartialEq for CharsetDecoder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CharsetDecoder {}
+
+/// The kind of header field which is decoded, as far as it matters for the
+/// places where encoded words are allowed (see
+/// [section 5 of the RFC](https://datatracker.ietf.org/doc/html/rfc2047#section-5)).
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, HeaderContext};
+///
+/// let decoder = Decoder::new().header_context(HeaderContext::Address);
+///
+/// assert!(decoder.decode("=?UTF-8?Q?Jos=C3=A9?= <jose@example.com>").is_ok());
+/// assert!(decoder.decode("Jose <=?UTF-8?Q?jos=C3=A9?=@example.com>").is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum HeaderContext {
+    /// Unstructured text like `Subject`: encoded words are allowed anywhere
+    /// (default).
+    Unstructured,
+
+    /// A header with addresses like `From` or `To`: encoded words are only
+    /// allowed in the display name and in comments, but not in a quoted
+    /// string or in the address itself.
+    Address,
+
+    /// A header with message ids like `Message-ID` or `References`: encoded
+    /// words aren't allowed at all.
+    MessageId,
+}
+
 type Result<T> = result::Result<T, Error>;
 
 /// Represents the decoder builder.
@@ -59,8 +379,165 @@ type Result<T> = result::Result<T, Error>;
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Decoder {
     /// Determines which strategy should be used, if the parser encounters
-    /// encoded words which are longer than allowed in the RFC (it's longer than 75 chars).
+    /// encoded words which are longer than allowed in the RFC (it's longer than 75 chars),
+    /// or rather than [Decoder::max_encoded_word_length].
     pub too_long_encoded_word: RecoverStrategy,
+
+    /// Determines which strategy should be used, if the encoded text of a B
+    /// encoded word isn't valid base64. See [ErrorClass::InvalidBase64].
+    pub invalid_base64: RecoverStrategy,
+
+    /// Determines which strategy should be used, if the encoded text of a Q
+    /// encoded word isn't valid quoted-printable. See
+    /// [ErrorClass::InvalidQuotedPrintable].
+    pub invalid_quoted_printable: RecoverStrategy,
+
+    /// Determines which strategy should be used, if the charset of an
+    /// encoded word is unknown. See [ErrorClass::UnknownCharset].
+    pub unknown_charset: RecoverStrategy,
+
+    /// Determines which strategy should be used, if the clear text isn't
+    /// valid UTF-8. See [ErrorClass::InvalidUtf8].
+    pub invalid_utf8: RecoverStrategy,
+
+    /// Determines which strategy should be used, if the encoding of an
+    /// encoded word is neither `B` nor `Q`. See [ErrorClass::InvalidEncoding].
+    pub invalid_encoding: RecoverStrategy,
+
+    /// If set, only encoded words whose charset is one of these labels
+    /// (compared case-insensitively) are decoded. All other encoded words
+    /// are kept verbatim as clear text.
+    pub decodable_charsets: Option<Vec<String>>,
+
+    /// Determines if a `?` which isn't followed by a `=` is allowed in the
+    /// encoded text of an encoded word and if the charset, the encoding and
+    /// the encoded text may be empty or missing. The encoded text still ends
+    /// in front of the `=?` of another encoded word.
+    pub lenient_encoded_text: bool,
+
+    /// Determines if a charset which consists only of digits is looked up as
+    /// IANA MIB enum number (for example `106` for `UTF-8`).
+    pub allow_mib_charset: bool,
+
+    /// If set, encoded words which can't be decoded (and would be kept
+    /// verbatim because of [RecoverStrategy::Skip]) are replaced with this
+    /// text.
+    pub replacement_for_undecodable: Option<String>,
+
+    /// Determines if encoded words have to be preceded by whitespace (or the
+    /// start of the string) as required by the RFC. Otherwise they are kept
+    /// as clear text.
+    pub strict_word_boundaries: bool,
+
+    /// Determines if encoded words have to be followed by whitespace (or the
+    /// end of the string) as required by the RFC. Otherwise they are kept as
+    /// clear text.
+    pub require_whitespace_after_word: bool,
+
+    /// The byte which separates the charset, the encoding and the encoded
+    /// text of an encoded word and which precedes the closing `=`.
+    pub delimiter: u8,
+
+    /// Charset labels which are used instead of the declared charset of the
+    /// encoded word at the given index.
+    pub charset_overrides: BTreeMap<usize, String>,
+
+    /// Determines what happens to the control characters in the decoded
+    /// string.
+    pub control_char_policy: ControlCharPolicy,
+
+    /// The transfer decoders for encodings other than B and Q, keyed by the
+    /// upper case letter of the encoding.
+    pub transfer_decoders: BTreeMap<u8, TransferDecoder>,
+
+    /// The charsets which are tried in order if the charset of an encoded
+    /// word is unknown or if its encoded text isn't valid in that charset.
+    pub charset_fallback_chain: Vec<String>,
+
+    /// If set, the clear text is decoded with this charset instead of UTF-8.
+    pub clear_text_charset: Option<String>,
+
+    /// Determines in which parts of the header encoded words are allowed.
+    pub header_context: HeaderContext,
+
+    /// Determines if adjacent encoded words with the same charset and
+    /// encoding are decoded with their charset at once.
+    pub merge_adjacent_same_charset: bool,
+
+    /// Determines if encoded words inside quoted strings of addresses are
+    /// decoded and the quoted pairs of the quoted strings unescaped.
+    pub decode_in_quoted_strings: bool,
+
+    /// Determines if zero width spaces and BOMs are removed from the input
+    /// before it's decoded.
+    pub strip_zero_width: bool,
+
+    /// If set, an error is returned if the decoded string contains a char
+    /// above this one.
+    pub max_codepoint: Option<char>,
+
+    /// The maximum length of an encoded word (including its delimiters)
+    /// before it's considered too long. See [ErrorClass::TooLongEncodedWord].
+    pub max_encoded_word_length: usize,
+
+    /// Determines if the whitespace between two adjacent encoded words is
+    /// removed, as required by the RFC.
+    pub collapse_adjacent_whitespace: bool,
+
+    /// Determines what happens to a `=` in Q encoded text which isn't
+    /// followed by two hex digits. See [MalformedEscapePolicy].
+    pub malformed_quoted_printable: MalformedEscapePolicy,
+
+    /// Determines if whitespace around the charset and the encoding of an
+    /// encoded word is allowed (and removed).
+    pub trim_field_whitespace: bool,
+
+    /// If set, this charset label is used instead of the declared charset of
+    /// every encoded word and instead of UTF-8 for the clear text.
+    pub charset_override: Option<String>,
+
+    /// Determines if B encoded text which isn't valid base64 is decoded with
+    /// the URL-safe base64 alphabet (`-` and `_` instead of `+` and `/`).
+    pub url_safe_base64_fallback: bool,
+
+    /// Determines if the line breaks of folded header lines are removed
+    /// before the input is lexed.
+    pub unfold: bool,
+
+    /// Determines if an `_` in Q encoded text is decoded to a space, as
+    /// described in the RFC.
+    pub underscore_as_space: bool,
+
+    /// Determines if an encoded word may lack its closing `?=` at the end of
+    /// the input or in front of the next encoded word.
+    pub allow_missing_suffix: bool,
+
+    /// Charset labels which stand for another charset, keyed by the
+    /// lowercased label with hyphens instead of underscores.
+    pub charset_aliases: BTreeMap<String, String>,
+
+    /// The maximum length of the decoded string in bytes, if any.
+    pub max_output_bytes: Option<usize>,
+
+    /// Determines if the hex digits of the escapes in Q encoded text have to
+    /// be uppercase.
+    pub strict_q_hex_case: bool,
+
+    /// Determines if a leading BOM (U+FEFF) is removed from the decoded text
+    /// of each encoded word.
+    pub strip_bom: bool,
+
+    /// The decoder which is tried before the built-in charsets, if any.
+    pub charset_decoder: Option<CharsetDecoder>,
+
+    /// Determines if the charset of clear text with 8-bit bytes is guessed
+    /// if no charset is declared for it.
+    #[cfg(feature = "chardetng")]
+    pub detect_charset: bool,
+
+    /// Determines if the decoded string is normalized to NFC.
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_nfc: bool,
 }
 
 impl Decoder {
@@ -69,6 +546,38 @@ impl Decoder {
         Self::default()
     }
 
+    /// Returns a decoder which is as lenient as Thunderbird when it displays
+    /// headers:
+    ///
+    /// - every [ErrorClass] is recovered with [RecoverStrategy::Decode], so
+    ///   too long encoded words are decoded anyway, base64 with missing
+    ///   padding or stray characters is decoded lossily, malformed
+    ///   quoted-printable escapes are kept, unknown charsets are decoded as
+    ///   far as possible and invalid UTF-8 is replaced with `U+FFFD`
+    /// - a lone `?` is allowed in the encoded text
+    ///   ([Decoder::lenient_encoded_text])
+    ///
+    /// Some of Thunderbird's leniency is the default of every decoder anyway:
+    /// encoded words glued to other text are decoded, too, and `ISO-8859-1`
+    /// and `US-ASCII` are decoded as `windows-1252`.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::thunderbird_compatible();
+    /// let decoded_str = decoder
+    ///     .decode("=?ISO-8859-1?Q?=80uro?= =?UTF-8?B?w6k?=")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(decoded_str, "€uroé");
+    /// ```
+    pub fn thunderbird_compatible() -> Self {
+        Self::new()
+            .recover_all(RecoverStrategy::Decode)
+            .lenient_encoded_text(true)
+    }
+
     /// Set the strategy if the decoder finds an encoded word which is too long.
     ///
     /// # Examples
@@ -130,177 +639,4177 @@ impl Decoder {
     ///
     /// assert_eq!(parsed, Err(Lexer(ParseEncodedWordTooLongError(TooLongEncodedWords(vec!["=?utf-8?B?TG9yZW0gaXBzdW0gZG9sb3Igc2l0IGFtZXQsIGNvbnNlY3RldHVyIGFkaXBpc2NpbmcgZWxpdC4gVXQgaW50ZXJkdW0gcXVhbSBldSBmYWNpbGlzaXMgb3JuYXJlLg==?=".to_string()])))));
     /// ```
+    ///
+    /// # Untrusted input
+    /// With [RecoverStrategy::Abort] and [RecoverStrategy::Skip] the encoded
+    /// text which reaches the transfer decoding (B or Q) is never longer than
+    /// an encoded word of [Decoder::max_encoded_word_length] chars. With
+    /// [RecoverStrategy::Decode] it's only
+    /// bounded by the length of the input. The decoding stays linear in its
+    /// length though, even for invalid base64 which is decoded lossily (see
+    /// the `base64` benchmark).
     pub fn too_long_encoded_word_strategy(mut self, strategy: RecoverStrategy) -> Self {
         self.too_long_encoded_word = strategy;
         self
     }
 
-    /// Decodes the given RFC 2047 MIME Message Header encoded string.
-    pub fn decode<T: AsRef<[u8]>>(self, encoded_str: T) -> Result<String> {
-        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
-        let parsed_text = parser::run(text_tokens)?;
-        let evaluated_string = evaluator::run(parsed_text)?;
-
-        Ok(evaluated_string)
+    /// Set the strategy if the charset of an encoded word is unknown, see
+    /// [ErrorClass::UnknownCharset]. Equals
+    /// `recover_for(ErrorClass::UnknownCharset, strategy)`.
+    ///
+    /// The default strategy, [RecoverStrategy::Decode], decodes the encoded
+    /// word as ASCII. Use [Decoder::charset_fallback_chain] to decode it with
+    /// another charset (like UTF-8) instead. [RecoverStrategy::Abort] returns
+    /// [ParserError::UnknownCharset](crate::ParserError::UnknownCharset).
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Error, ParserError, RecoverStrategy};
+    ///
+    /// let message = "=?x-unknown?Q?Andr=C3=A9?=";
+    ///
+    /// let decoder = Decoder::new();
+    /// assert_eq!(decoder.decode(message).unwrap(), "Andr\u{FFFD}\u{FFFD}");
+    ///
+    /// let decoder = Decoder::new().charset_fallback_chain(vec!["UTF-8".to_string()]);
+    /// assert_eq!(decoder.decode(message).unwrap(), "André");
+    ///
+    /// let decoder = Decoder::new().unknown_charset_strategy(RecoverStrategy::Abort);
+    /// assert_eq!(
+    ///     decoder.decode(message),
+    ///     Err(Error::Parser(ParserError::UnknownCharset("x-unknown".to_string())))
+    /// );
+    /// ```
+    pub fn unknown_charset_strategy(mut self, strategy: RecoverStrategy) -> Self {
+        self.unknown_charset = strategy;
+        self
     }
-}
 
-impl Default for Decoder {
-    /// Returns the decoder with the following default "settings":
+    /// Set the strategy if the encoding of an encoded word is neither `B` nor
+    /// `Q` (like the typo in `=?UTF-8?Q2?str?=`), see
+    /// [ErrorClass::InvalidEncoding]. Equals
+    /// `recover_for(ErrorClass::InvalidEncoding, strategy)`.
     ///
-    /// - `too_long_encoded_word`: [RecoverStrategy::Abort]
-    fn default() -> Self {
-        Self {
-            too_long_encoded_word: RecoverStrategy::Abort,
-        }
+    /// The default strategy, [RecoverStrategy::Abort], returns a
+    /// [ParserError](crate::ParserError) with the offending encoding. With
+    /// the other strategies the encoded word is kept as clear text.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Error, ParserError, RecoverStrategy};
+    ///
+    /// let message = "=?UTF-8?Q2?str?=";
+    ///
+    /// assert_eq!(
+    ///     Decoder::new().decode(message),
+    ///     Err(Error::Parser(ParserError::ParseEncodingTooBigError("Q2".to_string())))
+    /// );
+    ///
+    /// let decoder = Decoder::new().invalid_encoding_strategy(RecoverStrategy::Skip);
+    /// assert_eq!(decoder.decode(message).unwrap(), message);
+    /// ```
+    pub fn invalid_encoding_strategy(mut self, strategy: RecoverStrategy) -> Self {
+        self.invalid_encoding = strategy;
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    /// Here are the main-tests which are listed here:
-    /// https://datatracker.ietf.org/doc/html/rfc2047#section-8
-    /// Scroll down until you see the table.
-    mod rfc_tests {
-        use crate::decode;
+    /// Set the strategy for the given class of errors.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, ErrorClass, RecoverStrategy};
+    ///
+    /// let decoder = Decoder::new().recover_for(ErrorClass::InvalidBase64, RecoverStrategy::Skip);
+    ///
+    /// assert_eq!(decoder.decode("=?UTF-8?B?!!!?=").unwrap(), "=?UTF-8?B?!!!?=");
+    /// ```
+    pub fn recover_for(mut self, class: ErrorClass, strategy: RecoverStrategy) -> Self {
+        *self.recover_strategy_mut(class) = strategy;
+        self
+    }
 
-        #[test]
-        fn decode_encoded_word_single_char() {
-            assert_eq!(decode("=?ISO-8859-1?Q?a?=").unwrap(), "a");
+    /// Set the same strategy for every class of errors. Use
+    /// [Decoder::recover_for] afterwards to override single classes.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, RecoverStrategy};
+    ///
+    /// let decoder = Decoder::new().recover_all(RecoverStrategy::Skip);
+    /// let decoded_str = decoder.decode("=?UTF-8?X?str?= =?UTF-8?B?!!!?=").unwrap();
+    ///
+    /// assert_eq!(decoded_str, "=?UTF-8?X?str?= =?UTF-8?B?!!!?=");
+    /// ```
+    pub fn recover_all(mut self, strategy: RecoverStrategy) -> Self {
+        for class in Self::ERROR_CLASSES {
+            *self.recover_strategy_mut(class) = strategy.clone();
         }
+        self
+    }
 
-        #[test]
-        fn decode_encoded_word_separated_by_whitespace() {
-            assert_eq!(decode("=?ISO-8859-1?Q?a?= b").unwrap(), "a b");
-        }
+    /// Set the same strategy for the errors of the evaluator: the encoded text
+    /// which isn't valid base64 ([ErrorClass::InvalidBase64]) or
+    /// quoted-printable ([ErrorClass::InvalidQuotedPrintable]). With
+    /// [RecoverStrategy::Skip] a single encoded word which can't be decoded is
+    /// kept as it is instead of failing the whole header.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, RecoverStrategy};
+    ///
+    /// let message = "=?UTF-8?B?!!!?= =?UTF-8?Q?=ZZ?= =?UTF-8?Q?ok?=";
+    /// let decoder = Decoder::new().on_decode_error(RecoverStrategy::Skip);
+    ///
+    /// assert!(Decoder::new().decode(message).is_err());
+    /// assert_eq!(decoder.decode(message).unwrap(), "=?UTF-8?B?!!!?==?UTF-8?Q?=ZZ?=ok");
+    /// ```
+    pub fn on_decode_error(self, strategy: RecoverStrategy) -> Self {
+        self.recover_for(ErrorClass::InvalidBase64, strategy.clone())
+            .recover_for(ErrorClass::InvalidQuotedPrintable, strategy)
+    }
 
-        #[test]
-        fn decode_two_encoded_chars() {
-            assert_eq!(
-                decode("=?ISO-8859-1?Q?a?= =?ISO-8859-1?Q?b?=").unwrap(),
-                "ab"
-            );
-        }
+    /// Returns the strategy which is used for the given class of errors.
+    pub fn recover_strategy(&self, class: ErrorClass) -> RecoverStrategy {
+        let strategy = match class {
+            ErrorClass::TooLongEncodedWord => &self.too_long_encoded_word,
+            ErrorClass::InvalidBase64 => &self.invalid_base64,
+            ErrorClass::InvalidQuotedPrintable => &self.invalid_quoted_printable,
+            ErrorClass::UnknownCharset => &self.unknown_charset,
+            ErrorClass::InvalidUtf8 => &self.invalid_utf8,
+            ErrorClass::InvalidEncoding => &self.invalid_encoding,
+        };
 
-        #[test]
-        fn whitespace_between_two_encoded_words_should_be_ignored() {
-            assert_eq!(
-                decode("=?ISO-8859-1?Q?a?=  =?ISO-8859-1?Q?b?=").unwrap(),
-                "ab"
-            );
-        }
+        strategy.clone()
+    }
 
-        #[test]
-        fn whitespace_chars_between_two_encoded_words_should_be_ignored() {
-            assert_eq!(
-                decode(
-                    "=?ISO-8859-1?Q?a?=               
-                     =?ISO-8859-1?Q?b?="
-                )
-                .unwrap(),
-                "ab"
-            );
-        }
+    const ERROR_CLASSES: [ErrorClass; 6] = [
+        ErrorClass::TooLongEncodedWord,
+        ErrorClass::InvalidBase64,
+        ErrorClass::InvalidQuotedPrintable,
+        ErrorClass::UnknownCharset,
+        ErrorClass::InvalidUtf8,
+        ErrorClass::InvalidEncoding,
+    ];
 
-        #[test]
-        fn whitespace_encoded_in_encoded_word() {
-            assert_eq!(decode("=?ISO-8859-1?Q?a_b?=").unwrap(), "a b");
+    fn recover_strategy_mut(&mut self, class: ErrorClass) -> &mut RecoverStrategy {
+        match class {
+            ErrorClass::TooLongEncodedWord => &mut self.too_long_encoded_word,
+            ErrorClass::InvalidBase64 => &mut self.invalid_base64,
+            ErrorClass::InvalidQuotedPrintable => &mut self.invalid_quoted_printable,
+            ErrorClass::UnknownCharset => &mut self.unknown_charset,
+            ErrorClass::InvalidUtf8 => &mut self.invalid_utf8,
+            ErrorClass::InvalidEncoding => &mut self.invalid_encoding,
         }
+    }
 
-        #[test]
+    /// Only decode encoded words whose charset label is one of the given
+    /// `charsets`. Encoded words with any other charset are neither decoded
+    /// nor dropped: they are kept verbatim in the output.
+    ///
+    /// The labels are compared case-insensitively, but aliases aren't
+    /// resolved, so `utf8` has to be listed separately from `UTF-8` if both
+    /// should be trusted.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().decode_only_charsets(&["UTF-8", "US-ASCII"]);
+    /// let decoded_str = decoder
+    ///     .decode("=?UTF-8?Q?str?= =?KOI8-R?Q?=F0=D2=C9?=")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(decoded_str, "str =?KOI8-R?Q?=F0=D2=C9?=");
+    /// ```
+    pub fn decode_only_charsets(mut self, charsets: &[&str]) -> Self {
+        self.decodable_charsets = Some(charsets.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Allow a `?` inside the encoded text as long as it isn't followed by a
+    /// `=` (which would close the encoded word).
+    ///
+    /// Neither the B nor the Q encoding produce a literal `?`, but some
+    /// malformed Q encoded words contain one anyway.
+    ///
+    /// Empty charsets and encodings are allowed as well, and the encoded text
+    /// may be missing together with the `?` in front of it. An encoded word
+    /// without encoding and encoded text (like `=???=`) is decoded to an
+    /// empty string, an empty encoding with encoded text is an
+    /// [ErrorClass::InvalidEncoding]. An empty charset is an
+    /// [ErrorClass::UnknownCharset] which is decoded as UTF-8, or returns a
+    /// [ParserError::EmptyCharset](crate::ParserError::EmptyCharset) if the
+    /// strategy aborts.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?Q?a?b?=";
+    ///
+    /// // strict (default): the encoded word is invalid, so it's kept as clear text
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), message);
+    ///
+    /// // lenient: `a?b` is the encoded text
+    /// let decoder = Decoder::new().lenient_encoded_text(true);
+    /// assert_eq!(decoder.decode(message).unwrap(), "a?b");
+    /// assert_eq!(decoder.decode("=???=").unwrap(), "");
+    /// ```
+    pub fn lenient_encoded_text(mut self, lenient: bool) -> Self {
+        self.lenient_encoded_text = lenient;
+        self
+    }
+
+    /// Interpret charsets which consist only of digits as IANA MIB enum
+    /// number, as some ancient software does. Only the MIB numbers of common
+    /// charsets are known.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // 4 is the MIB number of ISO-8859-1
+    /// let decoder = Decoder::new().allow_mib_charset(true);
+    /// assert_eq!(decoder.decode("=?4?Q?Andr=E9?=").unwrap(), "André");
+    /// ```
+    pub fn allow_mib_charset(mut self, allow: bool) -> Self {
+        self.allow_mib_charset = allow;
+        self
+    }
+
+    /// Replace encoded words which can't be decoded with the given text
+    /// instead of keeping them verbatim.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, ErrorClass, RecoverStrategy};
+    ///
+    /// let decoder = Decoder::new()
+    ///     .recover_for(ErrorClass::InvalidBase64, RecoverStrategy::Skip)
+    ///     .replacement_for_undecodable("[unreadable]".to_string());
+    ///
+    /// let decoded_str = decoder.decode("Subject: =?UTF-8?B?!!!?=").unwrap();
+    /// assert_eq!(decoded_str, "Subject: [unreadable]");
+    /// ```
+    pub fn replacement_for_undecodable(mut self, replacement: String) -> Self {
+        self.replacement_for_undecodable = Some(replacement);
+        self
+    }
+
+    /// Only decode encoded words which are preceded by whitespace, the start
+    /// of the string or the opening parenthesis of a comment, as required by
+    /// [section 5 of the RFC](https://datatracker.ietf.org/doc/html/rfc2047#section-5).
+    /// Encoded words which are glued to other text are kept as clear text.
+    ///
+    /// By default the decoder is lenient and decodes them anyway.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().strict_word_boundaries(true);
+    ///
+    /// assert_eq!(decoder.decode("foo =?UTF-8?Q?x?=").unwrap(), "foo x");
+    /// assert_eq!(decoder.decode("foo=?UTF-8?Q?x?=").unwrap(), "foo=?UTF-8?Q?x?=");
+    ///
+    /// // lenient (default)
+    /// assert_eq!(Decoder::new().decode("foo=?UTF-8?Q?x?=").unwrap(), "foox");
+    /// ```
+    pub fn strict_word_boundaries(mut self, strict: bool) -> Self {
+        self.strict_word_boundaries = strict;
+        self
+    }
+
+    /// Only decode encoded words which are followed by whitespace, the end of
+    /// the string or the closing parenthesis of a comment, as required by
+    /// [section 5 of the RFC](https://datatracker.ietf.org/doc/html/rfc2047#section-5).
+    /// Encoded words which are directly followed by punctuation or other text
+    /// are kept as clear text.
+    ///
+    /// By default the decoder is lenient and decodes them anyway.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().require_whitespace_after_word(true);
+    ///
+    /// assert_eq!(decoder.decode("=?UTF-8?Q?x?= :").unwrap(), "x :");
+    /// assert_eq!(decoder.decode("=?UTF-8?Q?x?=:").unwrap(), "=?UTF-8?Q?x?=:");
+    ///
+    /// // lenient (default)
+    /// assert_eq!(Decoder::new().decode("=?UTF-8?Q?x?=:").unwrap(), "x:");
+    /// ```
+    pub fn require_whitespace_after_word(mut self, require: bool) -> Self {
+        self.require_whitespace_after_word = require;
+        self
+    }
+
+    /// Use the given byte instead of `?` to separate the charset, the
+    /// encoding and the encoded text of an encoded word and to close it, as
+    /// some broken mailers do. The encoded word still has to start with `=?`.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().alternate_delimiter(b'*');
+    /// assert_eq!(decoder.decode("=?UTF-8*Q*x*=").unwrap(), "x");
+    ///
+    /// // default: it's just clear text
+    /// assert_eq!(Decoder::new().decode("=?UTF-8*Q*x*=").unwrap(), "=?UTF-8*Q*x*=");
+    /// ```
+    pub fn alternate_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Decode the encoded word at the given index with the given charset,
+    /// regardless of the charset it declares. This is meant to repair
+    /// headers with a known mislabeled encoded word.
+    ///
+    /// The index starts at 0 and counts the encoded words which are decoded,
+    /// encoded words which are kept as clear text aren't counted.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // the second encoded word claims to be latin1, but it's windows-1251
+    /// let message = "=?UTF-8?Q?Hi?= =?ISO-8859-1?Q?=CF=F0=E8=E2=E5=F2?=";
+    /// let decoder = Decoder::new().override_charset_at(1, "windows-1251");
+    ///
+    /// assert_eq!(decoder.decode(message).unwrap(), "HiПривет");
+    /// ```
+    pub fn override_charset_at(mut self, index: usize, charset: &str) -> Self {
+        self.charset_overrides.insert(index, charset.to_string());
+        self
+    }
+
+    /// Decode the encoded words with the given nonstandard charset label with
+    /// the given charset. The alias is compared case-insensitively and
+    /// underscores match hyphens.
+    ///
+    /// A few common misspellings (like `latin-1`, `cp932` or `utf8mb4`) are
+    /// aliased by default, this overrides them.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?Latin_1?Q?caf=E9?= =?x-cyrillic?Q?=CF=F0=E8=E2=E5=F2?=";
+    /// let decoder = Decoder::new().charset_alias("x-cyrillic", "windows-1251");
+    ///
+    /// assert_eq!(decoder.decode(message).unwrap(), "caféПривет");
+    /// assert!(decoder.charset_is_supported("x-cyrillic"));
+    /// ```
+    pub fn charset_alias(mut self, alias: &str, charset: &str) -> Self {
+        let alias = parser::charset_alias_key(alias.as_bytes());
+        self.charset_aliases.insert(alias, charset.to_string());
+        self
+    }
+
+    /// Set what should happen to the control characters in the decoded
+    /// string, for example to keep a decoded escape sequence from messing up
+    /// a terminal. See [ControlCharPolicy].
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{ControlCharPolicy, Decoder};
+    ///
+    /// let decoder = Decoder::new().control_char_policy(ControlCharPolicy::Strip);
+    /// let decoded_str = decoder.decode("=?UTF-8?Q?=1B[31mred?=").unwrap();
+    ///
+    /// assert_eq!(decoded_str, "[31mred");
+    /// ```
+    pub fn control_char_policy(mut self, policy: ControlCharPolicy) -> Self {
+        self.control_char_policy = policy;
+        self
+    }
+
+    /// Register a transfer decoder for the given encoding letter (compared
+    /// case-insensitively), so encoded words with this encoding are decoded
+    /// with it before applying the charset. B and Q can't be replaced.
+    ///
+    /// If the transfer decoder returns `None`, the encoded word is kept
+    /// verbatim (or replaced, see [Decoder::replacement_for_undecodable]).
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // some generators use `X` for the percent-encoding of RFC 2231
+    /// fn percent_decode(encoded_text: &[u8]) -> Option<Vec<u8>> {
+    ///     let mut decoded = Vec::new();
+    ///     let mut bytes = encoded_text.iter();
+    ///
+    ///     while let Some(&byte) = bytes.next() {
+    ///         if byte == b'%' {
+    ///             let hex = [*bytes.next()?, *bytes.next()?];
+    ///             let hex = std::str::from_utf8(&hex).ok()?;
+    ///             decoded.push(u8::from_str_radix(hex, 16).ok()?);
+    ///         } else {
+    ///             decoded.push(byte);
+    ///         }
+    ///     }
+    ///
+    ///     Some(decoded)
+    /// }
+    ///
+    /// let decoder = Decoder::new().with_transfer_decoder(b'X', percent_decode);
+    /// assert_eq!(decoder.decode("=?utf-8?X?%e2%82%ac?=").unwrap(), "€");
+    /// ```
+    pub fn with_transfer_decoder<F>(mut self, encoding: u8, transfer_decoder: F) -> Self
+    where
+        F: Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.transfer_decoders.insert(
+            encoding.to_ascii_uppercase(),
+            TransferDecoder(Arc::new(transfer_decoder)),
+        );
+        self
+    }
+
+    /// Try the given charsets in order if the charset of an encoded word is
+    /// unknown or if the encoded word can't be decoded with its charset
+    /// without replacement characters.
+    ///
+    /// The first charset which decodes the encoded word without replacement
+    /// characters wins. If there's none, an unknown charset is decoded with
+    /// the first (known) charset of the chain and a known charset is kept.
+    /// Unknown labels in the chain are ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().charset_fallback_chain(vec![
+    ///     "UTF-8".to_string(),
+    ///     "windows-1252".to_string(),
+    /// ]);
+    ///
+    /// // `é` in UTF-8 and in windows-1252
+    /// assert_eq!(decoder.decode("=?x-unknown?Q?=C3=A9?=").unwrap(), "é");
+    /// assert_eq!(decoder.decode("=?x-unknown?Q?=E9?=").unwrap(), "é");
+    /// ```
+    pub fn charset_fallback_chain(mut self, charsets: Vec<String>) -> Self {
+        self.charset_fallback_chain = charsets;
+        self
+    }
+
+    /// Decode the clear text (everything outside of encoded words) with the
+    /// given charset instead of UTF-8, for example if the charset of a legacy
+    /// 8-bit header is known from elsewhere. Invalid bytes are replaced with
+    /// `U+FFFD`, so [ErrorClass::InvalidUtf8] doesn't apply anymore.
+    ///
+    /// If the label is unknown, the clear text is decoded as UTF-8.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().clear_text_charset("windows-1251");
+    /// let decoded_str = decoder.decode(b"\xcf\xf0\xe8\xe2\xe5\xf2 =?UTF-8?Q?x?=").unwrap();
+    ///
+    /// assert_eq!(decoded_str, "Привет x");
+    /// ```
+    pub fn clear_text_charset(mut self, charset: &str) -> Self {
+        self.clear_text_charset = Some(charset.to_string());
+        self
+    }
+
+    /// Guess the charset of clear text which contains 8-bit bytes but isn't
+    /// valid UTF-8, instead of treating it as (invalid) UTF-8. The guess is
+    /// made with [chardetng](https://docs.rs/chardetng) for each run of clear
+    /// text between encoded words. [Decoder::clear_text_charset] takes
+    /// precedence if it's set.
+    ///
+    /// This is a heuristic: the shorter the clear text, the less reliable the
+    /// guess. A few accented latin letters are usually detected fine, but a
+    /// handful of bytes of a less common charset may be mistaken for another
+    /// one. ASCII and valid UTF-8 clear text is never affected.
+    ///
+    /// Requires the `chardetng` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().detect_charset(true);
+    /// let decoded_str = decoder.decode(b"Caf\xe9 cr\xe8me =?UTF-8?Q?br=C3=BBl=C3=A9e?=").unwrap();
+    ///
+    /// assert_eq!(decoded_str, "Café crème brûlée");
+    /// ```
+    #[cfg(feature = "chardetng")]
+    pub fn detect_charset(mut self, detect_charset: bool) -> Self {
+        self.detect_charset = detect_charset;
+        self
+    }
+
+    /// Normalize the decoded string to the Unicode normalization form C
+    /// (NFC), like search indexes usually expect. The clear text and the
+    /// encoded words are normalized alike, each on its own for the segments
+    /// and the decoded words, and as a whole for the decoded string.
+    ///
+    /// Requires the `unicode-normalization` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // `e` followed by the combining acute accent
+    /// let message = "Cafe\u{301} =?UTF-8?Q?cre=CC=80me?=";
+    /// let decoder = Decoder::new().normalize_nfc(true);
+    ///
+    /// assert_eq!(decoder.decode(message).unwrap(), "Caf\u{e9} cr\u{e8}me");
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalize_nfc(mut self, normalize_nfc: bool) -> Self {
+        self.normalize_nfc = normalize_nfc;
+        self
+    }
+
+    /// Set the kind of header which is decoded, so an error is returned for
+    /// encoded words in places where the RFC doesn't allow them. See
+    /// [HeaderContext].
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Error, HeaderContext, LexerError};
+    ///
+    /// let decoder = Decoder::new().header_context(HeaderContext::MessageId);
+    ///
+    /// assert_eq!(
+    ///     decoder.decode("<=?UTF-8?Q?id?=@example.com>").unwrap_err(),
+    ///     Error::Lexer(LexerError::MisplacedEncodedWord("=?UTF-8?Q?id?=".to_string()))
+    /// );
+    /// ```
+    pub fn header_context(mut self, context: HeaderContext) -> Self {
+        self.header_context = context;
+        self
+    }
+
+    /// Decode encoded words inside the quoted strings of addresses, like in
+    /// `"=?UTF-8?Q?J=C3=B6rg?=" <jorg@example.com>`, if the
+    /// [HeaderContext] is [HeaderContext::Address].
+    ///
+    /// The RFC doesn't allow encoded words in quoted strings, but many mail
+    /// clients produce them anyway. The quotes are kept, while the backslashes
+    /// of the quoted pairs (like `\"`) in the quoted strings are removed.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, HeaderContext};
+    ///
+    /// let decoder = Decoder::new()
+    ///     .header_context(HeaderContext::Address)
+    ///     .decode_in_quoted_strings(true);
+    /// let decoded_str = decoder
+    ///     .decode(r#""=?UTF-8?Q?J=C3=B6rg?= \"JJ\"" <jorg@example.com>"#)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(decoded_str, r#""Jörg "JJ"" <jorg@example.com>"#);
+    /// ```
+    pub fn decode_in_quoted_strings(mut self, decode: bool) -> Self {
+        self.decode_in_quoted_strings = decode;
+        self
+    }
+
+    /// Remove the UTF-8 encoded zero width spaces (`U+200B`) and zero width
+    /// no-break spaces (`U+FEFF`, also known as BOM) from the input before
+    /// decoding it. Some copy-paste pipelines insert them between or even
+    /// inside of encoded words, which breaks them.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?B?SsO2\u{FEFF}cmc=?=\u{200B} =?UTF-8?Q?!?=";
+    ///
+    /// let decoder = Decoder::new().strip_zero_width(true);
+    /// assert_eq!(decoder.decode(message).unwrap(), "Jörg!");
+    ///
+    /// // by default the BOM is part of the base64 encoded text
+    /// assert!(Decoder::new().decode(message).is_err());
+    /// ```
+    pub fn strip_zero_width(mut self, strip: bool) -> Self {
+        self.strip_zero_width = strip;
+        self
+    }
+
+    /// Return an error if the decoded string contains a char above the given
+    /// one, for example `'\u{FF}'` for a downstream which only handles
+    /// Latin-1. The error contains the char and its position in the decoded
+    /// string (counted in `char`s).
+    ///
+    /// [Decoder::decode_detailed] isn't affected by this.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Error, EvaluatorError};
+    ///
+    /// let decoder = Decoder::new().max_codepoint('\u{FF}');
+    ///
+    /// assert_eq!(decoder.decode("=?UTF-8?Q?Andr=C3=A9?=").unwrap(), "André");
+    /// assert_eq!(
+    ///     decoder.decode("Price: =?UTF-8?Q?5=E2=82=AC?=").unwrap_err(),
+    ///     Error::Evaluator(EvaluatorError::CodepointAboveMaximum {
+    ///         codepoint: '€',
+    ///         position: 8,
+    ///     })
+    /// );
+    /// ```
+    pub fn max_codepoint(mut self, max_codepoint: char) -> Self {
+        self.max_codepoint = Some(max_codepoint);
+        self
+    }
+
+    /// Return an error as soon as the decoded string gets longer than the
+    /// given amount of bytes, so decoding untrusted headers with lots of
+    /// encoded words can't eat up the memory.
+    ///
+    /// [Decoder::decode_detailed] isn't affected by this.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Error, EvaluatorError};
+    ///
+    /// let decoder = Decoder::new().max_output_bytes(8);
+    ///
+    /// assert_eq!(decoder.decode("=?UTF-8?B?w6k=?= =?UTF-8?B?w6k=?=").unwrap(), "éé");
+    /// assert_eq!(
+    ///     decoder.decode("=?UTF-8?Q?Andr=C3=A9?= =?UTF-8?Q?Bj=C3=B6rk?=").unwrap_err(),
+    ///     Error::Evaluator(EvaluatorError::OutputTooLarge { max_bytes: 8 })
+    /// );
+    /// ```
+    pub fn max_output_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_output_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Return an error for escapes with lowercase hex digits (like `=e9`) in
+    /// Q encoded text, which the RFC doesn't allow. Useful to validate the
+    /// headers of outgoing messages, since lowercase hex digits are decoded
+    /// fine otherwise. The error contains the position of the `=` in the
+    /// encoded text.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Error, EvaluatorError};
+    ///
+    /// let message = "=?UTF-8?Q?caf=C3=a9?=";
+    /// let decoder = Decoder::new().strict_q_hex_case(true);
+    ///
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), "café");
+    /// assert_eq!(
+    ///     decoder.decode(message).unwrap_err(),
+    ///     Error::Evaluator(EvaluatorError::InvalidHexCase { position: 6 })
+    /// );
+    /// ```
+    pub fn strict_q_hex_case(mut self, strict: bool) -> Self {
+        self.strict_q_hex_case = strict;
+        self
+    }
+
+    /// Set if a leading BOM (U+FEFF) is removed from the decoded text of each
+    /// encoded word. Most charsets, like UTF-8 and UTF-16, drop the BOM while
+    /// decoding anyway, but others, like UTF-7, keep it. A BOM which isn't at
+    /// the start of the decoded text is kept.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-7?Q?+/v8-Hello?=";
+    /// let decoder = Decoder::new().strip_bom(true);
+    ///
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), "\u{FEFF}Hello");
+    /// assert_eq!(decoder.decode(message).unwrap(), "Hello");
+    /// ```
+    pub fn strip_bom(mut self, strip: bool) -> Self {
+        self.strip_bom = strip;
+        self
+    }
+
+    /// Register a charset decoder, which gets the charset label of an encoded
+    /// word and its transfer decoded bytes. If it returns `Some`, the string is
+    /// used as the decoded text, otherwise the bytes are decoded with the
+    /// built-in charsets as usual.
+    ///
+    /// A charset label counts as known if the charset decoder returns `Some`
+    /// for it (see [CharsetDecoder::claims]), so the strategy for
+    /// [ErrorClass::UnknownCharset] doesn't apply to it and
+    /// [Decoder::charset_is_supported] returns `true` for it.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new().with_charset_decoder(|label, bytes| {
+    ///     if label.eq_ignore_ascii_case("x-upper") {
+    ///         Some(String::from_utf8_lossy(bytes).to_uppercase())
+    ///     } else {
+    ///         None
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(
+    ///     decoder.decode("=?x-upper?Q?hello?= =?UTF-8?Q?world?=").unwrap(),
+    ///     "HELLOworld"
+    /// );
+    /// ```
+    pub fn with_charset_decoder<F>(mut self, charset_decoder: F) -> Self
+    where
+        F: Fn(&str, &[u8]) -> Option<String> + Send + Sync + 'static,
+    {
+        self.charset_decoder = Some(CharsetDecoder(Arc::new(charset_decoder)));
+        self
+    }
+
+    /// Set the maximum length of an encoded word (including its delimiters),
+    /// above which the strategy for [ErrorClass::TooLongEncodedWord] applies.
+    /// The RFC allows 75 chars, but some mailers write longer encoded words,
+    /// so lenient consumers can raise the limit instead of decoding every
+    /// encoded word regardless of its length.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = format!("=?UTF-8?Q?{}?=", "a".repeat(100));
+    ///
+    /// assert!(Decoder::new().decode(&message).is_err());
+    /// assert_eq!(
+    ///     Decoder::new().max_encoded_word_length(998).decode(&message).unwrap(),
+    ///     "a".repeat(100)
+    /// );
+    /// ```
+    pub fn max_encoded_word_length(mut self, max_encoded_word_length: usize) -> Self {
+        self.max_encoded_word_length = max_encoded_word_length;
+        self
+    }
+
+    /// Remove the whitespace between two adjacent encoded words (the
+    /// default), as required by
+    /// [section 6.2 of the RFC](https://datatracker.ietf.org/doc/html/rfc2047#section-6.2).
+    ///
+    /// Some senders rely on it being kept instead, for example between
+    /// encoded words with different charsets. If it's kept, adjacent encoded
+    /// words are never merged (see [Decoder::merge_adjacent_same_charset]).
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?Q?Caf=C3=A9?=\t=?ISO-8859-1?Q?cr=E8me?=";
+    ///
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), "Cafécrème");
+    /// assert_eq!(
+    ///     Decoder::new().collapse_adjacent_whitespace(false).decode(message).unwrap(),
+    ///     "Café\tcrème"
+    /// );
+    /// ```
+    pub fn collapse_adjacent_whitespace(mut self, collapse: bool) -> Self {
+        self.collapse_adjacent_whitespace = collapse;
+        self
+    }
+
+    /// Set what happens to a `=` in Q encoded text which isn't followed by
+    /// two hex digits, if the encoded word is decoded anyway (which is the
+    /// default strategy for [ErrorClass::InvalidQuotedPrintable]).
+    ///
+    /// Unlike [RecoverStrategy::Abort] for invalid quoted-printable,
+    /// [MalformedEscapePolicy::Error] accepts lowercase hex digits and other
+    /// deviations from the quoted-printable rules.
+    ///
+    /// See [MalformedEscapePolicy] for an example.
+    pub fn malformed_quoted_printable_strategy(mut self, policy: MalformedEscapePolicy) -> Self {
+        self.malformed_quoted_printable = policy;
+        self
+    }
+
+    /// Allow spaces and tabs around the charset and the encoding of an
+    /// encoded word (like in `=? UTF-8 ?Q?str?=`), which some broken encoders
+    /// emit, and remove them before the charset and the encoding are looked
+    /// up. By default, such encoded words are clear text.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=? UTF-8 ? Q ?Andr=C3=A9?=";
+    ///
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), message);
+    /// assert_eq!(
+    ///     Decoder::new().trim_field_whitespace(true).decode(message).unwrap(),
+    ///     "André"
+    /// );
+    /// ```
+    pub fn trim_field_whitespace(mut self, trim: bool) -> Self {
+        self.trim_field_whitespace = trim;
+        self
+    }
+
+    /// Decode every encoded word with the given charset, regardless of the
+    /// charset it declares, and the clear text with it instead of UTF-8. This
+    /// is meant for mail of senders which mislabel all of their headers.
+    ///
+    /// [Decoder::override_charset_at] and [Decoder::clear_text_charset] take
+    /// precedence, since they are more specific. If the label is unknown, the
+    /// encoded words are handled as if they declared an unknown charset (see
+    /// [ErrorClass::UnknownCharset]) and the clear text is decoded as UTF-8.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // labelled as US-ASCII and UTF-8, but everything is windows-1252
+    /// let message = b"=?US-ASCII?Q?caf=E9?= \xe0 =?UTF-8?Q?cr=E8me?=";
+    /// let decoder = Decoder::new().override_charset("windows-1252");
+    ///
+    /// assert_eq!(decoder.decode(message).unwrap(), "café à crème");
+    /// ```
+    pub fn override_charset(mut self, charset: &str) -> Self {
+        self.charset_override = Some(charset.to_string());
+        self
+    }
+
+    /// Decode B encoded text with the URL-safe base64 alphabet if it isn't
+    /// valid with the standard one, since some broken encoders use `-` and
+    /// `_` instead of `+` and `/`. The standard alphabet is always tried
+    /// first, so valid encoded words are decoded the same way.
+    ///
+    /// If neither alphabet works, [ErrorClass::InvalidBase64] applies.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // `???` in URL-safe base64
+    /// let message = "=?UTF-8?B?Pz8_?=";
+    ///
+    /// assert!(Decoder::new().decode(message).is_err());
+    /// assert_eq!(
+    ///     Decoder::new().url_safe_base64_fallback(true).decode(message).unwrap(),
+    ///     "???"
+    /// );
+    /// ```
+    pub fn url_safe_base64_fallback(mut self, fallback: bool) -> Self {
+        self.url_safe_base64_fallback = fallback;
+        self
+    }
+
+    /// Unfold the input like described in RFC 5322: each line break (`\r\n`
+    /// or a bare `\n`) which is followed by a space or a tab is removed
+    /// before the input is lexed, the whitespace after it is kept. Like any
+    /// other whitespace, it's dropped if it's between two encoded words.
+    ///
+    /// Useful if the header value is taken from the raw message, including
+    /// its continuation lines.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "Re:\r\n =?UTF-8?Q?Gr=C3=BC?=\r\n =?UTF-8?Q?=C3=9Fe?=\r\n\taus Bonn";
+    ///
+    /// assert_eq!(
+    ///     Decoder::new().unfold(true).decode(message).unwrap(),
+    ///     "Re: Grüße\taus Bonn"
+    /// );
+    /// ```
+    pub fn unfold(mut self, unfold: bool) -> Self {
+        self.unfold = unfold;
+        self
+    }
+
+    /// Set if an `_` in Q encoded text is decoded to a space, as described in
+    /// the RFC (the default), or kept as a literal `_`. The latter is meant
+    /// for broken senders which encode spaces as `=20` and don't escape
+    /// their underscores.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?Q?snake_case=20name?=";
+    ///
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), "snake case name");
+    /// assert_eq!(
+    ///     Decoder::new().underscore_as_space(false).decode(message).unwrap(),
+    ///     "snake_case name"
+    /// );
+    /// ```
+    pub fn underscore_as_space(mut self, underscore_as_space: bool) -> Self {
+        self.underscore_as_space = underscore_as_space;
+        self
+    }
+
+    /// Decode encoded words which lack their closing `?=`, like the ones of
+    /// truncated headers. The encoded text of such an encoded word runs up to
+    /// the end of the input or to the next encoded word and may contain
+    /// neither spaces nor the delimiter. By default, they are clear text.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?Q?Gr=C3=BC=C3=9Fe=?UTF-8?Q?_aus_K=C3=B6ln";
+    /// let decoder = Decoder::new().allow_missing_suffix(true);
+    ///
+    /// assert_eq!(decoder.decode(message).unwrap(), "Grüße aus Köln");
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), message);
+    /// ```
+    pub fn allow_missing_suffix(mut self, allow: bool) -> Self {
+        self.allow_missing_suffix = allow;
+        self
+    }
+
+    /// Concatenate the transfer decoded bytes of adjacent encoded words with
+    /// the same charset and encoding and decode them with their charset at
+    /// once, instead of decoding each encoded word on its own.
+    ///
+    /// The RFC requires each encoded word to be decodable on its own, but some
+    /// senders split multi-byte characters (or the state of stateful charsets
+    /// like `ISO-2022-JP`) across encoded words. [Decoder::decode_detailed]
+    /// still decodes each encoded word on its own.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // the two bytes of `é` are split across two encoded words
+    /// let message = "=?UTF-8?B?w6c=?= =?UTF-8?B?w6k=?= =?UTF-8?B?ww==?= =?UTF-8?B?qQ==?=";
+    ///
+    /// let decoder = Decoder::new().merge_adjacent_same_charset(true);
+    /// assert_eq!(decoder.decode(message).unwrap(), "çéé");
+    ///
+    /// // by default each encoded word is decoded on its own
+    /// assert_eq!(Decoder::new().decode(message).unwrap(), "çé\u{FFFD}\u{FFFD}");
+    /// ```
+    pub fn merge_adjacent_same_charset(mut self, merge: bool) -> Self {
+        self.merge_adjacent_same_charset = merge;
+        self
+    }
+
+    /// Returns `true` if the decoder knows the given charset label, so
+    /// encoded words with it are decoded with that charset. This includes the
+    /// aliases of the charsets, the charsets of the charset decoder (see
+    /// [Decoder::with_charset_decoder]) and, if enabled, MIB numbers (see
+    /// [Decoder::allow_mib_charset]).
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    ///
+    /// assert!(decoder.charset_is_supported("UTF-8"));
+    /// assert!(decoder.charset_is_supported("latin1"));
+    /// assert!(!decoder.charset_is_supported("x-unknown"));
+    /// ```
+    pub fn charset_is_supported(&self, label: &str) -> bool {
+        evaluator::is_supported_charset(label.as_bytes(), self)
+    }
+
+    /// Returns the encoded words which are longer than
+    /// [Decoder::max_encoded_word_length] together with their byte ranges in
+    /// the input, for example to point at them after a
+    /// [LexerError::ParseEncodedWordTooLongError](crate::LexerError::ParseEncodedWordTooLongError).
+    /// They're found regardless of [Decoder::too_long_encoded_word].
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let too_long_word = format!("=?UTF-8?Q?{}?=", "a".repeat(64));
+    /// let message = format!("a {} =?UTF-8?Q?b?=", too_long_word);
+    ///
+    /// let decoder = Decoder::new();
+    /// assert!(decoder.decode(&message).is_err());
+    /// assert_eq!(
+    ///     decoder.find_too_long_encoded_words(&message).unwrap(),
+    ///     vec![(too_long_word, 2..78)]
+    /// );
+    /// ```
+    pub fn find_too_long_encoded_words<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+    ) -> Result<Vec<(String, Range<usize>)>> {
+        Ok(lexer::find_too_long_encoded_words(
+            encoded_str.as_ref(),
+            self,
+        )?)
+    }
+
+    /// Returns the bytes which should be emitted instead of the given
+    /// undecodable encoded word, which hasn't been decoded because of the
+    /// given strategy (if any).
+    pub(crate) fn undecodable_replacement(
+        &self,
+        encoded_word: &EncodedWord,
+        strategy: Option<&RecoverStrategy>,
+    ) -> Vec<u8> {
+        match self.replacement_text(strategy) {
+            Some(replacement) => replacement.as_bytes().to_vec(),
+            None => encoded_word.original_bytes(self.delimiter),
+        }
+    }
+
+    /// Returns the text which replaces an undecodable encoded word, `None` if
+    /// it's kept verbatim.
+    pub(crate) fn replacement_text<'a>(
+        &'a self,
+        strategy: Option<&'a RecoverStrategy>,
+    ) -> Option<&'a str> {
+        match strategy {
+            Some(RecoverStrategy::Replace(replacement)) => Some(replacement),
+            _ => self.replacement_for_undecodable.as_deref(),
+        }
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string.
+    ///
+    /// The decoder isn't consumed, so it can be reused (or kept in a pool)
+    /// to decode multiple strings with the same settings.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    ///
+    /// assert_eq!(decoder.decode("=?UTF-8?Q?str?=").unwrap(), "str");
+    /// assert_eq!(decoder.decode("=?UTF-8?B?c3Ry?=").unwrap(), "str");
+    /// ```
+    pub fn decode<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<String> {
+        let encoded_str = encoded_str.as_ref();
+
+        // fast path: there's nothing to lex, parse or evaluate
+        if encoded_str.is_empty() {
+            return Ok(String::new());
+        }
+
+        let text_tokens = lexer::run(encoded_str, self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let evaluated_string = evaluator::run(parsed_text, self)?;
+
+        Ok(evaluated_string)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// returns the charset labels of the decoded encoded words together with
+    /// the decoded string. The labels are listed as they are declared, in
+    /// order of appearance, and labels which only differ in case are listed
+    /// once.
+    ///
+    /// Encoded words which are kept as clear text aren't decoded, so their
+    /// charsets aren't listed.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?Q?Gr=C3=BC=C3=9Fe?= =?ISO-8859-1?Q?aus_K=F6ln?= =?utf-8?B?IQ==?=";
+    /// let (decoded_str, charsets) = Decoder::new().decode_with_charsets(message).unwrap();
+    ///
+    /// assert_eq!(decoded_str, "Grüßeaus Köln!");
+    /// assert_eq!(charsets, vec!["UTF-8".to_string(), "ISO-8859-1".to_string()]);
+    /// ```
+    pub fn decode_with_charsets<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+    ) -> Result<(String, Vec<String>)> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+
+        let mut charsets: Vec<String> = Vec::new();
+        for parsed_encoded_word in &parsed_text {
+            if let parser::ParsedEncodedWord::EncodedWord { source, .. } = parsed_encoded_word {
+                let label = String::from_utf8_lossy(&source.charset);
+                if !charsets
+                    .iter()
+                    .any(|charset| charset.eq_ignore_ascii_case(&label))
+                {
+                    charsets.push(label.into_owned());
+                }
+            }
+        }
+
+        let decoded_str = evaluator::run(parsed_text, self)?;
+
+        Ok((decoded_str, charsets))
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string like
+    /// [Decoder::decode], but borrows the input instead of allocating a new
+    /// string if it's decoded to itself: if it's valid UTF-8, doesn't contain
+    /// an encoded word and none of the settings of the decoder change it.
+    ///
+    /// Useful for bulk header processing, where most headers are plain text.
+    ///
+    /// # Example
+    /// ```
+    /// use std::borrow::Cow;
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    ///
+    /// assert!(matches!(decoder.decode_cow(b"Hello World").unwrap(), Cow::Borrowed("Hello World")));
+    /// assert_eq!(
+    ///     decoder.decode_cow(b"Hello =?UTF-8?Q?W=C3=B6rld?=").unwrap(),
+    ///     Cow::<str>::Owned("Hello Wörld".to_string())
+    /// );
+    /// ```
+    pub fn decode_cow<'a>(&self, encoded_bytes: &'a [u8]) -> Result<Cow<'a, str>> {
+        match core::str::from_utf8(encoded_bytes) {
+            Ok(clear_text) if self.is_kept_verbatim(clear_text) => Ok(Cow::Borrowed(clear_text)),
+            _ => self.decode(encoded_bytes).map(Cow::Owned),
+        }
+    }
+
+    /// Returns `true` if the clear text would be decoded to itself: it doesn't
+    /// contain an encoded word and none of the settings for the clear text
+    /// change it.
+    fn is_kept_verbatim(&self, clear_text: &str) -> bool {
+        let has_encoded_word =
+            memchr::memmem::find(clear_text.as_bytes(), encoded_word::PREFIX).is_some();
+        let has_changed_control_char = self.control_char_policy != ControlCharPolicy::Keep
+            && clear_text.contains(|c: char| c.is_control() && c != '\t');
+        let has_quoted_string = self.decode_in_quoted_strings && clear_text.contains('"');
+        let has_char_above_maximum = self
+            .max_codepoint
+            .is_some_and(|max_codepoint| clear_text.chars().any(|c| c > max_codepoint));
+        let is_preprocessed = self.strip_zero_width || self.unfold;
+        let is_too_long = self
+            .max_output_bytes
+            .is_some_and(|max_bytes| clear_text.len() > max_bytes);
+        let has_clear_text_charset =
+            self.clear_text_charset.is_some() || self.charset_override.is_some();
+        #[cfg(feature = "unicode-normalization")]
+        let needs_normalization = self.normalize_nfc && !unicode_normalization::is_nfc(clear_text);
+        #[cfg(not(feature = "unicode-normalization"))]
+        let needs_normalization = false;
+
+        !has_encoded_word
+            && !has_changed_control_char
+            && !has_quoted_string
+            && !has_char_above_maximum
+            && !is_preprocessed
+            && !is_too_long
+            && !has_clear_text_charset
+            && !needs_normalization
+    }
+
+    /// Reads the whole input of the given reader and decodes it like
+    /// [Decoder::decode]. Useful to decode the headers of message files
+    /// without collecting them first.
+    ///
+    /// Requires the `std` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let reader = std::io::BufReader::new("=?UTF-8?Q?Gr=C3=BC=C3=9Fe?=".as_bytes());
+    ///
+    /// assert_eq!(Decoder::new().decode_stream(reader).unwrap(), "Grüße");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn decode_stream<R: Read>(&self, mut reader: R) -> Result<String> {
+        let mut encoded_bytes = Vec::new();
+        reader.read_to_end(&mut encoded_bytes)?;
+
+        self.decode(encoded_bytes)
+    }
+
+    /// Decodes each of the given inputs with [Decoder::decode] and joins the
+    /// decoded strings in order. Stops at the first input which can't be
+    /// decoded.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    /// let fragments = ["=?UTF-8?Q?Hello?=", ", ", "=?UTF-8?B?V29ybGQ=?="];
+    ///
+    /// assert_eq!(decoder.decode_all(fragments).unwrap(), "Hello, World");
+    /// assert_eq!(decoder.decode_all(Vec::<&str>::new()).unwrap(), "");
+    /// ```
+    pub fn decode_all<I>(&self, inputs: I) -> Result<String>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        inputs.into_iter().map(|input| self.decode(input)).collect()
+    }
+
+    /// Decodes each of the given inputs with [Decoder::decode] independently
+    /// and returns the results in the same order as the inputs, so one input
+    /// which can't be decoded doesn't affect the others.
+    ///
+    /// With the `rayon` feature, batches of at least 64 inputs are decoded in
+    /// parallel on the global rayon thread pool. Smaller batches (and all
+    /// batches without the feature) are decoded serially, since for them the
+    /// overhead of distributing the work outweighs the gain.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    /// let results = decoder.decode_batch(&["=?UTF-8?Q?a?=", "=?UTF-8?B?!?=", "c"]);
+    ///
+    /// assert_eq!(results.len(), 3);
+    /// assert_eq!(results[0].as_deref(), Ok("a"));
+    /// assert!(results[1].is_err());
+    /// assert_eq!(results[2].as_deref(), Ok("c"));
+    /// ```
+    pub fn decode_batch<T: AsRef<[u8]> + Sync>(&self, inputs: &[T]) -> Vec<Result<String>> {
+        #[cfg(feature = "rayon")]
+        if inputs.len() >= PARALLEL_BATCH_THRESHOLD {
+            use rayon::prelude::*;
+
+            return inputs.par_iter().map(|input| self.decode(input)).collect();
+        }
+
+        inputs.iter().map(|input| self.decode(input)).collect()
+    }
+
+    /// Decodes the given string up to the first part which can't be decoded
+    /// and returns the decoded prefix. The flag is `true` if decoding stopped
+    /// early because of an error, so the prefix isn't the whole string.
+    ///
+    /// Unlike the [RecoverStrategy]s nothing is skipped or decoded lossily:
+    /// everything after the first error is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    /// let message = "=?UTF-8?Q?Hello?= =?UTF-8?B?!!!?= =?UTF-8?Q?World?=";
+    ///
+    /// assert_eq!(decoder.decode_prefix_until_error(message), ("Hello".to_string(), true));
+    /// assert_eq!(
+    ///     decoder.decode_prefix_until_error("=?UTF-8?Q?Hello?="),
+    ///     ("Hello".to_string(), false)
+    /// );
+    /// ```
+    pub fn decode_prefix_until_error<T: AsRef<[u8]>>(&self, encoded_str: T) -> (String, bool) {
+        let (text_tokens, lexer_stopped_early) = lexer::run_until_error(encoded_str.as_ref(), self);
+        let parsed_text = parser::convert_tokens_to_encoded_words(text_tokens, self);
+        let (decoded_str, stopped_early) = evaluator::run_until_error(parsed_text, self);
+
+        (decoded_str, stopped_early || lexer_stopped_early)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// returns it together with its amount of extended grapheme clusters,
+    /// which is the amount of "characters" a user sees. This is what counts
+    /// when truncating a subject for display, for example.
+    ///
+    /// Requires the `unicode-segmentation` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// // "e" followed by a combining acute accent
+    /// let (decoded_str, grapheme_count) = Decoder::new()
+    ///     .decode_with_grapheme_count("=?UTF-8?Q?Caf=65=CC=81?=")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(decoded_str, "Cafe\u{301}");
+    /// assert_eq!(decoded_str.chars().count(), 5);
+    /// assert_eq!(grapheme_count, 4);
+    /// ```
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn decode_with_grapheme_count<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+    ) -> Result<(String, usize)> {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        let decoded_str = self.decode(encoded_str)?;
+        let grapheme_count = decoded_str.graphemes(true).count();
+
+        Ok((decoded_str, grapheme_count))
+    }
+
+    /// Decodes the leading phrase of the given RFC 2047 MIME Message Header
+    /// encoded string and returns it together with the rest of the input,
+    /// which can then be handed to another parser (an address parser, for
+    /// example).
+    ///
+    /// The phrase ends at the first special char of an address (`<`, `>`,
+    /// `@`, `,`, `;` or `:`) which isn't inside of an encoded word. The
+    /// whitespace in front of it is part of the rest.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let (phrase, rest) = Decoder::new()
+    ///     .decode_leading(b"=?UTF-8?Q?J=C3=B6rg?= Doe <jorg@example.com>")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(phrase, "Jörg Doe");
+    /// assert_eq!(rest, b" <jorg@example.com>");
+    /// ```
+    pub fn decode_leading<'a>(&self, encoded_bytes: &'a [u8]) -> Result<(String, &'a [u8])> {
+        let phrase_len = lexer::leading_phrase_len(encoded_bytes, self)?;
+        let (phrase, rest) = encoded_bytes.split_at(phrase_len);
+
+        Ok((self.decode(phrase)?, rest))
+    }
+
+    /// Decodes the value of a whole header line (like `Subject: ...`) and
+    /// returns it together with the field name.
+    ///
+    /// The line is split at the first colon outside of a quoted string and
+    /// the whitespace around the field name and in front of the value is
+    /// removed. Returns [LexerError::MissingFieldName](crate::LexerError::MissingFieldName)
+    /// if the line doesn't start with a field name.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let (field_name, value) = Decoder::new()
+    ///     .decode_header_field("Subject: =?UTF-8?Q?Gr=C3=BC=C3=9Fe?= aus Bonn")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(field_name, "Subject");
+    /// assert_eq!(value, "Grüße aus Bonn");
+    /// ```
+    pub fn decode_header_field<T: AsRef<[u8]>>(&self, line: T) -> Result<(String, String)> {
+        let (field_name, value) = lexer::split_header_field(line.as_ref())?;
+        let field_name = String::from_utf8_lossy(field_name).into_owned();
+
+        Ok((field_name, self.decode(value)?))
+    }
+
+    /// Decodes the display names of an address list (like the value of a
+    /// `To` or `From` header) and returns them together with their addresses,
+    /// which are kept untouched.
+    ///
+    /// The list is split at the commas outside of angle brackets and quoted
+    /// strings. The display name is `None` if the address isn't in angle
+    /// brackets, and the quotes around a quoted display name are removed.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let addresses = Decoder::new()
+    ///     .decode_address_list(r#"=?UTF-8?Q?J=C3=B6rg?= <jorg@example.com>, "Doe, Jane" <jane@example.com>, bob@example.com"#)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     addresses,
+    ///     vec![
+    ///         (Some("Jörg".to_string()), "jorg@example.com".to_string()),
+    ///         (Some("Doe, Jane".to_string()), "jane@example.com".to_string()),
+    ///         (None, "bob@example.com".to_string()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn decode_address_list<T: AsRef<[u8]>>(
+        &self,
+        line: T,
+    ) -> Result<Vec<(Option<String>, String)>> {
+        lexer::split_address_list(line.as_ref())
+            .into_iter()
+            .map(|(display_name, address)| {
+                let display_name = if display_name.is_empty() {
+                    None
+                } else {
+                    Some(self.decode(lexer::unquote_display_name(display_name))?)
+                };
+                let address = String::from_utf8_lossy(address).into_owned();
+
+                Ok((display_name, address))
+            })
+            .collect()
+    }
+
+    /// Decodes a single encoded word which has already been split into its
+    /// charset, encoding and encoded text, with the settings of this decoder.
+    ///
+    /// Like [crate::decode_encoded_word] no lexing is done, so this is useful
+    /// if the encoded word is embedded in another format. Since there's no
+    /// encoded word to keep verbatim, only the settings of the transfer and
+    /// charset decoding apply.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Decoder, Encoding};
+    ///
+    /// let decoder = Decoder::new();
+    ///
+    /// assert_eq!(decoder.decode_word("UTF-8", Encoding::B, b"w6dh").unwrap(), "ça");
+    /// assert_eq!(decoder.decode_word("ISO-8859-1", Encoding::Q, b"Andr=E9").unwrap(), "André");
+    /// ```
+    pub fn decode_word(
+        &self,
+        charset: &str,
+        encoding: Encoding,
+        encoded_text: &[u8],
+    ) -> Result<String> {
+        let encoding = match encoding {
+            Encoding::B => b'B',
+            Encoding::Q => b'Q',
+            Encoding::Custom(letter) => letter,
+        };
+        let encoded_word = EncodedWord::new(
+            charset.as_bytes().to_vec(),
+            vec![encoding],
+            encoded_text.to_vec(),
+        );
+
+        let parsed_encoded_word =
+            parser::ParsedEncodedWord::convert_encoded_word(encoded_word, self)?;
+        let decoded_str = evaluator::run(vec![parsed_encoded_word], self)?;
+
+        Ok(decoded_str)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// returns the original string (lossily converted to UTF-8) together with
+    /// the decoded one, for example for an audit log.
+    ///
+    /// The original string is the input as the decoder sees it, that is after
+    /// the preprocessing which is enabled for the decoder (like
+    /// [Decoder::strip_zero_width]).
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let (original, decoded) = Decoder::new()
+    ///     .decode_with_original("=?UTF-8?Q?=C3=A7a?= va")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(original, "=?UTF-8?Q?=C3=A7a?= va");
+    /// assert_eq!(decoded, "ça va");
+    /// ```
+    pub fn decode_with_original<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<(String, String)> {
+        let encoded_bytes = lexer::preprocess(encoded_str.as_ref(), self);
+        let decoded_str = self.decode(&encoded_bytes)?;
+        let original_str = String::from_utf8_lossy(&encoded_bytes).into_owned();
+
+        Ok((original_str, decoded_str))
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string into a
+    /// `Box<str>`, which doesn't waste memory on unused capacity like a
+    /// `String` might. Useful if many decoded headers are kept around.
+    ///
+    /// The decoded string is assembled in a buffer of exactly its length, so
+    /// no reallocation is needed to box it.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoded_str: Box<str> = Decoder::new().decode_boxed("=?UTF-8?Q?=C3=A7a?= va").unwrap();
+    ///
+    /// assert_eq!(&*decoded_str, "ça va");
+    /// ```
+    pub fn decode_boxed<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<Box<str>> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let decoded_str = evaluator::run_boxed(parsed_text, self)?;
+
+        Ok(decoded_str)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string lazily:
+    /// each clear text and encoded word is only decoded when the iterator
+    /// reaches it, so the whole decoded string is never built up front.
+    ///
+    /// An error of a single encoded word is yielded as an `Err` item and the
+    /// iteration goes on with the next one, so the caller can skip only the
+    /// broken word. If the input can't be lexed, the only item is the error.
+    /// The encoded words which [Decoder::merge_adjacent_same_charset] merges
+    /// are decoded together as a single item, like with [Decoder::decode].
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    /// let decoded_parts: Vec<_> = decoder
+    ///     .decode_iter("=?UTF-8?Q?a?= =?UTF-8?B?!?= =?UTF-8?B?Yg==?= c")
+    ///     .collect();
+    ///
+    /// assert_eq!(decoded_parts.len(), 4);
+    /// assert_eq!(decoded_parts[0].as_deref(), Ok("a"));
+    /// assert!(decoded_parts[1].is_err());
+    /// assert_eq!(decoded_parts[2].as_deref(), Ok("b"));
+    /// assert_eq!(decoded_parts[3].as_deref(), Ok(" c"));
+    /// ```
+    pub fn decode_iter<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+    ) -> impl Iterator<Item = Result<String>> + '_ {
+        let (text_tokens, lexer_error) = match lexer::run(encoded_str.as_ref(), self) {
+            Ok(text_tokens) => (text_tokens, None),
+            Err(err) => (Vec::new(), Some(Error::Lexer(err))),
+        };
+        let mut output_check = evaluator::OutputCheck::new(self);
+
+        let parsed_encoded_words = parser::convert_tokens_to_encoded_words(text_tokens, self);
+        let decoded_parts =
+            evaluator::group_parsed_words(parsed_encoded_words, self).map(move |group| {
+                let decoded_part = evaluator::decode_group(group?, self)?;
+                Ok(output_check.check(decoded_part)?)
+            });
+
+        lexer_error.map(Err).into_iter().chain(decoded_parts)
+    }
+
+    /// Returns the length in bytes of the string which [Decoder::decode]
+    /// would return for the given input.
+    ///
+    /// This does the same work as [Decoder::decode] (including the charset
+    /// decoding, otherwise the length wouldn't be accurate), except that the
+    /// decoded parts aren't concatenated into the final `String`.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let message = "=?UTF-8?Q?=C3=A7a?= va";
+    ///
+    /// assert_eq!(Decoder::new().decode_count_bytes(message).unwrap(), 6);
+    /// assert_eq!(Decoder::new().decode(message).unwrap().len(), 6);
+    /// ```
+    pub fn decode_count_bytes<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<usize> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let amount_bytes = evaluator::count_bytes(parsed_text, self)?;
+
+        Ok(amount_bytes)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// appends the decoded string to the given buffer. The buffer can be
+    /// reused across calls (after clearing it) to avoid an allocation per
+    /// call.
+    ///
+    /// The buffer is left untouched if an error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoder = Decoder::new();
+    /// let mut buffer = String::new();
+    ///
+    /// for (message, expected) in [("=?UTF-8?Q?=C3=A7a?= va", "ça va"), ("=?UTF-8?B?c3Ry?=", "str")] {
+    ///     buffer.clear();
+    ///     decoder.decode_into(message, &mut buffer).unwrap();
+    ///     assert_eq!(buffer, expected);
+    /// }
+    /// ```
+    pub fn decode_into<T: AsRef<[u8]>>(&self, encoded_str: T, buffer: &mut String) -> Result<()> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        evaluator::run_into_string(parsed_text, self, buffer)?;
+
+        Ok(())
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// appends the decoded UTF-8 bytes to the given buffer. Returns the amount
+    /// of appended bytes.
+    ///
+    /// The buffer is left untouched if an error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let mut buffer = b"Subject: ".to_vec();
+    /// let appended = Decoder::new()
+    ///     .decode_bytes_into("=?UTF-8?Q?=C3=A7a?= va", &mut buffer)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(appended, 6);
+    /// assert_eq!(buffer, "Subject: ça va".as_bytes());
+    /// ```
+    pub fn decode_bytes_into<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+        buffer: &mut Vec<u8>,
+    ) -> Result<usize> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let amount_bytes = evaluator::run_into(parsed_text, self, buffer)?;
+
+        Ok(amount_bytes)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// writes the decoded string to the writer, but not more than
+    /// `max_bytes` bytes of it. Returns `true` if the decoded string had
+    /// to be truncated.
+    ///
+    /// The decoded string is only truncated at `char` boundaries, so the
+    /// written bytes are always valid UTF-8, even if that means writing less
+    /// than `max_bytes` bytes. Decoding errors are returned as
+    /// [io::ErrorKind::InvalidData], before anything is written.
+    ///
+    /// Requires the `std` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let truncated = Decoder::new()
+    ///     .decode_to_writer_with_limit("=?UTF-8?Q?=C3=A7a?= va", &mut buffer, 4)
+    ///     .unwrap();
+    ///
+    /// assert!(truncated);
+    /// assert_eq!(buffer, "ça ".as_bytes());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn decode_to_writer_with_limit<T, W>(
+        &self,
+        encoded_str: T,
+        writer: &mut W,
+        max_bytes: usize,
+    ) -> io::Result<bool>
+    where
+        T: AsRef<[u8]>,
+        W: Write,
+    {
+        let decoded_str = self
+            .decode(encoded_str)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let mut end = max_bytes.min(decoded_str.len());
+        while !decoded_str.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        writer.write_all(&decoded_str.as_bytes()[..end])?;
+        Ok(end < decoded_str.len())
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string into the
+    /// raw decoded bytes: the encoded words are only transfer decoded (B or
+    /// Q), their charsets aren't decoded, and the clear text is kept as it
+    /// is. This allows to decode the bytes with another charset or to apply
+    /// another policy for invalid bytes.
+    ///
+    /// Since no charset is decoded, the settings for the charset decoding and
+    /// for the decoded string (like [Decoder::control_char_policy]) don't
+    /// apply.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let decoded_bytes = Decoder::new()
+    ///     .decode_to_bytes(b"=?ISO-8859-1?Q?Andr=E9?= =?UTF-8?B?w6k=?= \xff")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(decoded_bytes, b"Andr\xe9\xc3\xa9 \xff");
+    /// ```
+    pub fn decode_to_bytes<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<Vec<u8>> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let decoded_bytes = evaluator::run_to_bytes(parsed_text, self)?;
+
+        Ok(decoded_bytes)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// reports some information about the decoded string.
+    ///
+    /// See [DecodeReport] for an example.
+    pub fn decode_report<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<DecodeReport> {
+        let segments = self.decode_into_vec_segments(encoded_str)?;
+        Ok(DecodeReport::from_segments(segments, self))
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// lists the encoded words for which a [RecoverStrategy] has been applied,
+    /// so a fully decoded string can be told apart from a recovered one.
+    ///
+    /// Only encoded words are listed, the clear text (see
+    /// [ErrorClass::InvalidUtf8]) isn't.
+    ///
+    /// See [DecodeOutcome] for an example.
+    pub fn decode_verbose<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<DecodeOutcome> {
+        let encoded_bytes = encoded_str.as_ref();
+        let text = self.decode(encoded_bytes)?;
+
+        // keeps every encoded word as it is, so none of them is turned into
+        // clear text by a recover strategy before it's checked
+        let strict_decoder = self.clone().recover_all(RecoverStrategy::Abort);
+        let lexing_decoder = strict_decoder
+            .clone()
+            .too_long_encoded_word_strategy(RecoverStrategy::Decode);
+
+        let (tokens, spans): (Vec<Token>, Vec<_>) =
+            lexer::run_spanned(encoded_bytes, &lexing_decoder)?
+                .into_iter()
+                .unzip();
+        let too_long_words: Vec<bool> = tokens
+            .iter()
+            .map(|token| {
+                matches!(token, Token::EncodedWord(_)) && token.len() > self.max_encoded_word_length
+            })
+            .collect();
+
+        // all tokens are converted at once, so the charset overrides apply to
+        // the right encoded words
+        let parsed_words = parser::convert_tokens_to_encoded_words(tokens, &strict_decoder);
+
+        let mut recovered = Vec::new();
+        for ((parsed_word, span), is_too_long) in parsed_words.zip(spans).zip(too_long_words) {
+            let class = match parsed_word {
+                Ok(parser::ParsedEncodedWord::ClearText(_)) => continue,
+                _ if is_too_long => Some(ErrorClass::TooLongEncodedWord),
+                Ok(parsed_word) => evaluator::decode_parsed_word(parsed_word, &strict_decoder)
+                    .err()
+                    .and_then(|err| Error::Evaluator(err).class()),
+                Err(err) => Error::Parser(err).class(),
+            };
+            let source = &encoded_bytes[span];
+
+            if let Some(class) = class {
+                let strategy = self.recover_strategy(class);
+                if strategy != RecoverStrategy::Abort {
+                    recovered.push(RecoveredWord {
+                        encoded_word: String::from_utf8_lossy(source).into_owned(),
+                        class,
+                        strategy,
+                    });
+                }
+            }
+        }
+
+        Ok(DecodeOutcome { text, recovered })
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string into
+    /// owned segments, one per clear text and encoded word.
+    ///
+    /// See [DecodedSegment] for an example.
+    pub fn decode_into_vec_segments<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+    ) -> Result<Vec<DecodedSegment>> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let segments = evaluator::run_segments(parsed_text, self)?;
+
+        Ok(segments)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// returns the [Segment]s it's made of: the byte range of each clear text
+    /// and encoded word in the input, for example to highlight the encoded
+    /// words.
+    ///
+    /// See [Segment] for an example.
+    pub fn decode_with_diagnostics<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+    ) -> Result<(String, Vec<Segment>)> {
+        let (text_tokens, spans) = lexer::run_spanned(encoded_str.as_ref(), self)?
+            .into_iter()
+            .unzip();
+        let parsed_text = parser::run(text_tokens, self)?;
+        let segments = evaluator::segments(&parsed_text, spans);
+        let decoded_str = evaluator::run(parsed_text, self)?;
+
+        Ok((decoded_str, segments))
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// splits it on the given delimiter, for example to split an address list
+    /// on `,`.
+    ///
+    /// Only the clear text is split: a delimiter inside an encoded word, or
+    /// one which an encoded word decodes to, doesn't split the string.
+    ///
+    /// Since a delimiter is usually followed by a space (like in `a, b`), the
+    /// whitespace around each part is trimmed.
+    ///
+    /// The delimiter has to be an ASCII byte, since the clear text is split
+    /// after decoding it, where a non-ASCII byte isn't a char of its own.
+    /// Otherwise [Error::NonAsciiDelimiter] is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::Decoder;
+    ///
+    /// let parts = Decoder::new()
+    ///     .decode_split("=?UTF-8?Q?a,b?=, plain", b',')
+    ///     .unwrap();
+    ///
+    /// assert_eq!(parts, vec!["a,b", "plain"]);
+    /// ```
+    pub fn decode_split<T: AsRef<[u8]>>(
+        &self,
+        encoded_str: T,
+        delimiter: u8,
+    ) -> Result<Vec<String>> {
+        if !delimiter.is_ascii() {
+            return Err(Error::NonAsciiDelimiter(delimiter));
+        }
+
+        let delimiter = char::from(delimiter);
+        let mut parts = vec![String::new()];
+
+        for segment in self.decode_into_vec_segments(encoded_str)? {
+            let mut pieces = match segment.kind {
+                SegmentKind::Text => segment.decoded.split(delimiter).collect::<Vec<_>>(),
+                SegmentKind::Word => vec![segment.decoded.as_str()],
+            }
+            .into_iter();
+
+            if let (Some(last_part), Some(first_piece)) = (parts.last_mut(), pieces.next()) {
+                last_part.push_str(first_piece);
+            }
+            parts.extend(pieces.map(str::to_string));
+        }
+
+        Ok(parts.iter().map(|part| part.trim().to_string()).collect())
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string and
+    /// returns every intermediate result of each encoded word, which is
+    /// useful to find out why the decoded string looks wrong (is it the
+    /// base64, the charset or the label?). Clear text isn't included.
+    ///
+    /// See [DecodedWord] for an example.
+    pub fn decode_detailed<T: AsRef<[u8]>>(&self, encoded_str: T) -> Result<Vec<DecodedWord>> {
+        let text_tokens = lexer::run(encoded_str.as_ref(), self)?;
+        let parsed_text = parser::run(text_tokens, self)?;
+        let decoded_words = evaluator::run_detailed(parsed_text, self)?;
+
+        Ok(decoded_words)
+    }
+
+    /// Decodes the given RFC 2047 MIME Message Header encoded string on the
+    /// blocking thread pool of tokio, so the async runtime isn't blocked.
+    ///
+    /// Decoding is CPU-bound and synchronous, so offloading it is only
+    /// worthwhile for large inputs: for a usual header the overhead of
+    /// spawning the task is bigger than decoding it directly with
+    /// [Decoder::decode]. Multiple headers can be decoded concurrently by
+    /// awaiting multiple calls at once.
+    ///
+    /// Requires the `async` feature and must be called within a tokio runtime.
+    ///
+    /// # Errors
+    /// Besides the errors of [Decoder::decode], returns [Error::Io] with
+    /// [io::ErrorKind::Interrupted] if the decoding task has been cancelled,
+    /// for example because the runtime is shutting down.
+    ///
+    /// # Panics
+    /// Resumes the panic if the decoding task panicked.
+    #[cfg(feature = "async")]
+    pub async fn decode_async<T>(self, encoded_str: T) -> Result<String>
+    where
+        T: AsRef<[u8]> + Send + 'static,
+    {
+        match tokio::task::spawn_blocking(move || self.decode(encoded_str)).await {
+            Ok(decoded_str) => decoded_str,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(io::Error::new(io::ErrorKind::Interrupted, err).into()),
+        }
+    }
+}
+
+impl Default for Decoder {
+    /// Returns the decoder with the following default "settings":
+    ///
+    /// - `too_long_encoded_word`: [RecoverStrategy::Abort]
+    /// - `invalid_base64`: [RecoverStrategy::Abort]
+    /// - `invalid_quoted_printable`: [RecoverStrategy::Decode]
+    /// - `unknown_charset`: [RecoverStrategy::Decode]
+    /// - `invalid_utf8`: [RecoverStrategy::Abort]
+    /// - `invalid_encoding`: [RecoverStrategy::Abort]
+    /// - `decodable_charsets`: `None` (every charset is decoded)
+    /// - `lenient_encoded_text`: `false`
+    /// - `allow_mib_charset`: `false`
+    /// - `replacement_for_undecodable`: `None` (keep undecodable encoded words verbatim)
+    /// - `strict_word_boundaries`: `false`
+    /// - `require_whitespace_after_word`: `false`
+    /// - `delimiter`: `b'?'`
+    /// - `charset_overrides`: empty
+    /// - `control_char_policy`: [ControlCharPolicy::Keep]
+    /// - `transfer_decoders`: empty
+    /// - `charset_fallback_chain`: empty
+    /// - `clear_text_charset`: `None` (UTF-8)
+    /// - `header_context`: [HeaderContext::Unstructured]
+    /// - `merge_adjacent_same_charset`: `false`
+    /// - `decode_in_quoted_strings`: `false`
+    /// - `strip_zero_width`: `false`
+    /// - `max_codepoint`: `None`
+    /// - `max_encoded_word_length`: `75`
+    /// - `collapse_adjacent_whitespace`: `true`
+    /// - `malformed_quoted_printable`: [MalformedEscapePolicy::Keep]
+    /// - `trim_field_whitespace`: `false`
+    /// - `charset_override`: `None`
+    /// - `url_safe_base64_fallback`: `false`
+    /// - `unfold`: `false`
+    /// - `underscore_as_space`: `true`
+    /// - `allow_missing_suffix`: `false`
+    /// - `charset_aliases`: a few common misspellings, see [Decoder::charset_alias]
+    /// - `max_output_bytes`: `None`
+    /// - `strict_q_hex_case`: `false`
+    /// - `strip_bom`: `false`
+    /// - `charset_decoder`: `None`
+    /// - `detect_charset`: `false` (requires the `chardetng` feature)
+    /// - `normalize_nfc`: `false` (requires the `unicode-normalization` feature)
+    fn default() -> Self {
+        Self {
+            too_long_encoded_word: RecoverStrategy::Abort,
+            invalid_base64: RecoverStrategy::Abort,
+            invalid_quoted_printable: RecoverStrategy::Decode,
+            unknown_charset: RecoverStrategy::Decode,
+            invalid_utf8: RecoverStrategy::Abort,
+            invalid_encoding: RecoverStrategy::Abort,
+            decodable_charsets: None,
+            lenient_encoded_text: false,
+            allow_mib_charset: false,
+            replacement_for_undecodable: None,
+            strict_word_boundaries: false,
+            require_whitespace_after_word: false,
+            delimiter: QUESTION_MARK,
+            charset_overrides: BTreeMap::new(),
+            control_char_policy: ControlCharPolicy::Keep,
+            transfer_decoders: BTreeMap::new(),
+            charset_fallback_chain: Vec::new(),
+            clear_text_charset: None,
+            header_context: HeaderContext::Unstructured,
+            merge_adjacent_same_charset: false,
+            decode_in_quoted_strings: false,
+            strip_zero_width: false,
+            max_codepoint: None,
+            max_encoded_word_length: encoded_word::MAX_LENGTH,
+            collapse_adjacent_whitespace: true,
+            malformed_quoted_printable: MalformedEscapePolicy::Keep,
+            trim_field_whitespace: false,
+            charset_override: None,
+            url_safe_base64_fallback: false,
+            unfold: false,
+            underscore_as_space: true,
+            allow_missing_suffix: false,
+            charset_aliases: parser::DEFAULT_CHARSET_ALIASES
+                .iter()
+                .map(|&(alias, charset)| (alias.to_string(), charset.to_string()))
+                .collect(),
+            max_output_bytes: None,
+            strict_q_hex_case: false,
+            strip_bom: false,
+            charset_decoder: None,
+            #[cfg(feature = "chardetng")]
+            detect_charset: false,
+            #[cfg(feature = "unicode-normalization")]
+            normalize_nfc: false,
+        }
+    }
+}
+
+/// Generates a valid configuration: charset labels are taken from a list of
+/// known ones, the delimiter is one which broken mailers actually use and no
+/// transfer decoders are registered.
+///
+/// Requires the `arbitrary` feature.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Decoder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        const CHARSETS: &[&str] = &["UTF-8", "ISO-8859-1", "windows-1252", "KOI8-R", "Shift_JIS"];
+        const DELIMITERS: &[u8] = &[QUESTION_MARK, b'*', b'#', b'!'];
+
+        let arbitrary_charset = |u: &mut arbitrary::Unstructured<'a>| -> arbitrary::Result<String> {
+            Ok(u.choose(CHARSETS)?.to_string())
+        };
+
+        let mut decoder = Self::new();
+        for class in Self::ERROR_CLASSES {
+            decoder = decoder.recover_for(class, u.arbitrary()?);
+        }
+
+        if u.arbitrary()? {
+            let decodable_charsets = (0..u.int_in_range(0..=2)?)
+                .map(|_| arbitrary_charset(u))
+                .collect::<arbitrary::Result<_>>()?;
+            decoder.decodable_charsets = Some(decodable_charsets);
+        }
+        decoder.lenient_encoded_text = u.arbitrary()?;
+        decoder.allow_mib_charset = u.arbitrary()?;
+        decoder.replacement_for_undecodable = u.arbitrary()?;
+        decoder.strict_word_boundaries = u.arbitrary()?;
+        decoder.require_whitespace_after_word = u.arbitrary()?;
+        decoder.delimiter = *u.choose(DELIMITERS)?;
+        for _ in 0..u.int_in_range(0..=2)? {
+            let index = u.int_in_range(0..=4)?;
+            decoder
+                .charset_overrides
+                .insert(index, arbitrary_charset(u)?);
+        }
+        decoder.control_char_policy = u.arbitrary()?;
+        for _ in 0..u.int_in_range(0..=2)? {
+            decoder.charset_fallback_chain.push(arbitrary_charset(u)?);
+        }
+        decoder.clear_text_charset = if u.arbitrary()? {
+            Some(arbitrary_charset(u)?)
+        } else {
+            None
+        };
+        decoder.header_context = u.arbitrary()?;
+        decoder.merge_adjacent_same_charset = u.arbitrary()?;
+        decoder.decode_in_quoted_strings = u.arbitrary()?;
+        decoder.strip_zero_width = u.arbitrary()?;
+        decoder.max_codepoint = u.arbitrary()?;
+        decoder.max_encoded_word_length = u.int_in_range(encoded_word::MAX_LENGTH..=998)?;
+        decoder.collapse_adjacent_whitespace = u.arbitrary()?;
+        decoder.malformed_quoted_printable = u.arbitrary()?;
+        decoder.trim_field_whitespace = u.arbitrary()?;
+        decoder.charset_override = if u.arbitrary()? {
+            Some(arbitrary_charset(u)?)
+        } else {
+            None
+        };
+        decoder.url_safe_base64_fallback = u.arbitrary()?;
+        decoder.unfold = u.arbitrary()?;
+        decoder.underscore_as_space = u.arbitrary()?;
+        decoder.allow_missing_suffix = u.arbitrary()?;
+        if u.arbitrary()? {
+            decoder = decoder.charset_alias("x-arbitrary", &arbitrary_charset(u)?);
+        }
+        decoder.max_output_bytes = u.arbitrary()?;
+        decoder.strict_q_hex_case = u.arbitrary()?;
+        decoder.strip_bom = u.arbitrary()?;
+        #[cfg(feature = "chardetng")]
+        {
+            decoder.detect_charset = u.arbitrary()?;
+        }
+        #[cfg(feature = "unicode-normalization")]
+        {
+            decoder.normalize_nfc = u.arbitrary()?;
+        }
+
+        Ok(decoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Here are the main-tests which are listed here:
+    /// https://datatracker.ietf.org/doc/html/rfc2047#section-8
+    /// Scroll down until you see the table.
+    mod rfc_tests {
+        use crate::decode;
+
+        #[test]
+        fn decode_encoded_word_single_char() {
+            assert_eq!(decode("=?ISO-8859-1?Q?a?=").unwrap(), "a");
+        }
+
+        #[test]
+        fn decode_encoded_word_separated_by_whitespace() {
+            assert_eq!(decode("=?ISO-8859-1?Q?a?= b").unwrap(), "a b");
+        }
+
+        #[test]
+        fn decode_two_encoded_chars() {
+            assert_eq!(
+                decode("=?ISO-8859-1?Q?a?= =?ISO-8859-1?Q?b?=").unwrap(),
+                "ab"
+            );
+        }
+
+        #[test]
+        fn whitespace_between_two_encoded_words_should_be_ignored() {
+            assert_eq!(
+                decode("=?ISO-8859-1?Q?a?=  =?ISO-8859-1?Q?b?=").unwrap(),
+                "ab"
+            );
+        }
+
+        #[test]
+        fn whitespace_chars_between_two_encoded_words_should_be_ignored() {
+            assert_eq!(
+                decode(
+                    "=?ISO-8859-1?Q?a?=               
+                     =?ISO-8859-1?Q?b?="
+                )
+                .unwrap(),
+                "ab"
+            );
+        }
+
+        #[test]
+        fn whitespace_encoded_in_encoded_word() {
+            assert_eq!(decode("=?ISO-8859-1?Q?a_b?=").unwrap(), "a b");
+        }
+
+        #[test]
         fn ignore_whitespace_between_two_encoded_words_but_not_the_encoded_whitespace() {
             assert_eq!(
-                decode("=?ISO-8859-1?Q?a?= =?ISO-8859-2?Q?_b?=").unwrap(),
+                decode("=?ISO-8859-1?Q?a?= =?ISO-8859-2?Q?_b?=").unwrap(),
+                "a b"
+            );
+        }
+    }
+
+    /// Those are some custom tests
+    mod custom_tests {
+        use alloc::borrow::Cow;
+
+        use crate::{
+            decode, decode_encoded_word, ControlCharPolicy, DecodedSegment, Decoder, Encoding,
+            Error, ErrorClass, EvaluatorError, HeaderContext, LexerError, MalformedEscapePolicy,
+            ParserError, RecoverStrategy, SegmentKind,
+        };
+
+        #[test]
+        fn clear_empty() {
+            assert_eq!(decode("").unwrap(), "");
+        }
+
+        #[test]
+        fn clear_empty_bytes() {
+            assert_eq!(decode(b"").unwrap(), "");
+        }
+
+        #[test]
+        fn clear_whitespaces_only() {
+            assert_eq!(decode(" ").unwrap(), " ");
+            assert_eq!(decode(" \t ").unwrap(), " \t ");
+            assert_eq!(decode("\r\n ").unwrap(), "\r\n ");
+        }
+
+        #[test]
+        fn clear_with_spaces() {
+            assert_eq!(decode("str with spaces").unwrap(), "str with spaces");
+        }
+
+        #[test]
+        fn utf8_qs_empty() {
+            assert_eq!(decode("").unwrap(), "");
+        }
+
+        #[test]
+        fn utf8_qs_with_str() {
+            assert_eq!(decode("=?UTF-8?Q?str?=").unwrap(), "str");
+        }
+
+        #[test]
+        fn utf8_qs_with_spaces() {
+            assert_eq!(
+                decode("=?utf8?q?str_with_spaces?=").unwrap(),
+                "str with spaces"
+            );
+        }
+
+        #[test]
+        fn utf8_qs_with_spec_chars() {
+            assert_eq!(
+                decode("=?utf8?q?str_with_special_=C3=A7h=C3=A0r=C3=9F?=").unwrap(),
+                "str with special çhàrß"
+            );
+        }
+
+        #[test]
+        fn utf8_qs_double() {
+            assert_eq!(
+                decode("=?UTF-8?Q?str?=\r\n =?UTF-8?Q?str?=").unwrap(),
+                "strstr"
+            );
+            assert_eq!(
+                decode("=?UTF-8?Q?str?=\n =?UTF-8?Q?str?=").unwrap(),
+                "strstr"
+            );
+            assert_eq!(decode("=?UTF-8?Q?str?= =?UTF-8?Q?str?=").unwrap(), "strstr");
+            assert_eq!(decode("=?UTF-8?Q?str?==?UTF-8?Q?str?=").unwrap(), "strstr");
+        }
+
+        #[test]
+        fn utf8_b64_empty() {
+            assert_eq!(decode("=?UTF-8?B??=").unwrap(), "");
+        }
+
+        #[test]
+        fn utf8_b64_with_str() {
+            assert_eq!(decode("=?UTF-8?B?c3Ry?=").unwrap(), "str");
+        }
+
+        #[test]
+        fn utf8_b64_with_spaces() {
+            assert_eq!(
+                decode("=?utf8?b?c3RyIHdpdGggc3BhY2Vz?=").unwrap(),
+                "str with spaces"
+            );
+        }
+
+        #[test]
+        fn utf8_b64_with_spec_chars() {
+            assert_eq!(
+                decode("=?utf8?b?c3RyIHdpdGggc3BlY2lhbCDDp2jDoHLDnw==?=").unwrap(),
+                "str with special çhàrß"
+            );
+        }
+
+        #[test]
+        fn decode_encoded_word_fields() {
+            assert_eq!(
+                decode_encoded_word(b"ISO-8859-1", b'Q', b"Andr=E9").unwrap(),
+                "André"
+            );
+            assert_eq!(decode_encoded_word(b"utf-8", b'b', b"c3Ry").unwrap(), "str");
+        }
+
+        #[test]
+        fn decode_encoded_word_invalid_encoding() {
+            assert!(decode_encoded_word(b"UTF-8", b'X', b"str").is_err());
+        }
+
+        #[test]
+        fn decode_only_whitelisted_charsets() {
+            let decoder = Decoder::new().decode_only_charsets(&["utf-8"]);
+
+            assert_eq!(
+                decoder
+                    .decode("=?UTF-8?Q?caf=C3=A9?= =?KOI8-R?B?8NLJ18XU?=")
+                    .unwrap(),
+                "café =?KOI8-R?B?8NLJ18XU?="
+            );
+        }
+
+        #[test]
+        fn count_bytes_equals_decoded_len() {
+            let messages = [
+                "",
+                "str with spaces",
+                "=?ISO-8859-1?Q?Andr=E9?= Pirard",
+                "=?utf8?b?c3RyIHdpdGggc3BlY2lhbCDDp2jDoHLDnw==?=",
+            ];
+
+            for message in messages {
+                assert_eq!(
+                    Decoder::new().decode_count_bytes(message).unwrap(),
+                    decode(message).unwrap().len()
+                );
+            }
+        }
+
+        #[test]
+        fn recover_all_skip_broken_header() {
+            let message = [
+                "=?UTF-8?X?invalid_encoding?= ".as_bytes(),
+                "=?UTF-8?B?!!!?= ".as_bytes(),
+                "=?UNKNOWN?Q?charset?= ".as_bytes(),
+                "=?UTF-8?Q?lowercase=c3=a7?= ".as_bytes(),
+                "=?UTF-8?Q?aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa?= "
+                    .as_bytes(),
+                b"\xff",
+                "=?UTF-8?Q?valid?=".as_bytes(),
+            ]
+            .concat();
+
+            assert!(decode(&message).is_err());
+
+            let decoder = Decoder::new().recover_all(RecoverStrategy::Skip);
+            assert_eq!(
+                decoder.decode(&message).unwrap(),
+                concat!(
+                    "=?UTF-8?X?invalid_encoding?= ",
+                    "=?UTF-8?B?!!!?= ",
+                    "=?UNKNOWN?Q?charset?= ",
+                    "=?UTF-8?Q?lowercase=c3=a7?= ",
+                    "=?UTF-8?Q?aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa?= ",
+                    "valid"
+                )
+            );
+        }
+
+        #[test]
+        fn recover_all_decode_broken_header() {
+            let message = b"=?UTF-8?B?c3R!y?= =?UNKNOWN?Q?ascii?= \xff";
+            let decoder = Decoder::new().recover_all(RecoverStrategy::Decode);
+
+            assert_eq!(decoder.decode(message).unwrap(), "strascii \u{FFFD}");
+        }
+
+        #[test]
+        fn replacement_for_undecodable() {
+            let decoder = Decoder::new()
+                .recover_all(RecoverStrategy::Skip)
+                .replacement_for_undecodable("[unreadable]".to_string());
+
+            assert_eq!(
+                decoder
+                    .decode("=?UTF-8?Q?a?= and =?UTF-8?B?!!!?= =?UTF-8?X?b?=")
+                    .unwrap(),
+                "a and [unreadable] [unreadable]"
+            );
+        }
+
+        #[test]
+        fn decode_detailed_b_word() {
+            let decoded_words = Decoder::new()
+                .decode_detailed("Subject: =?utf-8?b?c3RyIHdpdGggc3BlY2lhbCDDp2jDoHLDnw==?=")
+                .unwrap();
+
+            assert_eq!(decoded_words.len(), 1);
+            assert_eq!(decoded_words[0].charset_label, "utf-8");
+            assert_eq!(decoded_words[0].encoding, Encoding::B);
+            assert_eq!(
+                decoded_words[0].raw_encoded_text,
+                b"c3RyIHdpdGggc3BlY2lhbCDDp2jDoHLDnw=="
+            );
+            assert_eq!(
+                decoded_words[0].transfer_decoded_bytes.as_deref(),
+                Some("str with special çhàrß".as_bytes())
+            );
+            assert_eq!(decoded_words[0].final_text, "str with special çhàrß");
+        }
+
+        #[test]
+        fn decode_into_vec_segments() {
+            let segments = Decoder::new()
+                .decode_into_vec_segments("=?UTF-8?Q?a?= =?ISO-8859-1?Q?b?= c")
+                .unwrap();
+
+            assert_eq!(
+                segments,
+                vec![
+                    DecodedSegment {
+                        kind: SegmentKind::Word,
+                        decoded: "a".to_string(),
+                        charset: Some("UTF-8".to_string()),
+                    },
+                    DecodedSegment {
+                        kind: SegmentKind::Word,
+                        decoded: "b".to_string(),
+                        charset: Some("ISO-8859-1".to_string()),
+                    },
+                    DecodedSegment {
+                        kind: SegmentKind::Text,
+                        decoded: " c".to_string(),
+                        charset: None,
+                    },
+                ]
+            );
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn serialize_segments() {
+            let segments = Decoder::new()
+                .decode_into_vec_segments("=?UTF-8?Q?a?= b")
+                .unwrap();
+
+            let json = serde_json::to_string(&segments).unwrap();
+            assert_eq!(
+                json,
+                r#"[{"kind":"word","decoded":"a","charset":"UTF-8"},{"kind":"text","decoded":" b","charset":null}]"#
+            );
+
+            let deserialized: Vec<DecodedSegment> = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized, segments);
+        }
+
+        #[test]
+        fn report_first_non_ascii() {
+            let report = Decoder::new()
+                .decode_report("Re: =?ISO-8859-1?Q?Andr=E9?= Pirard")
+                .unwrap();
+            assert_eq!(report.decoded, "Re: André Pirard");
+            assert_eq!(report.first_non_ascii, Some(8));
+
+            let report = Decoder::new().decode_report("=?UTF-8?Q?ascii?=").unwrap();
+            assert_eq!(report.first_non_ascii, None);
+        }
+
+        #[test]
+        fn report_dominant_charset() {
+            let report = Decoder::new()
+                .decode_report("=?ISO-8859-1?Q?Andr=E9?= =?UTF-8?Q?=C3=A7a_va_bien?= =?utf-8?Q?!?=")
+                .unwrap();
+            assert_eq!(report.decoded, "Andréça va bien!");
+            assert_eq!(report.dominant_charset.as_deref(), Some("UTF-8"));
+
+            let report = Decoder::new()
+                .decode_report("=?ISO-8859-1?Q?ab?= =?UTF-8?Q?cd?=")
+                .unwrap();
+            assert_eq!(report.dominant_charset.as_deref(), Some("ISO-8859-1"));
+
+            let report = Decoder::new().decode_report("no encoded words").unwrap();
+            assert_eq!(report.dominant_charset, None);
+        }
+
+        #[test]
+        fn mib_charset() {
+            let decoder = Decoder::new().allow_mib_charset(true);
+
+            assert_eq!(decoder.decode("=?4?Q?Andr=E9?=").unwrap(), "André");
+            assert_eq!(decoder.decode("=?106?B?w6dh?=").unwrap(), "ça");
+            assert_eq!(decoder.decode("=?2251?Q?=CF=F0=E8?=").unwrap(), "При");
+
+            // without the option, the charset is unknown
+            assert_eq!(decode("=?4?Q?Andr=E9?=").unwrap(), "Andr\u{FFFD}");
+        }
+
+        #[test]
+        fn hz_gb_2312() {
+            assert_eq!(
+                decode("=?HZ-GB-2312?Q?~{<:Ky2;S{#,NpJ)l6HK!#~}Bye.?=").unwrap(),
+                "己所不欲，勿施於人。Bye."
+            );
+            assert_eq!(
+                decode("=?hz-gb-2312?B?SGVsbG8gfntWUE5E?=").unwrap(),
+                "Hello 中文"
+            );
+        }
+
+        #[cfg(feature = "async")]
+        #[tokio::test]
+        async fn decode_async() {
+            let decoded_str = Decoder::new()
+                .decode_async("=?UTF-8?B?c3Ry?= =?UTF-8?Q?_with_spaces?=")
+                .await
+                .unwrap();
+
+            assert_eq!(decoded_str, "str with spaces");
+        }
+
+        #[test]
+        fn utf8_b64_with_line_breaks() {
+            assert_eq!(decode("=?UTF-8?B?c3Ry\nZXh0?=").unwrap(), "strext");
+            assert_eq!(decode("=?UTF-8?B?c3Ry\r\nZXh0?=").unwrap(), "strext");
+        }
+
+        #[test]
+        fn utf8_b64_trailing_bit() {
+            assert_eq!(
+                decode("=?utf-8?B?UG9ydGFsZSBIYWNraW5nVGVhbW==?=").unwrap(),
+                "Portale HackingTeam",
+            );
+        }
+
+        #[test]
+        fn decode_all_stops_at_first_error() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder
+                    .decode_all(["=?UTF-8?Q?a?=", " b ", "=?UTF-8?B?Yw==?="])
+                    .unwrap(),
+                "a b c"
+            );
+            assert!(decoder
+                .decode_all(["=?UTF-8?Q?a?=", "=?UTF-8?B?!!!?=", "c"])
+                .is_err());
+        }
+
+        #[test]
+        fn thunderbird_compatible() {
+            let decoder = Decoder::thunderbird_compatible();
+            let too_long = concat![
+                "=?utf-8?B?TG9yZW0gaXBzdW0gZG9sb3Igc2l0IGFtZXQsIGNvbnNlY3RldHVyIGFkaXBp",
+                "c2NpbmcgZWxpdC4=?="
+            ];
+
+            assert_eq!(
+                decoder.decode(too_long).unwrap(),
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit."
+            );
+            assert_eq!(decoder.decode("=?UTF-8?Q?a?b?=").unwrap(), "a?b");
+            assert_eq!(decoder.decode(b"\xff =?UTF-8?Q?x?=").unwrap(), "\u{FFFD} x");
+        }
+
+        #[test]
+        fn degenerate_encoded_words() {
+            let strict = Decoder::new();
+            let lenient = Decoder::new().lenient_encoded_text(true);
+
+            assert_eq!(strict.decode("=???=").unwrap(), "=???=");
+            assert_eq!(strict.decode("=?UTF-8??x?=").unwrap(), "=?UTF-8??x?=");
+
+            assert_eq!(lenient.decode("=???=").unwrap(), "");
+            assert_eq!(lenient.decode("a =???= b").unwrap(), "a  b");
+            assert_eq!(
+                lenient.decode("=?UTF-8??x?=").unwrap_err(),
+                Error::Parser(ParserError::ParseEncodingEmptyError)
+            );
+            assert_eq!(
+                lenient
+                    .clone()
+                    .recover_for(ErrorClass::InvalidEncoding, RecoverStrategy::Skip)
+                    .decode("=?UTF-8??x?=")
+                    .unwrap(),
+                "=?UTF-8??x?="
+            );
+        }
+
+        #[test]
+        fn override_charset_of_second_word() {
+            let message = "=?ISO-8859-1?Q?=E9?= =?ISO-8859-1?Q?=E9?= =?ISO-8859-1?Q?=E9?=";
+            let decoder = Decoder::new().override_charset_at(1, "windows-1251");
+
+            assert_eq!(decoder.decode(message).unwrap(), "éйé");
+            assert_eq!(Decoder::new().decode(message).unwrap(), "ééé");
+        }
+
+        #[test]
+        fn control_char_policy() {
+            let message = "=?UTF-8?Q?a=07b?= \x07 =?UTF-8?Q?=C2=85?=";
+            let decode_with = |policy| {
+                Decoder::new()
+                    .control_char_policy(policy)
+                    .decode(message)
+                    .unwrap()
+            };
+
+            assert_eq!(decode_with(ControlCharPolicy::Keep), "a\x07b \x07 \u{85}");
+            assert_eq!(decode_with(ControlCharPolicy::Strip), "ab  ");
+            assert_eq!(decode_with(ControlCharPolicy::Escape), "a^Gb ^G \\u{85}");
+        }
+
+        #[test]
+        fn comment_between_encoded_words() {
+            assert_eq!(
+                decode("=?UTF-8?Q?x?= (note) =?UTF-8?Q?y?=").unwrap(),
+                "x (note) y"
+            );
+            assert_eq!(
+                decode("=?UTF-8?Q?x?= ( ) =?UTF-8?Q?y?=").unwrap(),
+                "x ( ) y"
+            );
+            assert_eq!(decode("=?UTF-8?Q?x?= . =?UTF-8?Q?y?=").unwrap(), "x . y");
+            assert_eq!(decode("=?UTF-8?Q?x?=  \t =?UTF-8?Q?y?=").unwrap(), "xy");
+        }
+
+        #[test]
+        fn percent_transfer_decoder() {
+            let percent_decode = |encoded_text: &[u8]| {
+                let encoded_text = std::str::from_utf8(encoded_text).ok()?;
+                let mut parts = encoded_text.split('%');
+                let mut decoded = parts.next()?.as_bytes().to_vec();
+
+                for part in parts {
+                    decoded.push(u8::from_str_radix(part.get(..2)?, 16).ok()?);
+                    decoded.extend(&part.as_bytes()[2..]);
+                }
+
+                Some(decoded)
+            };
+            let decoder = Decoder::new().with_transfer_decoder(b'x', percent_decode);
+
+            assert_eq!(
+                decoder
+                    .decode("=?utf-8?X?%e2%82%ac?= =?utf-8?x?5%e2%82%ac?=")
+                    .unwrap(),
+                "€5€"
+            );
+            assert_eq!(
+                decoder.decode("=?utf-8?X?%zz?=").unwrap(),
+                "=?utf-8?X?%zz?="
+            );
+            assert_eq!(
+                decoder.decode_detailed("=?utf-8?X?%41?=").unwrap()[0].encoding,
+                Encoding::Custom(b'X')
+            );
+            assert!(Decoder::new().decode("=?utf-8?X?%e2%82%ac?=").is_err());
+        }
+
+        #[test]
+        fn charset_fallback_chain() {
+            let decoder = Decoder::new().charset_fallback_chain(vec![
+                "x-unknown".to_string(),
+                "UTF-8".to_string(),
+                "windows-1252".to_string(),
+            ]);
+
+            // not valid UTF-8, but valid windows-1252
+            assert_eq!(decoder.decode("=?x-unknown?Q?Andr=E9?=").unwrap(), "André");
+            assert_eq!(decoder.decode("=?UTF-8?Q?Andr=E9?=").unwrap(), "André");
+            assert_eq!(
+                decoder.decode("=?x-unknown?Q?Andr=C3=A9?=").unwrap(),
+                "André"
+            );
+
+            assert_eq!(
+                Decoder::new().decode("=?UTF-8?Q?Andr=E9?=").unwrap(),
+                "Andr\u{FFFD}"
+            );
+        }
+
+        #[test]
+        fn decode_prefix_until_error() {
+            let decoder = Decoder::new();
+            let too_long = concat![
+                "=?utf-8?B?TG9yZW0gaXBzdW0gZG9sb3Igc2l0IGFtZXQsIGNvbnNlY3RldHVyIGFkaXBp",
+                "c2NpbmcgZWxpdC4=?="
+            ];
+
+            assert_eq!(
+                decoder.decode_prefix_until_error("=?UTF-8?Q?a?= =?UTF-8?B?!!!?= =?UTF-8?Q?c?="),
+                ("a".to_string(), true)
+            );
+            assert_eq!(
+                decoder.decode_prefix_until_error(format!("a {} b", too_long)),
+                ("a ".to_string(), true)
+            );
+            assert_eq!(
+                decoder.decode_prefix_until_error(b"a =?UTF-8?Q?b?= \xff"),
+                ("a b".to_string(), true)
+            );
+            assert_eq!(
+                decoder.decode_prefix_until_error("=?UTF-8?Q?a?= b"),
+                ("a b".to_string(), false)
+            );
+        }
+
+        #[test]
+        fn decode_bytes_into_appends() {
+            let decoder = Decoder::new();
+            let mut buffer = b"a".to_vec();
+
+            assert_eq!(
+                decoder
+                    .decode_bytes_into("=?UTF-8?B?w6k=?= b", &mut buffer)
+                    .unwrap(),
+                4
+            );
+            assert_eq!(
+                decoder
+                    .decode_bytes_into("=?UTF-8?Q?c?=", &mut buffer)
+                    .unwrap(),
+                1
+            );
+            assert_eq!(buffer, "aé bc".as_bytes());
+
+            assert!(decoder
+                .decode_bytes_into("x =?UTF-8?B?!!!?=", &mut buffer)
+                .is_err());
+            assert_eq!(buffer, "aé bc".as_bytes());
+        }
+
+        #[test]
+        fn incomplete_encoded_word_at_end() {
+            assert_eq!(decode("hello =?").unwrap(), "hello =?");
+            assert_eq!(decode("=?UTF-8?Q?a?= =?UTF-8?Q?").unwrap(), "a =?UTF-8?Q?");
+        }
+
+        #[test]
+        fn clear_text_charset() {
+            let message = b"\xf0 =?UTF-8?Q?=D0=A0?= \xf0";
+
+            assert_eq!(
+                Decoder::new()
+                    .clear_text_charset("windows-1251")
+                    .decode(message)
+                    .unwrap(),
+                "р Р р"
+            );
+            assert_eq!(
+                Decoder::new()
+                    .clear_text_charset("x-unknown")
+                    .decode(message)
+                    .unwrap_err(),
+                decode(message).unwrap_err()
+            );
+
+            // invalid bytes are replaced instead of aborting the decoding
+            assert_eq!(
+                Decoder::new()
+                    .clear_text_charset("UTF-8")
+                    .decode(message)
+                    .unwrap(),
+                "\u{FFFD} Р \u{FFFD}"
+            );
+        }
+
+        #[cfg(feature = "unicode-normalization")]
+        #[test]
+        fn normalize_nfc() {
+            let decoder = Decoder::new().normalize_nfc(true);
+
+            // the combining accent is in another encoded word than its letter
+            assert_eq!(
+                decoder.decode("=?UTF-8?Q?e?= =?UTF-8?Q?=CC=81?=").unwrap(),
+                "\u{e9}"
+            );
+            assert_eq!(
+                decoder.decode("A\u{30a} =?UTF-8?B?77yh?=").unwrap(),
+                "\u{c5} \u{ff21}"
+            );
+            assert!(matches!(
+                decoder.decode_cow("Cafe\u{301}".as_bytes()).unwrap(),
+                Cow::Owned(decoded_str) if decoded_str == "Caf\u{e9}"
+            ));
+
+            let segments = decoder
+                .decode_into_vec_segments("=?UTF-8?Q?cre=CC=80me?=")
+                .unwrap();
+            assert_eq!(segments[0].decoded, "cr\u{e8}me");
+
+            // every entry point normalizes the whole decoded string
+            let message = "=?UTF-8?Q?e?= =?UTF-8?Q?=CC=81?=";
+            assert_eq!(&*decoder.decode_boxed(message).unwrap(), "\u{e9}");
+            assert_eq!(decoder.decode_count_bytes(message).unwrap(), 2);
+            assert_eq!(decoder.decode_report(message).unwrap().decoded, "\u{e9}");
+            assert_eq!(
+                decoder.decode_prefix_until_error(message),
+                ("\u{e9}".to_string(), false)
+            );
+
+            let mut buffer = String::from("x");
+            decoder.decode_into(message, &mut buffer).unwrap();
+            assert_eq!(buffer, "x\u{e9}");
+
+            let mut buffer = b"x".to_vec();
+            assert_eq!(decoder.decode_bytes_into(message, &mut buffer).unwrap(), 2);
+            assert_eq!(buffer, "x\u{e9}".as_bytes());
+
+            let mut buffer = Vec::new();
+            assert!(!decoder
+                .decode_to_writer_with_limit(message, &mut buffer, 10)
+                .unwrap());
+            assert_eq!(buffer, "\u{e9}".as_bytes());
+
+            assert_eq!(Decoder::new().decode("Cafe\u{301}").unwrap(), "Cafe\u{301}");
+        }
+
+        #[cfg(feature = "chardetng")]
+        #[test]
+        fn detect_charset() {
+            let message = b"R\xe9sum\xe9 =?UTF-8?Q?na=C3=AFve?= d\xe9j\xe0 vu";
+            let decoder = Decoder::new().detect_charset(true);
+
+            assert_eq!(decoder.decode(message).unwrap(), "Résumé naïve déjà vu");
+            assert_eq!(decoder.decode("déjà vu").unwrap(), "déjà vu");
+            assert!(decode(message).is_err());
+        }
+
+        #[test]
+        fn too_long_invalid_base64_is_not_decoded() {
+            let message = format!("=?UTF-8?B?{}?=", "!".repeat(100));
+
+            assert!(matches!(
+                decode(&message).unwrap_err(),
+                Error::Lexer(LexerError::ParseEncodedWordTooLongError(_))
+            ));
+            assert_eq!(
+                Decoder::new()
+                    .recover_all(RecoverStrategy::Skip)
+                    .decode(&message)
+                    .unwrap(),
+                message
+            );
+        }
+
+        #[cfg(feature = "unicode-segmentation")]
+        #[test]
+        fn decode_with_grapheme_count() {
+            let decoder = Decoder::new();
+
+            // "a" + combining ring above, then a family emoji joined with ZWJs
+            let (decoded_str, grapheme_count) = decoder
+                .decode_with_grapheme_count(
+                    "=?UTF-8?Q?a=CC=8A?= =?UTF-8?B?8J+RqOKAjfCfkanigI3wn5Gn?= b",
+                )
+                .unwrap();
+            assert_eq!(
+                decoded_str,
+                "a\u{30a}\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467} b"
+            );
+            assert_eq!(grapheme_count, 4);
+
+            assert_eq!(
+                decoder.decode_with_grapheme_count("").unwrap(),
+                (String::new(), 0)
+            );
+        }
+
+        #[test]
+        fn line_endings_between_encoded_words() {
+            for separator in ["\r", "\n", "\r\n", "\r\n \t  ", "\r\t"] {
+                let message = format!("=?UTF-8?Q?x?={}=?UTF-8?Q?y?=", separator);
+                assert_eq!(decode(message).unwrap(), "xy", "{:?}", separator);
+            }
+        }
+
+        #[test]
+        fn decode_split_keeps_encoded_delimiters() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder
+                    .decode_split("=?UTF-8?Q?a=2C_b?= <a@b>, =?UTF-8?B?Yyxk?=,,x", b',')
+                    .unwrap(),
+                vec!["a, b <a@b>", "c,d", "", "x"]
+            );
+            assert_eq!(decoder.decode_split("", b',').unwrap(), vec![""]);
+            // `é` is U+00E9, but 0xE9 isn't a char of the decoded clear text
+            assert_eq!(
+                decoder.decode_split("caf\u{e9}", 0xe9),
+                Err(Error::NonAsciiDelimiter(0xe9))
+            );
+            assert_eq!(
+                Error::NonAsciiDelimiter(0xe9).to_string(),
+                "cannot split on the delimiter 0xe9: delimiter isn't ASCII"
+            );
+        }
+
+        #[test]
+        fn misplaced_encoded_words_in_address() {
+            let decoder = Decoder::new().header_context(HeaderContext::Address);
+            let is_misplaced = |message: &str| {
+                matches!(
+                    decoder.decode(message),
+                    Err(Error::Lexer(LexerError::MisplacedEncodedWord(_)))
+                )
+            };
+
+            assert!(!is_misplaced(
+                "=?UTF-8?Q?a?= =?UTF-8?Q?b?= <ab@example.com>"
+            ));
+            assert!(!is_misplaced("ab@example.com (=?UTF-8?Q?a?=)"));
+            assert!(!is_misplaced("=?UTF-8?Q?a?= <a@b>, =?UTF-8?Q?b?= <b@c>"));
+
+            assert!(is_misplaced("=?UTF-8?Q?a?=@example.com"));
+            assert!(is_misplaced("ab@=?UTF-8?Q?example?=.com"));
+            assert!(is_misplaced("A <=?UTF-8?Q?a?=@example.com>"));
+            assert!(is_misplaced("\"=?UTF-8?Q?a?=\" <a@example.com>"));
+
+            assert_eq!(
+                Decoder::new().decode("=?UTF-8?Q?a?=@example.com").unwrap(),
+                "a@example.com"
+            );
+        }
+
+        #[test]
+        fn decode_in_quoted_strings() {
+            let decoder = Decoder::new()
+                .header_context(HeaderContext::Address)
+                .decode_in_quoted_strings(true);
+
+            assert_eq!(
+                decoder
+                    .decode("\"=?UTF-8?Q?J=C3=B6rg?=\" <jorg@example.com>")
+                    .unwrap(),
+                "\"Jörg\" <jorg@example.com>"
+            );
+            assert_eq!(
+                decoder
+                    .decode(r#""a \"=?UTF-8?Q?b?=\" \\" <a@b>, c\d (e \) f) <c@d>"#)
+                    .unwrap(),
+                r#""a "b" \" <a@b>, c\d (e \) f) <c@d>"#
+            );
+            assert!(decoder.decode("\"a\" <=?UTF-8?Q?a?=@b>").is_err());
+
+            // quoted pairs are kept in other headers
+            assert_eq!(
+                Decoder::new()
+                    .decode_in_quoted_strings(true)
+                    .decode(r#""=?UTF-8?Q?a?= \"b\"""#)
+                    .unwrap(),
+                r#""a \"b\"""#
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "std")]
+        fn decode_to_writer_with_limit() {
+            let decoder = Decoder::new();
+            let message = "ab =?UTF-8?Q?=E2=82=AC?= cd";
+            let write = |max_bytes| {
+                let mut buffer = Vec::new();
+                let truncated = decoder
+                    .decode_to_writer_with_limit(message, &mut buffer, max_bytes)
+                    .unwrap();
+                (String::from_utf8(buffer).unwrap(), truncated)
+            };
+
+            // `€` takes the bytes 3 to 5
+            assert_eq!(write(3), ("ab ".to_string(), true));
+            assert_eq!(write(4), ("ab ".to_string(), true));
+            assert_eq!(write(5), ("ab ".to_string(), true));
+            assert_eq!(write(6), ("ab €".to_string(), true));
+            assert_eq!(write(9), ("ab € cd".to_string(), false));
+            assert_eq!(write(100), ("ab € cd".to_string(), false));
+            assert_eq!(write(0), (String::new(), true));
+
+            let mut buffer = Vec::new();
+            let err = decoder
+                .decode_to_writer_with_limit("=?UTF-8?B?!?=", &mut buffer, 10)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert!(buffer.is_empty());
+        }
+
+        #[cfg(feature = "arbitrary")]
+        #[test]
+        fn arbitrary_decoder() {
+            use arbitrary::{Arbitrary, Unstructured};
+
+            let messages = [
+                "=?UTF-8?Q?a=C3=A9?= =?ISO-8859-1?B?6Q==?= \"x\" <a@b>",
+                "=?UTF-8*B*w6k*= =?x?Y?z?= =???= (=?UTF-8#Q#=07#=)",
+                "=?UTF-8!Q!a!= =?utf-8?q?=E2?= =?utf-8?q?=82=AC?=",
+            ];
+
+            for seed in 0..=u8::MAX {
+                let bytes: Vec<u8> = (0..64u8)
+                    .map(|i| seed.wrapping_mul(31).wrapping_add(i.wrapping_mul(seed | 1)))
+                    .collect();
+                let decoder = Decoder::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+
+                for message in messages {
+                    let _ = decoder.decode(message);
+                }
+            }
+        }
+
+        #[test]
+        fn strip_zero_width() {
+            let decoder = Decoder::new().strip_zero_width(true);
+            let bom = "\u{FEFF}";
+
+            assert_eq!(
+                decoder
+                    .decode(format!(
+                        "{bom}=?UTF-8?B?w6k{bom}=?= {bom}=?UTF-8?Q?a?={bom}"
+                    ))
+                    .unwrap(),
+                "éa"
+            );
+            assert_eq!(
+                decoder.decode("a\u{200B}b\u{200C}c \u{FEFF}").unwrap(),
+                "ab\u{200C}c "
+            );
+            assert_eq!(
+                decoder.decode_prefix_until_error("=?UTF-8?Q?a\u{200B}?="),
+                ("a".to_string(), false)
+            );
+        }
+
+        #[test]
+        fn decode_boxed() {
+            let message = "Re: =?ISO-8859-1?Q?Andr=E9?= =?UTF-8?B?w6dh?= va, =?x-unknown?Q?a?=";
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                &*decoder.decode_boxed(message).unwrap(),
+                decoder.decode(message).unwrap()
+            );
+            assert_eq!(&*decoder.decode_boxed("").unwrap(), "");
+            assert_eq!(
+                decoder.decode_boxed("=?UTF-8?B?!?=").unwrap_err(),
+                decoder.decode("=?UTF-8?B?!?=").unwrap_err()
+            );
+        }
+
+        #[test]
+        fn q_trailing_equal_sign() {
+            assert_eq!(decode("=?UTF-8?Q?abc=?=").unwrap(), "abc=");
+            assert_eq!(decode("=?UTF-8?Q?=C3=A9=?= x").unwrap(), "é= x");
+            assert_eq!(decode("=?UTF-8?Q?=?=").unwrap(), "=");
+
+            let message = "=?UTF-8?Q?abc=?=";
+            assert!(matches!(
+                Decoder::new()
+                    .recover_for(ErrorClass::InvalidQuotedPrintable, RecoverStrategy::Abort)
+                    .decode(message)
+                    .unwrap_err(),
+                Error::Evaluator(EvaluatorError::DecodeQuotedPrintableError(_))
+            ));
+            assert_eq!(
+                Decoder::new()
+                    .recover_for(ErrorClass::InvalidQuotedPrintable, RecoverStrategy::Skip)
+                    .decode(message)
+                    .unwrap(),
+                message
+            );
+        }
+
+        #[test]
+        fn max_codepoint() {
+            let decoder = Decoder::new().max_codepoint('\u{FF}');
+            let message = "Ça =?UTF-8?Q?va_=F0=9F=98=80?= bien";
+            let err = Error::Evaluator(EvaluatorError::CodepointAboveMaximum {
+                codepoint: '😀',
+                position: 6,
+            });
+
+            assert_eq!(decoder.decode(message).unwrap_err(), err);
+            assert_eq!(decoder.decode_boxed(message).unwrap_err(), err);
+            assert_eq!(decoder.decode_count_bytes(message).unwrap_err(), err);
+            assert_eq!(decoder.decode_into_vec_segments(message).unwrap_err(), err);
+            assert_eq!(
+                decoder.decode_prefix_until_error(message),
+                ("Ça ".to_string(), true)
+            );
+
+            let mut buffer = b"x".to_vec();
+            assert_eq!(
+                decoder.decode_bytes_into(message, &mut buffer).unwrap_err(),
+                err
+            );
+            assert_eq!(buffer, b"x");
+
+            assert_eq!(decoder.decode("Ça =?UTF-8?Q?=C3=BF?=").unwrap(), "Ça ÿ");
+            assert_eq!(
+                Decoder::new()
+                    .max_codepoint('\x7f')
+                    .decode("a ÿ")
+                    .unwrap_err(),
+                Error::Evaluator(EvaluatorError::CodepointAboveMaximum {
+                    codepoint: 'ÿ',
+                    position: 2,
+                })
+            );
+        }
+
+        #[test]
+        fn encoded_words_in_brackets() {
+            assert_eq!(
+                decode("[=?UTF-8?Q?Ank=C3=BCndigung?=] Rest").unwrap(),
+                "[Ankündigung] Rest"
+            );
+            assert_eq!(
+                decode("Re: [ =?UTF-8?Q?Liste?=  =?UTF-8?Q?_1?= ]  Rest").unwrap(),
+                "Re: [ Liste 1 ]  Rest"
+            );
+            assert_eq!(
+                decode("[=?UTF-8?Q?a?=][=?UTF-8?B?w7w=?=] =?UTF-8?Q?c?=").unwrap(),
+                "[a][ü] c"
+            );
+        }
+
+        #[test]
+        fn decode_with_original() {
+            let message = b"\xff =?ISO-8859-1?Q?Andr=E9?=";
+
+            assert!(Decoder::new().decode_with_original(message).is_err());
+            assert_eq!(
+                Decoder::new()
+                    .recover_all(RecoverStrategy::Decode)
+                    .decode_with_original(message)
+                    .unwrap(),
+                (
+                    "\u{FFFD} =?ISO-8859-1?Q?Andr=E9?=".to_string(),
+                    "\u{FFFD} André".to_string()
+                )
+            );
+            assert_eq!(
+                Decoder::new()
+                    .strip_zero_width(true)
+                    .decode_with_original("=?UTF-8?Q?a\u{200B}?= b")
+                    .unwrap(),
+                ("=?UTF-8?Q?a?= b".to_string(), "a b".to_string())
+            );
+        }
+
+        #[test]
+        fn underscore_in_charset_label() {
+            assert_eq!(decode("=?ISO_8859_1?Q?Andr=E9?=").unwrap(), "André");
+            assert_eq!(decode("=?UTF_8?B?w6dh?=").unwrap(), "ça");
+            assert_eq!(decode("=?windows_1252?Q?=80?=").unwrap(), "€");
+            assert!(Decoder::new().charset_is_supported("utf_8"));
+
+            // labels which are known with an underscore are used verbatim
+            assert_eq!(decode("=?iso_8859-2?Q?=B1?=").unwrap(), "ą");
+            assert!(!Decoder::new().charset_is_supported("x_unknown"));
+        }
+
+        #[test]
+        fn decode_word() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder.decode_word("UTF-8", Encoding::B, b"w6dh").unwrap(),
+                "ça"
+            );
+            assert_eq!(
+                decoder
+                    .decode_word("ISO-8859-1", Encoding::Q, b"Keld_J=F8rn")
+                    .unwrap(),
+                "Keld Jørn"
+            );
+            assert!(decoder.decode_word("UTF-8", Encoding::B, b"!").is_err());
+
+            let decoder = Decoder::new()
+                .with_transfer_decoder(b'X', |encoded_text| Some(encoded_text.to_vec()))
+                .max_codepoint('\x7f');
+            assert_eq!(
+                decoder
+                    .decode_word("UTF-8", Encoding::Custom(b'X'), b"raw")
+                    .unwrap(),
+                "raw"
+            );
+            assert!(decoder.decode_word("UTF-8", Encoding::B, b"w6dh").is_err());
+        }
+
+        #[test]
+        fn decode_leading() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder.decode_leading(b"=?UTF-8?Q?Name?= <a@b>").unwrap(),
+                ("Name".to_string(), &b" <a@b>"[..])
+            );
+            // the `@` and `<` inside of the encoded words don't end the phrase
+            assert_eq!(
+                decoder
+                    .decode_leading(b"=?UTF-8?Q?a=40b?= =?UTF-8?B?PD4=?=, c@d")
+                    .unwrap(),
+                ("a@b<>".to_string(), &b", c@d"[..])
+            );
+            assert_eq!(
+                decoder.decode_leading(b"just a phrase ").unwrap(),
+                ("just a phrase".to_string(), &b" "[..])
+            );
+            assert_eq!(
+                decoder.decode_leading(b"<a@b>").unwrap(),
+                (String::new(), &b"<a@b>"[..])
+            );
+            assert!(decoder.decode_leading(b"=?UTF-8?B?!?= <a@b>").is_err());
+        }
+
+        #[test]
+        fn decode_to_bytes() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder
+                    .decode_to_bytes(b"a\xff =?KOI8-R?B?8NLJ?= =?x-unknown?Q?=FE?=")
+                    .unwrap(),
+                b"a\xff \xf0\xd2\xc9\xfe"
+            );
+            assert!(decoder.decode_to_bytes("=?UTF-8?B?!?=").is_err());
+            assert_eq!(
+                Decoder::new()
+                    .recover_all(RecoverStrategy::Skip)
+                    .decode_to_bytes("=?UTF-8?B?!?= =?UTF-8?Q?=E9?=")
+                    .unwrap(),
+                b"=?UTF-8?B?!?=\xe9"
+            );
+        }
+
+        #[test]
+        fn unknown_charset_strategy() {
+            let message = "a =?x-unknown?Q?Andr=C3=A9?= b";
+
+            let decoder = Decoder::new().unknown_charset_strategy(RecoverStrategy::Abort);
+            assert_eq!(
+                decoder.decode(message),
+                Err(Error::Parser(ParserError::UnknownCharset(
+                    "x-unknown".to_string()
+                )))
+            );
+            assert_eq!(decoder.decode("=?UTF-8?Q?Andr=C3=A9?=").unwrap(), "André");
+
+            let decoder = Decoder::new().unknown_charset_strategy(RecoverStrategy::Skip);
+            assert_eq!(decoder.decode(message).unwrap(), message);
+
+            let decoder = Decoder::new()
+                .unknown_charset_strategy(RecoverStrategy::Decode)
+                .charset_fallback_chain(vec!["UTF-8".to_string()]);
+            assert_eq!(decoder.decode(message).unwrap(), "a André b");
+        }
+
+        #[test]
+        fn language_suffix() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder
+                    .decode("=?UTF-8*en?Q?Andr=C3=A9?= =?ISO-8859-1*fr?Q?=E9?=")
+                    .unwrap(),
+                "Andréé"
+            );
+            assert_eq!(
+                Decoder::new()
+                    .recover_all(RecoverStrategy::Skip)
+                    .decode("=?UTF-8*en?B?!?=")
+                    .unwrap(),
+                "=?UTF-8*en?B?!?="
+            );
+        }
+
+        #[test]
+        fn decode_with_diagnostics() {
+            let message = "a =?UTF-8?Q?b?= =?ISO-8859-1?B?4w==?= c =?x?X?d?=";
+            let (decoded_str, segments) = Decoder::new()
+                .recover_all(RecoverStrategy::Skip)
+                .decode_with_diagnostics(message)
+                .unwrap();
+
+            assert_eq!(decoded_str, "a bã c =?x?X?d?=");
+            let spans: Vec<&str> = segments
+                .iter()
+                .map(|segment| &message[segment.span.clone()])
+                .collect();
+            assert_eq!(
+                spans,
+                [
+                    "a ",
+                    "=?UTF-8?Q?b?=",
+                    "=?ISO-8859-1?B?4w==?=",
+                    " c =?x?X?d?="
+                ]
+            );
+            assert_eq!(segments[2].charset.as_deref(), Some("windows-1252"));
+            assert_eq!(segments[2].encoding, Some(Encoding::B));
+            assert_eq!(segments[3].kind, SegmentKind::Text);
+            assert_eq!(segments[3].encoding, None);
+
+            // the spans refer to the input with the zero width chars
+            let message = "\u{200B}a =?UTF-8?Q?b?=\u{FEFF}";
+            let (decoded_str, segments) = Decoder::new()
+                .strip_zero_width(true)
+                .decode_with_diagnostics(message)
+                .unwrap();
+
+            assert_eq!(decoded_str, "a b");
+            assert_eq!(&message[segments[0].span.clone()], "a ");
+            assert_eq!(&message[segments[1].span.clone()], "=?UTF-8?Q?b?=");
+        }
+
+        #[test]
+        fn max_encoded_word_length() {
+            // 76 chars
+            let message = format!("=?UTF-8?Q?{}?=", "a".repeat(64));
+
+            assert!(Decoder::new().decode(&message).is_err());
+            assert_eq!(
+                Decoder::new()
+                    .max_encoded_word_length(76)
+                    .decode(&message)
+                    .unwrap(),
+                "a".repeat(64)
+            );
+
+            let decoder = Decoder::new()
+                .max_encoded_word_length(20)
+                .too_long_encoded_word_strategy(RecoverStrategy::Skip);
+            assert_eq!(
+                decoder
+                    .decode("=?UTF-8?Q?abcdefghi?= =?UTF-8?Q?ab?=")
+                    .unwrap(),
+                "=?UTF-8?Q?abcdefghi?= ab"
+            );
+        }
+
+        #[test]
+        fn decode_iter() {
+            let decoder = Decoder::new().unknown_charset_strategy(RecoverStrategy::Abort);
+            let decoded_parts: Vec<Result<String, Error>> = decoder
+                .decode_iter("a =?x-unknown?Q?b?= =?UTF-8?Q?c?= d")
+                .collect();
+
+            assert_eq!(
+                decoded_parts,
+                vec![
+                    Ok("a ".to_string()),
+                    Err(Error::Parser(ParserError::UnknownCharset(
+                        "x-unknown".to_string()
+                    ))),
+                    Ok("c".to_string()),
+                    Ok(" d".to_string()),
+                ]
+            );
+
+            let too_long = format!("=?UTF-8?Q?{}?=", "a".repeat(80));
+            let decoded_parts: Vec<Result<String, Error>> =
+                decoder.decode_iter(&too_long).collect();
+            assert_eq!(decoded_parts.len(), 1);
+            assert!(matches!(decoded_parts[0], Err(Error::Lexer(_))));
+
+            // the positions only count the decoded parts
+            let decoder = Decoder::new().max_codepoint('\x7f');
+            let decoded_parts: Vec<Result<String, Error>> = decoder
+                .decode_iter("ab =?UTF-8?Q?=C3=A9?= =?UTF-8?Q?=C3=A9?=")
+                .collect();
+            assert_eq!(
+                decoded_parts[2],
+                Err(Error::Evaluator(EvaluatorError::CodepointAboveMaximum {
+                    codepoint: 'é',
+                    position: 3,
+                }))
+            );
+
+            // the bytes of `€` (E2 82 AC) are split across the encoded words
+            let message = "a =?UTF-8?B?4oI=?= =?UTF-8?B?rA==?= =?UTF-8?Q?=E2?= b";
+            for decoder in [
+                Decoder::new(),
+                Decoder::new().merge_adjacent_same_charset(true),
+            ] {
+                let decoded_str = decoder
+                    .decode_iter(message)
+                    .collect::<Result<String, Error>>()
+                    .unwrap();
+                assert_eq!(decoded_str, decoder.decode(message).unwrap());
+            }
+
+            let decoder = Decoder::new().merge_adjacent_same_charset(true);
+            let decoded_parts: Vec<Result<String, Error>> = decoder.decode_iter(message).collect();
+            assert_eq!(decoded_parts.len(), 4);
+            assert_eq!(decoded_parts[1], Ok("€".to_string()));
+            assert_eq!(decoded_parts[2], Ok("\u{FFFD}".to_string()));
+        }
+
+        #[test]
+        fn collapse_adjacent_whitespace() {
+            let decoder = Decoder::new().collapse_adjacent_whitespace(false);
+
+            assert_eq!(
+                decoder
+                    .decode("a =?UTF-8?Q?b?= \t =?UTF-8?Q?c?==?UTF-8?Q?d?= e")
+                    .unwrap(),
+                "a b \t cd e"
+            );
+            assert_eq!(
+                Decoder::new()
+                    .decode("a =?UTF-8?Q?b?= \t =?UTF-8?Q?c?==?UTF-8?Q?d?= e")
+                    .unwrap(),
+                "a bcd e"
+            );
+
+            // the merged encoded words are separated by the whitespace now
+            let decoder = decoder.merge_adjacent_same_charset(true);
+            assert_eq!(
+                decoder
+                    .decode("=?UTF-8?Q?=E2=82?= =?UTF-8?Q?=AC?=")
+                    .unwrap(),
+                "\u{FFFD} \u{FFFD}"
+            );
+        }
+
+        #[test]
+        fn malformed_quoted_printable() {
+            let message = "=?UTF-8?Q?a=3db=C3=A9=ZZc=E?=";
+            let decode_with = |policy| {
+                Decoder::new()
+                    .malformed_quoted_printable_strategy(policy)
+                    .decode(message)
+            };
+
+            assert_eq!(
+                decode_with(MalformedEscapePolicy::Keep).unwrap(),
+                "a=bé=ZZc=E"
+            );
+            assert_eq!(
+                decode_with(MalformedEscapePolicy::Drop).unwrap(),
+                "a=béZZcE"
+            );
+            assert_eq!(
+                decode_with(MalformedEscapePolicy::Error),
+                Err(Error::Evaluator(EvaluatorError::MalformedEscape {
+                    position: 11
+                }))
+            );
+            assert_eq!(
+                decode_with(MalformedEscapePolicy::Error)
+                    .unwrap_err()
+                    .to_string(),
+                "the `=` at position 11 of the Q encoded text isn't followed by two hex digits"
+            );
+
+            // the escape after a malformed one is decoded
+            assert_eq!(decode("=?UTF-8?Q?a=b=3Dc?=").unwrap(), "a=b=c");
+
+            // a trailing `=` is malformed, too
+            assert_eq!(
+                Decoder::new()
+                    .malformed_quoted_printable_strategy(MalformedEscapePolicy::Drop)
+                    .decode("=?UTF-8?Q?a=?=")
+                    .unwrap(),
+                "a"
+            );
+
+            // the policy only applies if the encoded word is decoded anyway
+            let decoder = Decoder::new()
+                .malformed_quoted_printable_strategy(MalformedEscapePolicy::Drop)
+                .recover_for(ErrorClass::InvalidQuotedPrintable, RecoverStrategy::Skip);
+            assert_eq!(decoder.decode(message).unwrap(), message);
+        }
+
+        #[test]
+        fn decode_verbose() {
+            let outcome = Decoder::new()
+                .decode_verbose("=?UTF-8?Q?a?= =?UTF-8?B?Yg==?= c")
+                .unwrap();
+            assert_eq!(outcome.text, "ab c");
+            assert!(outcome.is_fully_decoded());
+
+            let too_long_word = concat![
+                "=?UTF-8?Q?",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                "?="
+            ];
+            let message = format!("{} =?X-UNKNOWN?Q?b?= =?UTF-8?B?!?=", too_long_word);
+            let decoder = Decoder::new()
+                .too_long_encoded_word_strategy(RecoverStrategy::Decode)
+                .recover_for(ErrorClass::InvalidBase64, RecoverStrategy::Skip);
+            let outcome = decoder.decode_verbose(&message).unwrap();
+
+            assert_eq!(outcome.text, decoder.decode(&message).unwrap());
+            assert_eq!(
+                outcome
+                    .recovered
+                    .iter()
+                    .map(|word| (
+                        word.encoded_word.as_str(),
+                        word.class,
+                        word.strategy.clone()
+                    ))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (
+                        too_long_word,
+                        ErrorClass::TooLongEncodedWord,
+                        RecoverStrategy::Decode
+                    ),
+                    (
+                        "=?X-UNKNOWN?Q?b?=",
+                        ErrorClass::UnknownCharset,
+                        RecoverStrategy::Decode
+                    ),
+                    (
+                        "=?UTF-8?B?!?=",
+                        ErrorClass::InvalidBase64,
+                        RecoverStrategy::Skip
+                    ),
+                ]
+            );
+
+            // an aborted decoding is returned as an error
+            assert!(Decoder::new().decode_verbose(&message).is_err());
+
+            // the charset override only applies to the first encoded word
+            let outcome = Decoder::new()
+                .override_charset_at(0, "x-unknown")
+                .decode_verbose("=?UTF-8?Q?a?= =?UTF-8?Q?b?= =?UTF-8?Q?c?=")
+                .unwrap();
+            assert_eq!(
+                outcome
+                    .recovered
+                    .iter()
+                    .map(|word| (word.encoded_word.as_str(), word.class))
+                    .collect::<Vec<_>>(),
+                vec![("=?UTF-8?Q?a?=", ErrorClass::UnknownCharset)]
+            );
+        }
+
+        #[test]
+        fn override_charset() {
+            let message = b"=?UTF-8?Q?=E9?= \xe9 =?UTF-8?Q?=E9?=";
+
+            assert_eq!(
+                Decoder::new()
+                    .override_charset("windows-1252")
+                    .decode(message)
+                    .unwrap(),
+                "é é é"
+            );
+
+            // the more specific settings win
+            assert_eq!(
+                Decoder::new()
+                    .override_charset("windows-1252")
+                    .override_charset_at(1, "ISO-8859-5")
+                    .clear_text_charset("ISO-8859-7")
+                    .decode(message)
+                    .unwrap(),
+                "é ι щ"
+            );
+
+            // an encoded word with an unknown declared charset isn't skipped
+            let decoder = Decoder::new()
+                .override_charset("windows-1252")
+                .unknown_charset_strategy(RecoverStrategy::Skip);
+            assert_eq!(decoder.decode("=?X-UNKNOWN?Q?=E9?=").unwrap(), "é");
+        }
+
+        #[test]
+        fn url_safe_base64_fallback() {
+            let decoder = Decoder::new().url_safe_base64_fallback(true);
+
+            // `ü?~` and `>>>` in URL-safe and in standard base64
+            assert_eq!(decoder.decode("=?UTF-8?B?w7w_fg==?=").unwrap(), "ü?~");
+            assert_eq!(decoder.decode("=?UTF-8?B?Pj4-?=").unwrap(), ">>>");
+            assert_eq!(decoder.decode("=?UTF-8?B?w7w/fg==?=").unwrap(), "ü?~");
+
+            // mixed alphabets are invalid in both
+            assert!(matches!(
+                decoder.decode("=?UTF-8?B?Pz8_Pj4+?="),
+                Err(Error::Evaluator(EvaluatorError::DecodeBase64Error(_)))
+            ));
+            assert_eq!(
+                decoder
+                    .clone()
+                    .recover_for(ErrorClass::InvalidBase64, RecoverStrategy::Skip)
+                    .decode("=?UTF-8?B?Pz8_Pj4+?=")
+                    .unwrap(),
+                "=?UTF-8?B?Pz8_Pj4+?="
+            );
+        }
+
+        #[test]
+        fn decode_header_field() {
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder
+                    .decode_header_field("From:\t=?UTF-8?Q?J=C3=B6rg?= <jorg@example.com>")
+                    .unwrap(),
+                ("From".to_string(), "Jörg <jorg@example.com>".to_string())
+            );
+            // the obsolete whitespace in front of the colon is removed
+            assert_eq!(
+                decoder.decode_header_field("X-Custom : a: b").unwrap(),
+                ("X-Custom".to_string(), "a: b".to_string())
+            );
+            assert_eq!(
+                decoder.decode_header_field("Subject:").unwrap(),
+                ("Subject".to_string(), String::new())
+            );
+
+            for line in [
+                "no colon",
+                ": no field name",
+                "two words: value",
+                "\"a:b\" c",
+            ] {
+                assert_eq!(
+                    decoder.decode_header_field(line),
+                    Err(Error::Lexer(LexerError::MissingFieldName(line.to_string())))
+                );
+            }
+        }
+
+        #[test]
+        fn unfold() {
+            let decoder = Decoder::new().unfold(true);
+
+            let subject = concat![
+                "=?ISO-8859-1?Q?Caf=E9?= and\r\n",
+                " =?UTF-8?B?w6lsw6h2ZQ==?=\r\n",
+                "  =?UTF-8?Q?s?= meet\n",
+                "\tagain\r\n",
+                "\r\n",
+            ];
+            assert_eq!(
+                decoder.decode(subject).unwrap(),
+                "Café and élèves meet\tagain\r\n\r\n"
+            );
+            assert_eq!(
+                Decoder::new().decode(subject).unwrap(),
+                "Café and\r\n élèves meet\n\tagain\r\n\r\n"
+            );
+
+            // the spans still point into the input
+            let (decoded_str, segments) = decoder
+                .strip_zero_width(true)
+                .decode_with_diagnostics("a\r\n \u{200B}=?UTF-8?Q?b?=")
+                .unwrap();
+            assert_eq!(decoded_str, "a b");
+            assert_eq!(
+                segments
+                    .iter()
+                    .map(|segment| segment.span.clone())
+                    .collect::<Vec<_>>(),
+                vec![0..4, 7..20]
+            );
+        }
+
+        #[test]
+        fn decode_cow() {
+            let is_borrowed = |decoder: &Decoder, message: &str| {
+                let decoded_str = decoder.decode_cow(message.as_bytes()).unwrap();
+                assert_eq!(decoded_str, decoder.decode(message).unwrap());
+                matches!(decoded_str, Cow::Borrowed(_))
+            };
+
+            assert!(is_borrowed(&Decoder::new(), ""));
+            assert!(is_borrowed(&Decoder::new(), "Grüße aus Bonn"));
+            assert!(is_borrowed(&Decoder::new(), "a = b? c"));
+            assert!(!is_borrowed(&Decoder::new(), "=?UTF-8?Q?a?="));
+            // looks like the start of an encoded word
+            assert!(!is_borrowed(&Decoder::new(), "a =? b"));
+
+            let decoder = Decoder::new().control_char_policy(ControlCharPolicy::Strip);
+            assert!(is_borrowed(&decoder, "a\tb"));
+            assert!(!is_borrowed(&decoder, "a\u{1b}b"));
+
+            let decoder = Decoder::new()
+                .header_context(HeaderContext::Address)
+                .decode_in_quoted_strings(true);
+            assert!(!is_borrowed(&decoder, r#""a\"b" <a@b.c>"#));
+
+            assert!(!is_borrowed(&Decoder::new().unfold(true), "a b"));
+            assert!(!is_borrowed(
+                &Decoder::new().clear_text_charset("UTF-8"),
                 "a b"
+            ));
+
+            assert!(Decoder::new()
+                .max_codepoint('\u{7f}')
+                .decode_cow("é".as_bytes())
+                .is_err());
+            assert!(Decoder::new().decode_cow(b"\xff").is_err());
+        }
+
+        #[test]
+        fn decode_batch() {
+            let decoder = Decoder::new();
+            let inputs: Vec<String> = (0..200)
+                .map(|index| match index % 3 {
+                    0 => format!("=?UTF-8?Q?{}?=", index),
+                    1 => "=?UTF-8?B?!?=".to_string(),
+                    _ => format!("{} =?UTF-8?B?w6k=?=", index),
+                })
+                .collect();
+
+            // large enough to be decoded in parallel with the rayon feature
+            let results = decoder.decode_batch(&inputs);
+            assert_eq!(results.len(), inputs.len());
+            for (input, result) in inputs.iter().zip(results) {
+                assert_eq!(result, decoder.decode(input));
+            }
+
+            assert_eq!(decoder.decode_batch::<&str>(&[]), vec![]);
+        }
+
+        #[test]
+        fn underscore_as_space() {
+            let decoder = Decoder::new().underscore_as_space(false);
+
+            assert_eq!(decoder.decode("=?UTF-8?Q?a_b=5Fc?=").unwrap(), "a_b_c");
+            assert_eq!(decode("=?UTF-8?Q?a_b=5Fc?=").unwrap(), "a b_c");
+
+            // the B encoding isn't affected
+            assert_eq!(decoder.decode("=?UTF-8?B?YSBi?=").unwrap(), "a b");
+        }
+
+        /// UTF-7 isn't part of the Encoding Standard, but it's decoded by the
+        /// charset crate, which old Exchange servers rely on.
+        #[test]
+        fn utf7() {
+            assert_eq!(decode("=?UTF-7?Q?+AOQ-?=").unwrap(), "ä");
+            assert_eq!(decode("=?utf-7?B?K0FPUS0=?=").unwrap(), "ä");
+            assert_eq!(
+                decode("=?UTF-7?Q?Hi_+ZeVnLIqe-?= =?UTF-7?Q?!?=").unwrap(),
+                "Hi 日本語!"
+            );
+            assert_eq!(
+                Decoder::new()
+                    .allow_mib_charset(true)
+                    .decode("=?1012?Q?+AOQ-?=")
+                    .unwrap(),
+                "ä"
             );
+            assert!(Decoder::new().charset_is_supported("UTF-7"));
         }
-    }
 
-    /// Those are some custom tests
-    mod custom_tests {
-        use crate::decode;
+        #[test]
+        #[cfg(feature = "std")]
+        fn decode_stream() {
+            use std::io;
+
+            struct FailingReader;
+
+            impl io::Read for FailingReader {
+                fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                    Err(io::Error::new(io::ErrorKind::BrokenPipe, "pipe closed"))
+                }
+            }
+
+            let decoder = Decoder::new();
+
+            assert_eq!(
+                decoder
+                    .decode_stream("a =?UTF-8?B?w6k=?=".as_bytes())
+                    .unwrap(),
+                "a é"
+            );
+
+            let err = decoder.decode_stream(FailingReader).unwrap_err();
+            assert_eq!(
+                err,
+                Error::Io {
+                    kind: io::ErrorKind::BrokenPipe,
+                    message: "pipe closed".to_string(),
+                }
+            );
+            assert_eq!(err.to_string(), "cannot read the input: pipe closed");
+        }
 
         #[test]
-        fn clear_empty() {
-            assert_eq!(decode("").unwrap(), "");
+        fn skip_keeps_original_bytes() {
+            let message = "a =? UTF-8 ?B?!!!!?= b =?UTF-8*EN?Q?=ZZ?= c =?UTF-8?B?w6k=?=";
+            let decoder = Decoder::new()
+                .trim_field_whitespace(true)
+                .recover_for(ErrorClass::InvalidBase64, RecoverStrategy::Skip)
+                .recover_for(ErrorClass::InvalidQuotedPrintable, RecoverStrategy::Skip);
+
+            assert_eq!(
+                decoder.decode(message).unwrap(),
+                "a =? UTF-8 ?B?!!!!?= b =?UTF-8*EN?Q?=ZZ?= c é"
+            );
+            assert_eq!(
+                decoder.decode_to_bytes(message).unwrap(),
+                "a =? UTF-8 ?B?!!!!?= b =?UTF-8*EN?Q?=ZZ?= c é".as_bytes()
+            );
+
+            let decoder = decoder.allow_missing_suffix(true);
+            assert_eq!(
+                decoder.decode("=?UTF-8?B?!!!! =?UTF-8?B?w6k=").unwrap(),
+                "=?UTF-8?B?!!!! é"
+            );
         }
 
         #[test]
-        fn clear_with_spaces() {
-            assert_eq!(decode("str with spaces").unwrap(), "str with spaces");
+        fn on_decode_error() {
+            let decoder = Decoder::new().on_decode_error(RecoverStrategy::Decode);
+
+            assert_eq!(
+                decoder.recover_strategy(ErrorClass::InvalidBase64),
+                RecoverStrategy::Decode
+            );
+            assert_eq!(
+                decoder.recover_strategy(ErrorClass::InvalidQuotedPrintable),
+                RecoverStrategy::Decode
+            );
+            assert_eq!(
+                decoder.recover_strategy(ErrorClass::UnknownCharset),
+                RecoverStrategy::Decode
+            );
+            assert_eq!(
+                decoder.recover_strategy(ErrorClass::TooLongEncodedWord),
+                RecoverStrategy::Abort
+            );
+            assert_eq!(decoder.decode("a =?UTF-8?B?w6k?=").unwrap(), "a é");
+
+            let decoder = Decoder::new().on_decode_error(RecoverStrategy::Abort);
+            assert!(matches!(
+                decoder.decode("=?UTF-8?Q?=ZZ?="),
+                Err(Error::Evaluator(
+                    EvaluatorError::DecodeQuotedPrintableError(_)
+                ))
+            ));
         }
 
         #[test]
-        fn utf8_qs_empty() {
-            assert_eq!(decode("").unwrap(), "");
+        fn decode_into() {
+            let decoder = Decoder::new();
+            let mut buffer = "Subject: ".to_string();
+
+            decoder
+                .decode_into("=?UTF-8?B?w6k=?= =?UTF-8?Q?t=C3=A9?=", &mut buffer)
+                .unwrap();
+            assert_eq!(buffer, "Subject: été");
+
+            assert!(decoder
+                .decode_into("a =?UTF-8?B?w6k=?= =?UTF-8?B?!!!?=", &mut buffer)
+                .is_err());
+            assert_eq!(buffer, "Subject: été");
         }
 
         #[test]
-        fn utf8_qs_with_str() {
-            assert_eq!(decode("=?UTF-8?Q?str?=").unwrap(), "str");
+        fn empty_charset() {
+            let lenient = Decoder::new().lenient_encoded_text(true);
+
+            assert_eq!(lenient.decode("=??Q?x?=").unwrap(), "x");
+            assert_eq!(lenient.decode("=??B?eA==?=").unwrap(), "x");
+            assert_eq!(lenient.decode("=??Q?=C3=A9t=C3=A9?=").unwrap(), "été");
+            assert_eq!(Decoder::new().decode("=??Q?x?=").unwrap(), "=??Q?x?=");
+
+            let aborting = lenient
+                .clone()
+                .unknown_charset_strategy(RecoverStrategy::Abort);
+            let err = aborting.decode("=??B?eA==?=").unwrap_err();
+            assert_eq!(err, Error::Parser(ParserError::EmptyCharset));
+            assert_eq!(err.to_string(), "cannot parse charset: charset is empty");
+
+            let outcome = lenient.decode_verbose("a =??Q?x?=").unwrap();
+            assert_eq!(outcome.text, "a x");
+            assert_eq!(outcome.recovered[0].class, ErrorClass::UnknownCharset);
         }
 
         #[test]
-        fn utf8_qs_with_spaces() {
+        fn decode_address_list() {
+            let decoder = Decoder::new();
+
             assert_eq!(
-                decode("=?utf8?q?str_with_spaces?=").unwrap(),
-                "str with spaces"
+                decoder
+                    .decode_address_list(
+                        r#" "=?UTF-8?Q?M=C3=BCller?=, \"Hans\"" <hans@example.com>,,<a,b@example.com> "#
+                    )
+                    .unwrap(),
+                vec![
+                    (
+                        Some(r#"Müller, "Hans""#.to_string()),
+                        "hans@example.com".to_string()
+                    ),
+                    (None, "a,b@example.com".to_string()),
+                ]
+            );
+            assert_eq!(
+                decoder
+                    .decode_address_list("=?ISO-8859-1?Q?Ren=E9?= Doe <=?x?Q?y?=@example.com>")
+                    .unwrap(),
+                vec![(
+                    Some("René Doe".to_string()),
+                    "=?x?Q?y?=@example.com".to_string()
+                )]
             );
+            assert_eq!(decoder.decode_address_list("  ").unwrap(), vec![]);
+            assert!(decoder
+                .decode_address_list("=?UTF-8?B?!!!?= <a@example.com>")
+                .is_err());
         }
 
         #[test]
-        fn utf8_qs_with_spec_chars() {
+        fn charset_alias() {
+            let decoder = Decoder::new();
+
             assert_eq!(
-                decode("=?utf8?q?str_with_special_=C3=A7h=C3=A0r=C3=9F?=").unwrap(),
-                "str with special çhàrß"
+                decoder
+                    .decode("=?CP932?B?g2WDWINn?= =?utf8mb4?Q?=F0=9F=98=80?=")
+                    .unwrap(),
+                "テスト😀"
+            );
+            assert!(decoder.charset_is_supported("LATIN_9"));
+            assert!(!decoder.charset_is_supported("x-cyrillic"));
+
+            let decoder = decoder
+                .charset_alias("X_Cyrillic", "KOI8-R")
+                .charset_alias("latin-1", "windows-1251");
+            assert_eq!(
+                decoder
+                    .decode("=?x-cyrillic?Q?=F0=D2=C9=D7=C5=D4?= =?LATIN-1?Q?=EC=E8=F0?=")
+                    .unwrap(),
+                "Приветмир"
+            );
+
+            let unknown = Decoder::new()
+                .charset_alias("x-broken", "x-unknown")
+                .unknown_charset_strategy(RecoverStrategy::Abort);
+            assert_eq!(
+                unknown.decode("=?x-broken?Q?a?="),
+                Err(Error::Parser(ParserError::UnknownCharset(
+                    "x-broken".to_string()
+                )))
             );
         }
 
         #[test]
-        fn utf8_qs_double() {
+        fn decode_with_charsets() {
+            let decoder = Decoder::new().unknown_charset_strategy(RecoverStrategy::Skip);
+
             assert_eq!(
-                decode("=?UTF-8?Q?str?=\r\n =?UTF-8?Q?str?=").unwrap(),
-                "strstr"
+                decoder.decode_with_charsets("only clear text").unwrap(),
+                ("only clear text".to_string(), vec![])
             );
             assert_eq!(
-                decode("=?UTF-8?Q?str?=\n =?UTF-8?Q?str?=").unwrap(),
-                "strstr"
+                decoder
+                    .decode_with_charsets(
+                        "=?KOI8-R?B?8NLJ?= =?x-unknown?Q?a?= =?koi8-r?Q?=D7=C5=D4?= =?UTF-8*en?Q?!?="
+                    )
+                    .unwrap(),
+                (
+                    "При =?x-unknown?Q?a?= вет!".to_string(),
+                    vec!["KOI8-R".to_string(), "UTF-8".to_string()]
+                )
             );
-            assert_eq!(decode("=?UTF-8?Q?str?= =?UTF-8?Q?str?=").unwrap(), "strstr");
-            assert_eq!(decode("=?UTF-8?Q?str?==?UTF-8?Q?str?=").unwrap(), "strstr");
+            assert!(decoder.decode_with_charsets("=?UTF-8?B?!?=").is_err());
         }
 
         #[test]
-        fn utf8_b64_empty() {
-            assert_eq!(decode("=?UTF-8?B??=").unwrap(), "");
+        fn max_output_bytes() {
+            let decoder = Decoder::new().max_output_bytes(6);
+            let err = || Error::Evaluator(EvaluatorError::OutputTooLarge { max_bytes: 6 });
+
+            assert_eq!(decoder.decode("ab =?UTF-8?Q?=C3=A9?=!").unwrap(), "ab é!");
+            assert_eq!(decoder.decode("ab =?UTF-8?Q?=C3=A9?=!!"), Err(err()));
+            assert_eq!(
+                err().to_string(),
+                "the decoded string is longer than 6 bytes"
+            );
+
+            let mut buffer = Vec::new();
+            assert_eq!(
+                decoder.decode_bytes_into("=?UTF-8?B?QUFBQUFBQQ==?=", &mut buffer),
+                Err(err())
+            );
+            assert!(buffer.is_empty());
+            assert_eq!(decoder.decode_cow(b"abcdefg"), Err(err()));
+            assert!(decoder
+                .decode_iter("abcdef =?UTF-8?Q?x?=")
+                .any(|decoded_part| decoded_part.is_err()));
         }
 
         #[test]
-        fn utf8_b64_with_str() {
-            assert_eq!(decode("=?UTF-8?B?c3Ry?=").unwrap(), "str");
+        fn strict_q_hex_case() {
+            let decoder = Decoder::new().strict_q_hex_case(true);
+
+            assert_eq!(
+                decoder
+                    .decode("=?UTF-8?Q?=C3=A9=3D?= =?UTF-8?B?w6k=?=")
+                    .unwrap(),
+                "é=é"
+            );
+            // neither a hex escape nor Q encoded text
+            assert_eq!(decoder.decode("=?UTF-8?Q?=zz?=").unwrap(), "=zz");
+            assert_eq!(
+                decoder.decode("=?ISO-8859-1?B?6Q==?= abc").unwrap(),
+                "é abc"
+            );
+
+            for (message, position) in [("=?UTF-8?Q?=c3=a9?=", 0), ("=?UTF-8?Q?a==C3=aF?=", 5)] {
+                assert_eq!(
+                    decoder.decode(message),
+                    Err(Error::Evaluator(EvaluatorError::InvalidHexCase {
+                        position
+                    })),
+                    "{}",
+                    message
+                );
+            }
+            assert_eq!(
+                EvaluatorError::InvalidHexCase { position: 5 }.to_string(),
+                "the escape at position 5 of the Q encoded text has lowercase hex digits"
+            );
         }
 
         #[test]
-        fn utf8_b64_with_spaces() {
+        fn q_encoding() {
+            let robust = Decoder::new();
+            let strict = Decoder::new()
+                .recover_for(ErrorClass::InvalidQuotedPrintable, RecoverStrategy::Abort);
+
+            // trailing whitespace is kept
+            assert_eq!(robust.decode("=?UTF-8?Q?a__?=").unwrap(), "a  ");
+            assert_eq!(strict.decode("=?UTF-8?Q?a=20?=").unwrap(), "a ");
+            // there are no soft line breaks
             assert_eq!(
-                decode("=?utf8?b?c3RyIHdpdGggc3BhY2Vz?=").unwrap(),
-                "str with spaces"
+                robust
+                    .decode_word("UTF-8", Encoding::Q, b"a=\r\nb")
+                    .unwrap(),
+                "a=\r\nb"
+            );
+            assert!(strict
+                .decode_word("UTF-8", Encoding::Q, b"a=\r\nb")
+                .is_err());
+            // the length of the encoded text isn't limited
+            let long_text = "a".repeat(100);
+            assert_eq!(
+                strict
+                    .decode_word("UTF-8", Encoding::Q, long_text.as_bytes())
+                    .unwrap(),
+                long_text
+            );
+            // 8-bit bytes aren't dropped
+            assert_eq!(
+                robust
+                    .decode_word("ISO-8859-1", Encoding::Q, b"caf\xe9")
+                    .unwrap(),
+                "café"
             );
+            assert!(strict
+                .decode_word("ISO-8859-1", Encoding::Q, b"caf\xe9")
+                .is_err());
+            assert!(strict.decode("=?UTF-8?Q?=e9?=").is_err());
         }
 
         #[test]
-        fn utf8_b64_with_spec_chars() {
+        fn replacement_for_skipped_encoded_words() {
+            let too_long_word = format!("=?UTF-8?Q?{}?=", "a".repeat(70));
+            let broken_words = [
+                too_long_word.as_str(),
+                "=?UTF-8?B?!!!?=",
+                "=?UTF-8?Q?=ZZ?=",
+                "=?x-unknown?Q?a?=",
+                "=?UTF-8?X?a?=",
+            ];
+            let skipping_decoder = Decoder::new()
+                .recover_all(RecoverStrategy::Skip)
+                .replacement_for_undecodable("[unreadable]".to_string());
+            let replacing_decoder =
+                Decoder::new().recover_all(RecoverStrategy::Replace("[unreadable]".to_string()));
+
+            for decoder in [&skipping_decoder, &replacing_decoder] {
+                for broken_word in broken_words {
+                    let message = format!("a {} b", broken_word);
+                    assert_eq!(
+                        decoder.decode(&message).unwrap(),
+                        "a [unreadable] b",
+                        "{}",
+                        broken_word
+                    );
+                }
+            }
+
+            // the invalid bytes of the clear text are dropped when skipped, but
+            // replaced with the text of RecoverStrategy::Replace
+            assert_eq!(skipping_decoder.decode(b"a \xff\xfe b").unwrap(), "a  b");
             assert_eq!(
-                decode("=?utf8?b?c3RyIHdpdGggc3BlY2lhbCDDp2jDoHLDnw==?=").unwrap(),
-                "str with special çhàrß"
+                replacing_decoder.decode(b"a \xff\xfe b \xff").unwrap(),
+                "a [unreadable] b [unreadable]"
             );
         }
 
+        /// Each prefix used to be scanned up to the end of the input, so
+        /// these took quadratic time.
         #[test]
-        fn utf8_b64_trailing_bit() {
+        fn pathological_prefixes() {
+            use std::time::{Duration, Instant};
+
+            let lenient = Decoder::new()
+                .lenient_encoded_text(true)
+                .allow_missing_suffix(true);
+            // the lexing used to take quadratic time: about 10 s for 1 000
+            // encoded words in a debug build, so 2 000 of them took 40 s
+            let start = Instant::now();
+
+            let message = "=?".repeat(20_000);
+            assert_eq!(Decoder::new().decode(&message).unwrap(), message);
+            assert_eq!(lenient.decode(&message).unwrap(), message);
+
+            let message = "=?UTF-8?Q?a".repeat(2_000);
+            assert_eq!(lenient.decode(&message).unwrap(), "a".repeat(2_000));
+
+            let message = "=?UTF-8?Q?a?b".repeat(2_000);
+            assert_eq!(lenient.decode(&message).unwrap(), message);
+
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "took {:?}",
+                start.elapsed()
+            );
+        }
+
+        #[test]
+        fn strip_bom() {
+            let decoder = Decoder::new().strip_bom(true);
+
             assert_eq!(
-                decode("=?utf-8?B?UG9ydGFsZSBIYWNraW5nVGVhbW==?=").unwrap(),
-                "Portale HackingTeam",
+                decoder
+                    .decode("=?UTF-7?Q?+/v8-a?= =?UTF-7?Q?+/v8-b?= c")
+                    .unwrap(),
+                "ab c"
+            );
+            // only a leading BOM is removed
+            assert_eq!(decoder.decode("=?UTF-7?Q?a+/v8-b?=").unwrap(), "a\u{FEFF}b");
+            // the BOM of clear text isn't touched
+            assert_eq!(
+                decoder.decode("\u{FEFF}=?UTF-8?Q?a?=").unwrap(),
+                "\u{FEFF}a"
+            );
+            assert_eq!(
+                Decoder::new().decode("=?UTF-7?Q?+/v8-a?=").unwrap(),
+                "\u{FEFF}a"
+            );
+        }
+
+        #[test]
+        fn charset_decoder() {
+            // decodes the bytes as UTF-8 without the check of the charset
+            let decoder = Decoder::new().with_charset_decoder(|label, bytes| match label {
+                "x-custom" | "ISO-8859-1" => Some(String::from_utf8_lossy(bytes).into_owned()),
+                _ => None,
+            });
+
+            assert_eq!(
+                decoder
+                    .decode("=?x-custom?Q?caf=C3=A9?= =?ISO-8859-1?Q?=C3=A9?=")
+                    .unwrap(),
+                "caféé"
+            );
+            // the built-in charsets are used if the charset decoder returns `None`
+            assert_eq!(
+                decoder
+                    .decode("=?iso-8859-1?Q?=E9?= =?UTF-8?B?w6k=?=")
+                    .unwrap(),
+                "éé"
+            );
+            assert!(decoder.charset_is_supported("x-custom"));
+            assert!(!decoder.charset_is_supported("x-unknown"));
+
+            // the charsets of the charset decoder aren't unknown
+            for strategy in [RecoverStrategy::Abort, RecoverStrategy::Skip] {
+                let decoder = decoder.clone().unknown_charset_strategy(strategy);
+                assert_eq!(decoder.decode("=?x-custom?Q?a?=").unwrap(), "a");
+                assert_eq!(
+                    decoder
+                        .decode_verbose("=?x-custom?Q?a?=")
+                        .unwrap()
+                        .recovered,
+                    vec![]
+                );
+            }
+
+            let decoder = decoder.unknown_charset_strategy(RecoverStrategy::Abort);
+            assert!(decoder.decode("=?x-unknown?Q?a?=").is_err());
+            assert_eq!(decoder.clone(), decoder);
+        }
+
+        #[test]
+        fn merge_adjacent_same_charset() {
+            let decoder = Decoder::new().merge_adjacent_same_charset(true);
+            // the bytes of `€` (E2 82 AC) are split across the encoded words
+            let message = "=?UTF-8?B?4oI=?= =?utf-8?B?rA==?= x =?UTF-8?Q?=E2?= =?UTF-8?B?gqw=?=";
+
+            assert_eq!(
+                decoder.decode(message).unwrap(),
+                "€ x \u{FFFD}\u{FFFD}\u{FFFD}"
+            );
+            assert_eq!(decoder.decode_count_bytes(message).unwrap(), 15);
+
+            let segments = decoder.decode_into_vec_segments(message).unwrap();
+            assert_eq!(segments.len(), 4);
+            assert_eq!(segments[0].decoded, "€");
+        }
+
+        #[test]
+        fn charset_is_supported() {
+            let decoder = Decoder::new();
+
+            assert!(decoder.charset_is_supported("utf-8"));
+            assert!(decoder.charset_is_supported("HZ-GB-2312"));
+            assert!(decoder.charset_is_supported("cp1252"));
+            assert!(!decoder.charset_is_supported("x-unknown"));
+            assert!(!decoder.charset_is_supported("106"));
+            assert!(decoder.allow_mib_charset(true).charset_is_supported("106"));
+        }
+
+        #[test]
+        fn alternate_delimiter() {
+            let decoder = Decoder::new()
+                .alternate_delimiter(b'*')
+                .recover_all(RecoverStrategy::Skip);
+
+            assert_eq!(decoder.decode("=?UTF-8*Q*x*=").unwrap(), "x");
+            assert_eq!(
+                decoder.decode("=?UTF-8*Q*a*= =?UTF-8*B*YQ==*=").unwrap(),
+                "aa"
+            );
+            assert_eq!(decoder.decode("=?UTF-8?Q?x?=").unwrap(), "=?UTF-8?Q?x?=");
+            // skipped encoded words are kept with their original delimiter
+            assert_eq!(
+                decoder.decode("=?UTF-8*B*!!!*=").unwrap(),
+                "=?UTF-8*B*!!!*="
             );
         }
     }
@@ -0,0 +1,408 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use core::{ops::Range, result};
+use thiserror::Error;
+
+use crate::{lexer::encoded_word, Encoding};
+
+/// All errors which the encoder can throw.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("cannot encode with charset {0}: charset is unknown")]
+    UnknownCharset(String),
+    #[error("cannot encode {0:?}: it can't be represented in charset {1}")]
+    UnencodableChar(char, String),
+    #[error("cannot encode with encoding {0}: B or Q is expected")]
+    UnsupportedEncoding(char),
+}
+
+type Result<T> = result::Result<T, Error>;
+
+/// The chars which are kept as they are by the Q encoding. These are the ones
+/// which are allowed in every place of a header (see
+/// [section 5 of the RFC](https://datatracker.ietf.org/doc/html/rfc2047#section-5)).
+const Q_SAFE_SPECIALS: &[u8] = b"!*+-/";
+
+/// Represents the encoder builder, the counterpart of [crate::Decoder].
+///
+/// Only the words which need it are encoded: the ones with non-ASCII chars,
+/// control chars or which look like the start of an encoded word. Adjacent
+/// words which need to be encoded are put into the same encoded words,
+/// together with the whitespace between them. Each encoded word is at most 75
+/// chars long, longer runs are split into multiple encoded words separated by
+/// a space. Folding the header into lines is left to the caller.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{decode, Encoder, Encoding};
+///
+/// let encoder = Encoder::new().charset("ISO-8859-1").encoding(Encoding::Q);
+/// let encoded_str = encoder.encode("Re: Café crème").unwrap();
+///
+/// assert_eq!(encoded_str, "Re: =?ISO-8859-1?Q?Caf=E9_cr=E8me?=");
+/// assert_eq!(decode(&encoded_str).unwrap(), "Re: Café crème");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Encoder {
+    /// The label of the charset the text is encoded with.
+    pub charset: String,
+
+    /// The encoding of the encoded words, either [Encoding::B] or
+    /// [Encoding::Q].
+    pub encoding: Encoding,
+}
+
+impl Encoder {
+    /// Equals [Encoder::default].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the label of the charset the text is encoded with.
+    ///
+    /// Charsets which can only be decoded (like `UTF-16` and `UTF-7`) are
+    /// encoded as `UTF-8` instead, just like the
+    /// [Encoding Standard](https://encoding.spec.whatwg.org/) does.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Encoder, Encoding};
+    ///
+    /// let encoder = Encoder::new().charset("KOI8-R").encoding(Encoding::B);
+    ///
+    /// assert_eq!(encoder.encode("Привет").unwrap(), "=?KOI8-R?B?8NLJ18XU?=");
+    /// ```
+    pub fn charset(mut self, charset: &str) -> Self {
+        self.charset = charset.to_string();
+        self
+    }
+
+    /// Set the encoding of the encoded words. [Encoding::Custom] isn't
+    /// supported and results in an error when encoding.
+    ///
+    /// # Example
+    /// ```
+    /// use rfc2047_decoder::{Encoder, Encoding};
+    ///
+    /// let encoder = Encoder::new().encoding(Encoding::B);
+    ///
+    /// assert_eq!(encoder.encode("ça va").unwrap(), "=?UTF-8?B?w6dh?= va");
+    /// ```
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Encodes the given string into a RFC 2047 MIME Message Header encoded
+    /// string.
+    pub fn encode<T: AsRef<str>>(&self, input: T) -> Result<String> {
+        let input = input.as_ref();
+        let (label, charset) = self.resolve_charset()?;
+
+        if let Encoding::Custom(letter) = self.encoding {
+            return Err(Error::UnsupportedEncoding(letter as char));
+        }
+
+        let mut encoded_str = String::with_capacity(input.len());
+        let mut position = 0;
+
+        for run in runs_to_encode(input) {
+            encoded_str.push_str(&input[position..run.start]);
+            self.encode_run(&input[run.clone()], label, charset, &mut encoded_str)?;
+            position = run.end;
+        }
+        encoded_str.push_str(&input[position..]);
+
+        Ok(encoded_str)
+    }
+
+    /// Returns the label which is written into the encoded words and the
+    /// charset which the text is encoded with.
+    fn resolve_charset(&self) -> Result<(&str, &'static encoding_rs::Encoding)> {
+        let label = self.charset.trim();
+        let charset = encoding_rs::Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| Error::UnknownCharset(self.charset.clone()))?;
+        let output_charset = charset.output_encoding();
+
+        if output_charset == charset {
+            Ok((label, charset))
+        } else {
+            Ok((output_charset.name(), output_charset))
+        }
+    }
+
+    /// Encodes the run into as many encoded words as needed to keep each of
+    /// them within the maximum length.
+    fn encode_run(
+        &self,
+        run: &str,
+        label: &str,
+        charset: &'static encoding_rs::Encoding,
+        encoded_str: &mut String,
+    ) -> Result<()> {
+        // =?<label>?<encoding>?<encoded text>?=
+        let max_encoded_text_len = encoded_word::MAX_LENGTH.saturating_sub(label.len() + 7);
+
+        let mut chunk_start = 0;
+        let mut chunk_encoded_text = String::new();
+
+        for (index, c) in run.char_indices() {
+            let chunk_end = index + c.len_utf8();
+            let encoded_text = self.encode_text(&run[chunk_start..chunk_end], charset, label)?;
+
+            // a single char which doesn't fit is put into an encoded word anyway
+            if encoded_text.len() > max_encoded_text_len && index > chunk_start {
+                self.push_encoded_word(label, &chunk_encoded_text, encoded_str);
+                encoded_str.push(' ');
+
+                chunk_start = index;
+                chunk_encoded_text =
+                    self.encode_text(&run[chunk_start..chunk_end], charset, label)?;
+            } else {
+                chunk_encoded_text = encoded_text;
+            }
+        }
+
+        self.push_encoded_word(label, &chunk_encoded_text, encoded_str);
+        Ok(())
+    }
+
+    fn push_encoded_word(&self, label: &str, encoded_text: &str, encoded_str: &mut String) {
+        let encoding = match self.encoding {
+            Encoding::B => 'B',
+            _ => 'Q',
+        };
+
+        encoded_str.push_str(&format!("=?{}?{}?{}?=", label, encoding, encoded_text));
+    }
+
+    /// Applies the charset encoding and the transfer encoding (B or Q).
+    fn encode_text(
+        &self,
+        text: &str,
+        charset: &'static encoding_rs::Encoding,
+        label: &str,
+    ) -> Result<String> {
+        let (bytes, _, had_errors) = charset.encode(text);
+
+        if had_errors {
+            let unencodable_char = text
+                .chars()
+                .find(|c| charset.encode(c.encode_utf8(&mut [0; 4])).2)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            return Err(Error::UnencodableChar(unencodable_char, label.to_string()));
+        }
+
+        let encoded_text = match self.encoding {
+            Encoding::B => STANDARD.encode(bytes),
+            _ => encode_q(&bytes),
+        };
+
+        Ok(encoded_text)
+    }
+}
+
+impl Default for Encoder {
+    /// Returns the encoder with the following default "settings":
+    ///
+    /// - `charset`: `UTF-8`
+    /// - `encoding`: [Encoding::Q]
+    fn default() -> Self {
+        Self {
+            charset: "UTF-8".to_string(),
+            encoding: Encoding::Q,
+        }
+    }
+}
+
+fn encode_q(bytes: &[u8]) -> String {
+    let mut encoded_text = String::with_capacity(bytes.len());
+
+    for &byte in bytes {
+        match byte {
+            b' ' => encoded_text.push('_'),
+            _ if byte.is_ascii_alphanumeric() || Q_SAFE_SPECIALS.contains(&byte) => {
+                encoded_text.push(byte as char)
+            }
+            _ => encoded_text.push_str(&format!("={:02X}", byte)),
+        }
+    }
+
+    encoded_text
+}
+
+/// Returns `true` if the word can't be kept as clear text.
+fn needs_encoding(word: &str) -> bool {
+    word.chars().any(|c| !c.is_ascii() || c.is_ascii_control()) || word.contains("=?")
+}
+
+/// Returns the byte ranges of the input which have to be encoded: the words
+/// which need it, merged with the whitespace between them if they are
+/// adjacent.
+fn runs_to_encode(input: &str) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut previous_word_is_encoded = false;
+
+    for word in word_ranges(input) {
+        if !needs_encoding(&input[word.clone()]) {
+            previous_word_is_encoded = false;
+            continue;
+        }
+
+        match runs.last_mut() {
+            Some(run) if previous_word_is_encoded => run.end = word.end,
+            _ => runs.push(word),
+        }
+        previous_word_is_encoded = true;
+    }
+
+    runs
+}
+
+/// Returns the byte ranges of the words of the input, which are separated by
+/// (ASCII) whitespace.
+fn word_ranges(input: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+
+    for (index, byte) in input.bytes().enumerate() {
+        match (byte.is_ascii_whitespace(), word_start) {
+            (true, Some(start)) => {
+                words.push(start..index);
+                word_start = None;
+            }
+            (false, None) => word_start = Some(index),
+            _ => {}
+        }
+    }
+
+    if let Some(start) = word_start {
+        words.push(start..input.len());
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoder, Error};
+    use crate::{decode, Encoding};
+
+    const CORPUS: &[&str] = &[
+        "",
+        "plain ascii",
+        "Café",
+        "  Re: Café crème brûlée  ",
+        "Jörg <jorg@example.com>",
+        "ça va et toi ? oui",
+        "日本語のテキストはとても長いので、いくつかのエンコードされた単語に分割する必要があります。",
+        "Ünïcödé wörds ëvërywhërë änd sömë äscïï ïn bëtwëën, lönger thän 75 chärs",
+        "emoji 😀😃😄😁😆😅😂🤣😊😇🙂🙃😉😌😍🥰😘😗😙😚😋",
+        "tab\tand\x07bell",
+        "looks like =?UTF-8?Q?an?= encoded word",
+    ];
+
+    #[test]
+    fn round_trip() {
+        for encoding in [Encoding::B, Encoding::Q] {
+            for charset in ["UTF-8", "utf-8"] {
+                let encoder = Encoder::new().charset(charset).encoding(encoding.clone());
+
+                for input in CORPUS {
+                    let encoded_str = encoder.encode(input).unwrap();
+                    assert_eq!(decode(&encoded_str).unwrap(), *input, "{}", encoded_str);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encoded_words_are_not_too_long() {
+        for encoding in [Encoding::B, Encoding::Q] {
+            let encoder = Encoder::new().encoding(encoding);
+
+            for input in CORPUS {
+                let encoded_str = encoder.encode(input).unwrap();
+                for word in encoded_str.split(' ').filter(|word| word.starts_with("=?")) {
+                    assert!(word.len() <= 75, "{}", word);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_is_kept() {
+        assert_eq!(
+            Encoder::new().encode("Hello, world!").unwrap(),
+            "Hello, world!"
+        );
+        assert_eq!(
+            Encoder::new().encode("a é b è c").unwrap(),
+            "a =?UTF-8?Q?=C3=A9?= b =?UTF-8?Q?=C3=A8?= c"
+        );
+        assert_eq!(
+            Encoder::new().encode("é è").unwrap(),
+            "=?UTF-8?Q?=C3=A9_=C3=A8?="
+        );
+    }
+
+    #[test]
+    fn long_runs_are_split() {
+        let input = "é".repeat(40);
+        let encoded_str = Encoder::new().encode(&input).unwrap();
+
+        // 10 `é` (=C3=A9) fit into one encoded word
+        assert_eq!(encoded_str.matches("=?UTF-8?Q?").count(), 4);
+        assert_eq!(decode(&encoded_str).unwrap(), input);
+    }
+
+    #[test]
+    fn legacy_charsets() {
+        for (charset, input) in [
+            ("ISO-8859-1", "Keld Jørn Simonsen"),
+            ("windows-1252", "5 € à la crème"),
+            (
+                "Shift_JIS",
+                "日本語のテキストはとても長いので分割する必要があります",
+            ),
+            (
+                "ISO-2022-JP",
+                "日本語のテキストはとても長いので分割する必要があります",
+            ),
+        ] {
+            for encoding in [Encoding::B, Encoding::Q] {
+                let encoder = Encoder::new().charset(charset).encoding(encoding);
+                let encoded_str = encoder.encode(input).unwrap();
+
+                assert!(encoded_str.contains(charset), "{}", encoded_str);
+                assert_eq!(decode(&encoded_str).unwrap(), input, "{}", encoded_str);
+            }
+        }
+
+        // UTF-16 can't be encoded, UTF-8 is used instead
+        assert_eq!(
+            Encoder::new().charset("UTF-16").encode("é").unwrap(),
+            "=?UTF-8?Q?=C3=A9?="
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Encoder::new().charset("x-unknown").encode("é"),
+            Err(Error::UnknownCharset("x-unknown".to_string()))
+        );
+        assert_eq!(
+            Encoder::new().charset("ISO-8859-1").encode("a é 日"),
+            Err(Error::UnencodableChar('日', "ISO-8859-1".to_string()))
+        );
+        assert_eq!(
+            Encoder::new().encoding(Encoding::Custom(b'X')).encode("é"),
+            Err(Error::UnsupportedEncoding('X'))
+        );
+    }
+}
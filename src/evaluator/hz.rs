@@ -0,0 +1,124 @@
+//! Decoder for the HZ encoding (`HZ-GB-2312`) as described in
+//! [RFC 1843](https://datatracker.ietf.org/doc/html/rfc1843).
+//!
+//! The `charset` crate doesn't support HZ (it maps the label to the
+//! replacement encoding), so it's decoded here.
+
+use alloc::{string::String, vec::Vec};
+use charset::Charset;
+
+pub const LABEL: &[u8] = b"hz-gb-2312";
+
+const TILDE: u8 = b'~';
+const REPLACEMENT_CHAR: char = '\u{FFFD}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Ascii,
+    Gb,
+}
+
+/// Returns `true` if the given charset label is the label of HZ.
+pub fn is_hz_label(label: &[u8]) -> bool {
+    label.eq_ignore_ascii_case(LABEL)
+}
+
+/// Decodes the given HZ encoded bytes. Invalid sequences are replaced with
+/// `U+FFFD` and an unterminated `~{` sequence simply ends with the input.
+pub fn decode(bytes: &[u8]) -> String {
+    let mut decoded_str = String::with_capacity(bytes.len());
+    let mut gb_bytes: Vec<u8> = Vec::new();
+    let mut mode = Mode::Ascii;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        let next_byte = bytes.get(index + 1).copied();
+
+        match (mode, byte, next_byte) {
+            (_, TILDE, Some(b'{')) => {
+                mode = Mode::Gb;
+                index += 2;
+            }
+            (_, TILDE, Some(b'}')) => {
+                flush_gb_bytes(&mut gb_bytes, &mut decoded_str);
+                mode = Mode::Ascii;
+                index += 2;
+            }
+            (Mode::Ascii, TILDE, Some(TILDE)) => {
+                decoded_str.push('~');
+                index += 2;
+            }
+            // line continuation
+            (Mode::Ascii, TILDE, Some(b'\n')) => index += 2,
+            (Mode::Ascii, TILDE, _) => {
+                decoded_str.push(REPLACEMENT_CHAR);
+                index += 1;
+            }
+            (Mode::Ascii, _, _) => {
+                decoded_str.push(byte as char);
+                index += 1;
+            }
+            (Mode::Gb, b'\r' | b'\n', _) => {
+                // a line break implicitly ends the GB mode
+                flush_gb_bytes(&mut gb_bytes, &mut decoded_str);
+                decoded_str.push(byte as char);
+                mode = Mode::Ascii;
+                index += 1;
+            }
+            (Mode::Gb, _, _) => {
+                gb_bytes.push(byte | 0x80);
+                index += 1;
+            }
+        }
+    }
+
+    flush_gb_bytes(&mut gb_bytes, &mut decoded_str);
+    decoded_str
+}
+
+/// Decodes the collected GB2312 bytes and appends them to the decoded string.
+fn flush_gb_bytes(gb_bytes: &mut Vec<u8>, decoded_str: &mut String) {
+    if gb_bytes.is_empty() {
+        return;
+    }
+
+    let gb2312 = Charset::for_label(b"GB2312").expect("GB2312 is a known charset");
+    decoded_str.push_str(&gb2312.decode_without_bom_handling(gb_bytes).0);
+    gb_bytes.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    #[test]
+    fn ascii_only() {
+        assert_eq!(decode(b"Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn escaped_tilde() {
+        assert_eq!(decode(b"Hello ~~ world"), "Hello ~ world");
+    }
+
+    #[test]
+    fn gb_mode() {
+        assert_eq!(
+            decode(b"~{<:Ky2;S{#,NpJ)l6HK!#~}Bye."),
+            "己所不欲，勿施於人。Bye."
+        );
+        assert_eq!(decode(b"Hello ~{VPND~} end"), "Hello 中文 end");
+    }
+
+    #[test]
+    fn unterminated_gb_mode() {
+        assert_eq!(decode(b"Hello ~{VPND"), "Hello 中文");
+        assert_eq!(decode(b"~{VPN"), "中\u{FFFD}");
+    }
+
+    #[test]
+    fn line_continuation() {
+        assert_eq!(decode(b"Hello~\nworld"), "Helloworld");
+    }
+}
@@ -0,0 +1,1073 @@
+mod hz;
+
+use alloc::{
+    boxed::Box,
+    string::{self, String, ToString},
+    vec,
+    vec::Vec,
+};
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use core::{convert::Infallible, ops::Range, result};
+use thiserror::Error;
+
+use crate::{
+    decoder::{ControlCharPolicy, MalformedEscapePolicy, RecoverStrategy},
+    lexer::encoded_word::EncodedWord,
+    parser::{self, ClearText, Encoding, ParsedCharset, ParsedEncodedWord, ParsedEncodedWords},
+    Decoder,
+};
+
+/// All errors which the evaluator can throw.
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error(transparent)]
+    DecodeUtf8Error(#[from] string::FromUtf8Error),
+    #[error("{0}")]
+    DecodeBase64Error(base64::DecodeError),
+    #[error("{0}")]
+    DecodeQuotedPrintableError(quoted_printable::QuotedPrintableError),
+    #[error("the char {codepoint:?} at position {position} is above the maximum code point")]
+    CodepointAboveMaximum { codepoint: char, position: usize },
+    #[error(
+        "the `=` at position {position} of the Q encoded text isn't followed by two hex digits"
+    )]
+    MalformedEscape { position: usize },
+    #[error("the decoded string is longer than {max_bytes} bytes")]
+    OutputTooLarge { max_bytes: usize },
+    #[error("the escape at position {position} of the Q encoded text has lowercase hex digits")]
+    InvalidHexCase { position: usize },
+}
+
+// The errors of base64 and quoted_printable only implement `Error` with the
+// `std` feature, so they can't be marked as `#[from]`.
+impl From<base64::DecodeError> for Error {
+    fn from(err: base64::DecodeError) -> Self {
+        Self::DecodeBase64Error(err)
+    }
+}
+
+impl From<quoted_printable::QuotedPrintableError> for Error {
+    fn from(err: quoted_printable::QuotedPrintableError) -> Self {
+        Self::DecodeQuotedPrintableError(err)
+    }
+}
+
+type Result<T> = result::Result<T, Error>;
+
+/// The kind of a [DecodedSegment].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SegmentKind {
+    /// The segment is clear text.
+    Text,
+    /// The segment is an encoded word.
+    Word,
+}
+
+/// An owned, decoded part of the input: either a clear text or an encoded word.
+///
+/// With the `serde` feature it can be (de)serialized, for example to cache
+/// decoded headers without running the decoder again.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, SegmentKind};
+///
+/// let segments = Decoder::new()
+///     .decode_into_vec_segments("Hello =?UTF-8?Q?W=C3=B6rld?=")
+///     .unwrap();
+///
+/// assert_eq!(segments[0].kind, SegmentKind::Text);
+/// assert_eq!(segments[0].decoded, "Hello ");
+/// assert_eq!(segments[0].charset, None);
+///
+/// assert_eq!(segments[1].kind, SegmentKind::Word);
+/// assert_eq!(segments[1].decoded, "Wörld");
+/// assert_eq!(segments[1].charset.as_deref(), Some("UTF-8"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedSegment {
+    /// Whether the segment is clear text or an encoded word.
+    pub kind: SegmentKind,
+    /// The decoded text of the segment.
+    pub decoded: String,
+    /// The charset label of the encoded word, `None` for clear text.
+    pub charset: Option<String>,
+}
+
+/// The source of a part of the decoded string: the byte range of a clear text
+/// or an encoded word in the input.
+///
+/// The whitespace between two encoded words in a row isn't part of any
+/// segment since it's dropped.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, Encoding, SegmentKind};
+///
+/// let message = "Hello =?UTF-8?Q?W=C3=B6rld?=";
+/// let (decoded_str, segments) = Decoder::new().decode_with_diagnostics(message).unwrap();
+///
+/// assert_eq!(decoded_str, "Hello Wörld");
+///
+/// assert_eq!(segments[0].span, 0..6);
+/// assert_eq!(segments[0].kind, SegmentKind::Text);
+///
+/// assert_eq!(&message[segments[1].span.clone()], "=?UTF-8?Q?W=C3=B6rld?=");
+/// assert_eq!(segments[1].kind, SegmentKind::Word);
+/// assert_eq!(segments[1].charset.as_deref(), Some("UTF-8"));
+/// assert_eq!(segments[1].encoding, Some(Encoding::Q));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Segment {
+    /// The byte range of the segment in the input.
+    pub span: Range<usize>,
+    /// Whether the segment is clear text or an encoded word.
+    pub kind: SegmentKind,
+    /// The name of the charset the encoded word is decoded with, `None` for
+    /// clear text and for an unknown charset.
+    pub charset: Option<String>,
+    /// The encoding of the encoded word, `None` for clear text.
+    pub encoding: Option<Encoding>,
+}
+
+/// Every intermediate result of decoding a single encoded word.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, Encoding};
+///
+/// let decoded_words = Decoder::new().decode_detailed("=?UTF-8?B?w6dh?= va").unwrap();
+/// let decoded_word = &decoded_words[0];
+///
+/// assert_eq!(decoded_word.charset_label, "UTF-8");
+/// assert_eq!(decoded_word.encoding, Encoding::B);
+/// assert_eq!(decoded_word.raw_encoded_text, b"w6dh");
+/// assert_eq!(decoded_word.transfer_decoded_bytes, Some(vec![0xc3, 0xa7, b'a']));
+/// assert_eq!(decoded_word.final_text, "ça");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DecodedWord {
+    /// The charset label as it's written in the encoded word.
+    pub charset_label: String,
+    /// The encoding of the encoded word.
+    pub encoding: Encoding,
+    /// The encoded text as it's written in the encoded word.
+    pub raw_encoded_text: Vec<u8>,
+    /// The bytes after applying the transfer decoding (B or Q), or `None` if
+    /// the encoded text couldn't be decoded.
+    pub transfer_decoded_bytes: Option<Vec<u8>>,
+    /// The text after applying the charset decoding. This is what
+    /// [Decoder::decode] emits for this encoded word.
+    pub final_text: String,
+}
+
+/// Decodes the base64 encoded bytes with the standard alphabet, or with the
+/// URL-safe one if that fails and [Decoder::url_safe_base64_fallback] is set.
+fn decode_base64(encoded_bytes: Vec<u8>, decoder: &Decoder) -> Result<Vec<u8>> {
+    let base64_decoder = |alphabet| {
+        let config = GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true);
+        GeneralPurpose::new(alphabet, config)
+    };
+
+    // line breaks may end up in the encoded text through improper
+    // (un)folding, they are never part of the base64 alphabet
+    let encoded_bytes = encoded_bytes
+        .into_iter()
+        .filter(|b| *b != b'\r' && *b != b'\n')
+        .collect::<Vec<u8>>();
+
+    // stops at the first invalid char, so invalid input is rejected early
+    let decoded_bytes = base64_decoder(&alphabet::STANDARD)
+        .decode(&encoded_bytes)
+        .or_else(|err| {
+            if decoder.url_safe_base64_fallback {
+                base64_decoder(&alphabet::URL_SAFE)
+                    .decode(&encoded_bytes)
+                    .map_err(|_| err)
+            } else {
+                Err(err)
+            }
+        })?;
+
+    Ok(decoded_bytes)
+}
+
+/// Decodes the base64 encoded bytes although they are invalid: all chars which
+/// aren't part of the base64 alphabet are ignored, as well as the padding.
+fn decode_base64_lossy(encoded_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let base64_decoder = {
+        let config = GeneralPurposeConfig::new()
+            .with_decode_allow_trailing_bits(true)
+            .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+        GeneralPurpose::new(&alphabet::STANDARD, config)
+    };
+
+    let mut encoded_bytes = encoded_bytes
+        .into_iter()
+        .filter(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/')
+        .collect::<Vec<u8>>();
+
+    // a single char left over can't encode a whole byte
+    if encoded_bytes.len() % 4 == 1 {
+        encoded_bytes.pop();
+    }
+
+    let decoded_bytes = base64_decoder.decode(encoded_bytes)?;
+
+    Ok(decoded_bytes)
+}
+
+/// Decodes the Q encoded text as described in section 4.2 of RFC 2047: a `_`
+/// is a space and a `=` is followed by the two hex digits of a byte. Unlike
+/// the quoted-printable encoding of RFC 2045 there are no (soft) line breaks,
+/// so every other byte, including trailing whitespace, stands for itself.
+///
+/// In the strict parse mode an escape without two uppercase hex digits and
+/// bytes which aren't printable ASCII are errors. Otherwise such an escape is
+/// kept as it is.
+fn decode_q_encoding(
+    encoded_bytes: &[u8],
+    parse_mode: quoted_printable::ParseMode,
+    decoder: &Decoder,
+) -> Result<Vec<u8>> {
+    use quoted_printable::QuotedPrintableError;
+
+    const SPACE: u8 = b' ';
+    const UNDERSCORE: u8 = b'_';
+    const EQUAL_SIGN: u8 = b'=';
+
+    let is_strict = parse_mode == quoted_printable::ParseMode::Strict;
+    let hex_value = |digit: u8| {
+        char::from(digit)
+            .to_digit(16)
+            .map_or(0, |value| value as u8)
+    };
+
+    let mut decoded_bytes = Vec::with_capacity(encoded_bytes.len());
+    let mut position = 0;
+
+    while let Some(&byte) = encoded_bytes.get(position) {
+        match byte {
+            UNDERSCORE if decoder.underscore_as_space => decoded_bytes.push(SPACE),
+            EQUAL_SIGN => match encoded_bytes.get(position + 1..position + 3) {
+                Some(&[high, low]) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                    if is_strict && (high.is_ascii_lowercase() || low.is_ascii_lowercase()) {
+                        return Err(QuotedPrintableError::LowercaseHexOctet.into());
+                    }
+                    decoded_bytes.push(hex_value(high) << 4 | hex_value(low));
+                    position += 3;
+                    continue;
+                }
+                Some(_) if is_strict => return Err(QuotedPrintableError::InvalidHexOctet.into()),
+                None if is_strict => return Err(QuotedPrintableError::IncompleteHexOctet.into()),
+                _ => decoded_bytes.push(EQUAL_SIGN),
+            },
+            byte if is_strict && byte != b'\t' && !(SPACE..=b'~').contains(&byte) => {
+                return Err(QuotedPrintableError::InvalidByte.into());
+            }
+            byte => decoded_bytes.push(byte),
+        }
+        position += 1;
+    }
+
+    Ok(decoded_bytes)
+}
+
+/// Applies the [MalformedEscapePolicy] of the decoder to each `=` of the Q
+/// encoded text which isn't followed by two hex digits.
+fn handle_malformed_escapes(encoded_bytes: Vec<u8>, decoder: &Decoder) -> Result<Vec<u8>> {
+    const EQUAL_SIGN: u8 = b'=';
+
+    let is_malformed_escape = |position: usize| {
+        encoded_bytes[position] == EQUAL_SIGN
+            && !matches!(
+                encoded_bytes.get(position + 1..position + 3),
+                Some([high, low]) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit()
+            )
+    };
+
+    match decoder.malformed_quoted_printable {
+        // escaped, so the quoted-printable decoder doesn't take the chars after
+        // it as part of the escape
+        MalformedEscapePolicy::Keep => Ok((0..encoded_bytes.len())
+            .flat_map(|position| {
+                if is_malformed_escape(position) {
+                    &b"=3D"[..]
+                } else {
+                    &encoded_bytes[position..position + 1]
+                }
+            })
+            .copied()
+            .collect()),
+        MalformedEscapePolicy::Drop => Ok((0..encoded_bytes.len())
+            .filter(|&position| !is_malformed_escape(position))
+            .map(|position| encoded_bytes[position])
+            .collect()),
+        MalformedEscapePolicy::Error => {
+            match (0..encoded_bytes.len()).find(|&position| is_malformed_escape(position)) {
+                Some(position) => Err(Error::MalformedEscape { position }),
+                None => Ok(encoded_bytes),
+            }
+        }
+    }
+}
+
+/// Returns an error for the first escape with lowercase hex digits (like `=e9`)
+/// in the Q encoded text, if [Decoder::strict_q_hex_case] is set.
+fn check_hex_case(encoded_bytes: &[u8], decoder: &Decoder) -> Result<()> {
+    if !decoder.strict_q_hex_case {
+        return Ok(());
+    }
+
+    let has_lowercase_hex_digits = |position: usize| {
+        encoded_bytes[position] == b'='
+            && matches!(
+                encoded_bytes.get(position + 1..position + 3),
+                Some([high, low]) if high.is_ascii_hexdigit()
+                    && low.is_ascii_hexdigit()
+                    && (high.is_ascii_lowercase() || low.is_ascii_lowercase())
+            )
+    };
+
+    match (0..encoded_bytes.len()).find(|&position| has_lowercase_hex_digits(position)) {
+        Some(position) => Err(Error::InvalidHexCase { position }),
+        None => Ok(()),
+    }
+}
+
+/// Applies the transfer decoding of the encoded word. Returns `None` if the
+/// encoded text is invalid and the encoded word should be kept as it is.
+fn decode_with_encoding(
+    encoding: Encoding,
+    encoded_bytes: Vec<u8>,
+    decoder: &Decoder,
+) -> Result<Option<Vec<u8>>> {
+    let decoded_bytes = match encoding {
+        Encoding::B => match decoder.invalid_base64 {
+            RecoverStrategy::Abort => Some(decode_base64(encoded_bytes, decoder)?),
+            RecoverStrategy::Skip | RecoverStrategy::Replace(_) => {
+                decode_base64(encoded_bytes, decoder).ok()
+            }
+            RecoverStrategy::Decode => decode_base64(encoded_bytes.clone(), decoder)
+                .or_else(|_| decode_base64_lossy(encoded_bytes))
+                .ok(),
+        },
+        Encoding::Q => {
+            use quoted_printable::ParseMode;
+
+            check_hex_case(&encoded_bytes, decoder)?;
+
+            match decoder.invalid_quoted_printable {
+                RecoverStrategy::Abort => Some(decode_q_encoding(
+                    &encoded_bytes,
+                    ParseMode::Strict,
+                    decoder,
+                )?),
+                RecoverStrategy::Skip | RecoverStrategy::Replace(_) => {
+                    decode_q_encoding(&encoded_bytes, ParseMode::Strict, decoder).ok()
+                }
+                RecoverStrategy::Decode => {
+                    let encoded_bytes = handle_malformed_escapes(encoded_bytes, decoder)?;
+                    Some(decode_q_encoding(
+                        &encoded_bytes,
+                        ParseMode::Robust,
+                        decoder,
+                    )?)
+                }
+            }
+        }
+        Encoding::Custom(letter) => decoder
+            .transfer_decoders
+            .get(&letter)
+            .and_then(|transfer_decoder| transfer_decoder.decode(&encoded_bytes)),
+    };
+
+    Ok(decoded_bytes)
+}
+
+/// Returns the strategy which applies if the encoded text can't be transfer
+/// decoded with the given encoding, `None` for the custom transfer decoders.
+pub fn transfer_decoding_strategy<'a>(
+    encoding: &Encoding,
+    decoder: &'a Decoder,
+) -> Option<&'a RecoverStrategy> {
+    match encoding {
+        Encoding::B => Some(&decoder.invalid_base64),
+        Encoding::Q => Some(&decoder.invalid_quoted_printable),
+        Encoding::Custom(_) => None,
+    }
+}
+
+/// Returns `true` if encoded words with the given charset label can be decoded
+/// with the charset, the same way as in [decode_with_charset].
+pub fn is_supported_charset(label: &[u8], decoder: &Decoder) -> bool {
+    hz::is_hz_label(label) || parser::is_known_charset(label, decoder)
+}
+
+fn decode_with_charset(
+    charset: Option<ParsedCharset>,
+    label: &[u8],
+    decoded_bytes: Vec<u8>,
+    decoder: &Decoder,
+) -> Result<String> {
+    if let Some(charset_decoder) = &decoder.charset_decoder {
+        let label = String::from_utf8_lossy(label);
+        if let Some(decoded_str) = charset_decoder.decode(&label, &decoded_bytes) {
+            return Ok(strip_bom(decoded_str, decoder));
+        }
+    }
+
+    if hz::is_hz_label(label) {
+        return Ok(strip_bom(hz::decode(&decoded_bytes), decoder));
+    }
+
+    let decoded_str = match charset.map(ParsedCharset::charset) {
+        Some(charset) => match charset.decode(&decoded_bytes) {
+            (decoded_str, _, false) => decoded_str.into_owned(),
+            (decoded_str, _, true) => decode_with_fallback_chain(&decoded_bytes, decoder)
+                .filter(|(_, had_errors)| !had_errors)
+                .map_or_else(
+                    || decoded_str.into_owned(),
+                    |(fallback_str, _)| fallback_str,
+                ),
+        },
+        None => decode_with_fallback_chain(&decoded_bytes, decoder).map_or_else(
+            || charset::decode_ascii(&decoded_bytes).into_owned(),
+            |(fallback_str, _)| fallback_str,
+        ),
+    };
+
+    Ok(strip_bom(decoded_str, decoder))
+}
+
+/// Removes a leading BOM from the decoded string, if [Decoder::strip_bom] is
+/// set.
+fn strip_bom(mut decoded_str: String, decoder: &Decoder) -> String {
+    if decoder.strip_bom && decoded_str.starts_with('\u{FEFF}') {
+        decoded_str.remove(0);
+    }
+
+    decoded_str
+}
+
+/// Decodes the bytes with the first charset of [Decoder::charset_fallback_chain]
+/// which decodes them without errors, or with the first known charset of the
+/// chain if none does. The flag is `true` if the decoded string has errors.
+fn decode_with_fallback_chain(decoded_bytes: &[u8], decoder: &Decoder) -> Option<(String, bool)> {
+    let mut fallback_results = decoder
+        .charset_fallback_chain
+        .iter()
+        .filter_map(|label| parser::charset_for_label(label.as_bytes(), decoder))
+        .map(|charset| {
+            let (decoded_str, _, had_errors) = charset.decode(decoded_bytes);
+            (decoded_str.into_owned(), had_errors)
+        });
+
+    let first_result = fallback_results.next()?;
+    if !first_result.1 {
+        return Some(first_result);
+    }
+
+    fallback_results
+        .find(|(_, had_errors)| !had_errors)
+        .or(Some(first_result))
+}
+
+fn decode_utf8_string(clear_text: ClearText, decoder: &Decoder) -> Result<String> {
+    let clear_text_charset = decoder
+        .clear_text_charset
+        .as_ref()
+        .or(decoder.charset_override.as_ref())
+        .and_then(|label| parser::charset_for_label(label.as_bytes(), decoder));
+
+    if let Some(charset) = clear_text_charset {
+        return Ok(charset.decode(&clear_text).0.into_owned());
+    }
+
+    #[cfg(feature = "chardetng")]
+    if decoder.detect_charset && core::str::from_utf8(&clear_text).is_err() {
+        return Ok(detect_charset(&clear_text)
+            .decode(&clear_text)
+            .0
+            .into_owned());
+    }
+
+    let decoded_str = match decoder.invalid_utf8 {
+        RecoverStrategy::Abort => String::from_utf8(clear_text)?,
+        RecoverStrategy::Decode => String::from_utf8_lossy(&clear_text).into_owned(),
+        RecoverStrategy::Skip => clear_text
+            .utf8_chunks()
+            .map(|chunk| chunk.valid())
+            .collect(),
+        RecoverStrategy::Replace(ref replacement) => {
+            let mut decoded_str = String::with_capacity(clear_text.len());
+            let mut follows_invalid_bytes = false;
+
+            // adjacent invalid sequences are replaced only once
+            for chunk in clear_text.utf8_chunks() {
+                decoded_str.push_str(chunk.valid());
+                let starts_invalid_run = !chunk.valid().is_empty() || !follows_invalid_bytes;
+                if !chunk.invalid().is_empty() && starts_invalid_run {
+                    decoded_str.push_str(replacement);
+                }
+                follows_invalid_bytes = !chunk.invalid().is_empty();
+            }
+
+            decoded_str
+        }
+    };
+
+    Ok(decoded_str)
+}
+
+/// Guesses the charset of the given bytes which aren't valid UTF-8.
+#[cfg(feature = "chardetng")]
+fn detect_charset(bytes: &[u8]) -> charset::Charset {
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    charset::Charset::for_encoding(detector.guess(None, false))
+}
+
+/// Normalizes the decoded string to NFC, if [Decoder::normalize_nfc] is set.
+#[cfg(feature = "unicode-normalization")]
+pub fn normalize(decoded_str: String, decoder: &Decoder) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    if decoder.normalize_nfc && !unicode_normalization::is_nfc(&decoded_str) {
+        decoded_str.nfc().collect()
+    } else {
+        decoded_str
+    }
+}
+
+#[cfg(not(feature = "unicode-normalization"))]
+pub fn normalize(decoded_str: String, _decoder: &Decoder) -> String {
+    decoded_str
+}
+
+/// Returns `true` if the decoded string is normalized to NFC. The decoded
+/// parts are normalized on their own, but a combining char may follow its
+/// base char in another part, so the whole decoded string has to be
+/// normalized again.
+#[cfg(feature = "unicode-normalization")]
+fn normalizes_nfc(decoder: &Decoder) -> bool {
+    decoder.normalize_nfc
+}
+
+#[cfg(not(feature = "unicode-normalization"))]
+fn normalizes_nfc(_decoder: &Decoder) -> bool {
+    false
+}
+
+/// Strips or escapes the control characters of the decoded string according
+/// to the [ControlCharPolicy] of the decoder.
+fn apply_control_char_policy(decoded_str: String, decoder: &Decoder) -> String {
+    let is_control = |c: char| c.is_control() && c != '\t';
+
+    match decoder.control_char_policy {
+        ControlCharPolicy::Keep => decoded_str,
+        ControlCharPolicy::Strip => decoded_str.chars().filter(|&c| !is_control(c)).collect(),
+        ControlCharPolicy::Escape if !decoded_str.contains(is_control) => decoded_str,
+        ControlCharPolicy::Escape => {
+            let mut escaped_str = String::with_capacity(decoded_str.len());
+
+            for c in decoded_str.chars() {
+                match c {
+                    '\x7f' => escaped_str.push_str("^?"),
+                    c if c < ' ' && c != '\t' => {
+                        escaped_str.push('^');
+                        escaped_str.push((c as u8 + b'@') as char);
+                    }
+                    c if is_control(c) => escaped_str.extend(c.escape_unicode()),
+                    c => escaped_str.push(c),
+                }
+            }
+
+            escaped_str
+        }
+    }
+}
+
+fn decode_parsed_encoded_word(
+    charset: Option<ParsedCharset>,
+    encoding: Encoding,
+    encoded_text: Vec<u8>,
+    source: EncodedWord,
+    decoder: &Decoder,
+) -> Result<String> {
+    let strategy = transfer_decoding_strategy(&encoding, decoder);
+
+    match decode_with_encoding(encoding.clone(), encoded_text, decoder)? {
+        Some(decoded_bytes) => {
+            decode_with_charset(charset, &source.charset, decoded_bytes, decoder)
+        }
+        None => {
+            let replacement = decoder.undecodable_replacement(&source, strategy);
+            Ok(String::from_utf8_lossy(&replacement).into_owned())
+        }
+    }
+}
+
+fn decode_parsed_encoded_word_detailed(
+    charset: Option<ParsedCharset>,
+    encoding: Encoding,
+    encoded_text: Vec<u8>,
+    source: EncodedWord,
+    decoder: &Decoder,
+) -> Result<DecodedWord> {
+    let transfer_decoded_bytes =
+        decode_with_encoding(encoding.clone(), encoded_text.clone(), decoder)?;
+
+    let final_text = match &transfer_decoded_bytes {
+        Some(decoded_bytes) => {
+            decode_with_charset(charset, &source.charset, decoded_bytes.clone(), decoder)?
+        }
+        None => {
+            let strategy = transfer_decoding_strategy(&encoding, decoder);
+            let replacement = decoder.undecodable_replacement(&source, strategy);
+            String::from_utf8_lossy(&replacement).into_owned()
+        }
+    };
+
+    let final_text = normalize(apply_control_char_policy(final_text, decoder), decoder);
+
+    Ok(DecodedWord {
+        charset_label: String::from_utf8_lossy(&source.charset).into_owned(),
+        encoding,
+        raw_encoded_text: encoded_text,
+        transfer_decoded_bytes,
+        final_text,
+    })
+}
+
+pub fn decode_parsed_word(
+    parsed_encoded_word: ParsedEncodedWord,
+    decoder: &Decoder,
+) -> Result<String> {
+    let decoded_str = match parsed_encoded_word {
+        ParsedEncodedWord::ClearText(clear_text) => decode_utf8_string(clear_text, decoder),
+        ParsedEncodedWord::EncodedWord {
+            charset,
+            encoding,
+            encoded_text,
+            source,
+        } => decode_parsed_encoded_word(charset, encoding, encoded_text, source, decoder),
+    }?;
+
+    Ok(normalize(
+        apply_control_char_policy(decoded_str, decoder),
+        decoder,
+    ))
+}
+
+/// Groups adjacent encoded words with the same charset and encoding, if
+/// [Decoder::merge_adjacent_same_charset] is set. Otherwise every parsed
+/// encoded word is its own group.
+fn group_words(
+    parsed_encoded_words: ParsedEncodedWords,
+    decoder: &Decoder,
+) -> Vec<ParsedEncodedWords> {
+    group_parsed_words(
+        parsed_encoded_words.into_iter().map(Ok::<_, Infallible>),
+        decoder,
+    )
+    .flatten()
+    .collect()
+}
+
+/// Groups the parsed encoded words lazily like [group_words]. A word which
+/// couldn't be parsed is yielded as its own error and ends the group before
+/// it.
+pub fn group_parsed_words<'a, I, E>(
+    parsed_encoded_words: I,
+    decoder: &'a Decoder,
+) -> impl Iterator<Item = result::Result<ParsedEncodedWords, E>> + 'a
+where
+    I: IntoIterator<Item = result::Result<ParsedEncodedWord, E>>,
+    I::IntoIter: 'a,
+{
+    let mut parsed_encoded_words = parsed_encoded_words.into_iter().peekable();
+
+    core::iter::from_fn(move || {
+        let mut group = match parsed_encoded_words.next()? {
+            Ok(parsed_encoded_word) => vec![parsed_encoded_word],
+            Err(err) => return Some(Err(err)),
+        };
+
+        while let Some(Ok(next_word)) = parsed_encoded_words.peek() {
+            if !decoder.merge_adjacent_same_charset
+                || !have_same_charset(group.last().unwrap(), next_word)
+            {
+                break;
+            }
+
+            if let Some(Ok(next_word)) = parsed_encoded_words.next() {
+                group.push(next_word);
+            }
+        }
+
+        Some(Ok(group))
+    })
+}
+
+fn have_same_charset(word: &ParsedEncodedWord, other_word: &ParsedEncodedWord) -> bool {
+    match (word, other_word) {
+        (
+            ParsedEncodedWord::EncodedWord {
+                charset,
+                encoding,
+                source,
+                ..
+            },
+            ParsedEncodedWord::EncodedWord {
+                charset: other_charset,
+                encoding: other_encoding,
+                source: other_source,
+                ..
+            },
+        ) => {
+            charset == other_charset
+                && encoding == other_encoding
+                && source.charset.eq_ignore_ascii_case(&other_source.charset)
+        }
+        _ => false,
+    }
+}
+
+/// Decodes a group of parsed encoded words. The transfer decoded bytes of
+/// multiple encoded words are concatenated and decoded with their charset at
+/// once, unless one of them can't be transfer decoded.
+pub fn decode_group(mut group: ParsedEncodedWords, decoder: &Decoder) -> Result<String> {
+    if group.len() == 1 {
+        return decode_parsed_word(group.remove(0), decoder);
+    }
+
+    let mut merged_bytes = Vec::new();
+    for parsed_encoded_word in &group {
+        if let ParsedEncodedWord::EncodedWord {
+            encoding,
+            encoded_text,
+            ..
+        } = parsed_encoded_word
+        {
+            match decode_with_encoding(encoding.clone(), encoded_text.clone(), decoder)? {
+                Some(decoded_bytes) => merged_bytes.extend(decoded_bytes),
+                None => {
+                    return group
+                        .into_iter()
+                        .map(|parsed_encoded_word| decode_parsed_word(parsed_encoded_word, decoder))
+                        .collect();
+                }
+            }
+        }
+    }
+
+    match group.remove(0) {
+        ParsedEncodedWord::EncodedWord {
+            charset, source, ..
+        } => {
+            let decoded_str = decode_with_charset(charset, &source.charset, merged_bytes, decoder)?;
+            Ok(normalize(
+                apply_control_char_policy(decoded_str, decoder),
+                decoder,
+            ))
+        }
+        ParsedEncodedWord::ClearText(clear_text) => decode_utf8_string(clear_text, decoder),
+    }
+}
+
+/// Checks the decoded parts against [Decoder::max_output_bytes] and
+/// [Decoder::max_codepoint]. The bytes and chars of the parts are counted to
+/// check the length of and report the position in the whole decoded string. A
+/// part which fails the check isn't counted.
+pub struct OutputCheck<'a> {
+    decoder: &'a Decoder,
+    position: usize,
+    output_bytes: usize,
+}
+
+impl<'a> OutputCheck<'a> {
+    pub fn new(decoder: &'a Decoder) -> Self {
+        Self {
+            decoder,
+            position: 0,
+            output_bytes: 0,
+        }
+    }
+
+    pub fn check(&mut self, decoded_part: String) -> Result<String> {
+        if let Some(max_bytes) = self.decoder.max_output_bytes {
+            if self.output_bytes + decoded_part.len() > max_bytes {
+                return Err(Error::OutputTooLarge { max_bytes });
+            }
+            self.output_bytes += decoded_part.len();
+        }
+
+        let max_codepoint = match self.decoder.max_codepoint {
+            Some(max_codepoint) => max_codepoint,
+            None => return Ok(decoded_part),
+        };
+
+        for (index, c) in decoded_part.chars().enumerate() {
+            if c > max_codepoint {
+                return Err(Error::CodepointAboveMaximum {
+                    codepoint: c,
+                    position: self.position + index,
+                });
+            }
+        }
+        self.position += decoded_part.chars().count();
+
+        Ok(decoded_part)
+    }
+}
+
+pub fn run(parsed_encoded_words: ParsedEncodedWords, decoder: &Decoder) -> Result<String> {
+    let mut decoded_str = String::new();
+    run_into_string(parsed_encoded_words, decoder, &mut decoded_str)?;
+
+    Ok(decoded_str)
+}
+
+/// Decodes the parsed encoded words like [run] but appends the decoded string
+/// to the given buffer. The buffer is left untouched if an error is returned.
+pub fn run_into_string(
+    parsed_encoded_words: ParsedEncodedWords,
+    decoder: &Decoder,
+    buffer: &mut String,
+) -> Result<()> {
+    let original_len = buffer.len();
+    let mut output_check = OutputCheck::new(decoder);
+
+    for group in group_words(parsed_encoded_words, decoder) {
+        match decode_group(group, decoder).and_then(|part| output_check.check(part)) {
+            Ok(decoded_word) => buffer.push_str(&decoded_word),
+            Err(err) => {
+                buffer.truncate(original_len);
+                return Err(err);
+            }
+        }
+    }
+
+    if normalizes_nfc(decoder) {
+        let decoded_str = buffer.split_off(original_len);
+        buffer.push_str(&normalize(decoded_str, decoder));
+    }
+
+    Ok(())
+}
+
+/// Decodes the parsed encoded words like [run], but into a buffer of exactly
+/// the length of the decoded string, so boxing it doesn't reallocate.
+pub fn run_boxed(parsed_encoded_words: ParsedEncodedWords, decoder: &Decoder) -> Result<Box<str>> {
+    let mut output_check = OutputCheck::new(decoder);
+
+    let decoded_parts = group_words(parsed_encoded_words, decoder)
+        .into_iter()
+        .map(|group| decode_group(group, decoder).and_then(|part| output_check.check(part)))
+        .collect::<Result<Vec<String>>>()?;
+
+    let mut decoded_str = String::with_capacity(decoded_parts.iter().map(String::len).sum());
+    for decoded_part in &decoded_parts {
+        decoded_str.push_str(decoded_part);
+    }
+
+    Ok(normalize(decoded_str, decoder).into_boxed_str())
+}
+
+/// Applies only the transfer decoding (B or Q) to the encoded words and
+/// concatenates the decoded bytes with the bytes of the clear text, without
+/// decoding any charset.
+pub fn run_to_bytes(
+    parsed_encoded_words: ParsedEncodedWords,
+    decoder: &Decoder,
+) -> Result<Vec<u8>> {
+    let mut decoded_bytes = Vec::new();
+
+    for parsed_encoded_word in parsed_encoded_words {
+        match parsed_encoded_word {
+            ParsedEncodedWord::ClearText(clear_text) => decoded_bytes.extend(clear_text),
+            ParsedEncodedWord::EncodedWord {
+                encoding,
+                encoded_text,
+                source,
+                ..
+            } => {
+                let strategy = transfer_decoding_strategy(&encoding, decoder);
+                match decode_with_encoding(encoding.clone(), encoded_text, decoder)? {
+                    Some(transfer_decoded_bytes) => decoded_bytes.extend(transfer_decoded_bytes),
+                    None => {
+                        decoded_bytes.extend(decoder.undecodable_replacement(&source, strategy))
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(decoded_bytes)
+}
+
+/// Decodes the parsed encoded words like [run] but appends the decoded bytes
+/// to the buffer. On error the buffer is truncated to its original length.
+pub fn run_into(
+    parsed_encoded_words: ParsedEncodedWords,
+    decoder: &Decoder,
+    buffer: &mut Vec<u8>,
+) -> Result<usize> {
+    if normalizes_nfc(decoder) {
+        let decoded_str = run(parsed_encoded_words, decoder)?;
+        buffer.extend(decoded_str.as_bytes());
+        return Ok(decoded_str.len());
+    }
+
+    let original_len = buffer.len();
+    let mut output_check = OutputCheck::new(decoder);
+
+    for group in group_words(parsed_encoded_words, decoder) {
+        match decode_group(group, decoder).and_then(|part| output_check.check(part)) {
+            Ok(decoded_word) => buffer.extend(decoded_word.as_bytes()),
+            Err(err) => {
+                buffer.truncate(original_len);
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(buffer.len() - original_len)
+}
+
+/// Decodes the parsed encoded words like [run] up to the first one which can't
+/// be parsed or decoded. The flag is `true` if it stopped early.
+pub fn run_until_error<I>(parsed_encoded_words: I, decoder: &Decoder) -> (String, bool)
+where
+    I: IntoIterator<Item = result::Result<ParsedEncodedWord, parser::Error>>,
+{
+    let mut stopped_early = false;
+    let parsed_encoded_words = parsed_encoded_words
+        .into_iter()
+        .map_while(|parsed_encoded_word| {
+            stopped_early = parsed_encoded_word.is_err();
+            parsed_encoded_word.ok()
+        })
+        .collect();
+
+    let mut decoded_str = String::new();
+    let mut output_check = OutputCheck::new(decoder);
+
+    for group in group_words(parsed_encoded_words, decoder) {
+        match decode_group(group, decoder).and_then(|part| output_check.check(part)) {
+            Ok(decoded_word) => decoded_str.push_str(&decoded_word),
+            Err(_) => return (normalize(decoded_str, decoder), true),
+        }
+    }
+
+    (normalize(decoded_str, decoder), stopped_early)
+}
+
+/// Decodes each parsed encoded word like [run] but keeps them as separate
+/// segments. Merged encoded words are a single segment.
+pub fn run_segments(
+    parsed_encoded_words: ParsedEncodedWords,
+    decoder: &Decoder,
+) -> Result<Vec<DecodedSegment>> {
+    let mut output_check = OutputCheck::new(decoder);
+
+    group_words(parsed_encoded_words, decoder)
+        .into_iter()
+        .map(|group| {
+            let (kind, charset) = match &group[0] {
+                ParsedEncodedWord::ClearText(_) => (SegmentKind::Text, None),
+                ParsedEncodedWord::EncodedWord { source, .. } => (
+                    SegmentKind::Word,
+                    Some(String::from_utf8_lossy(&source.charset).into_owned()),
+                ),
+            };
+
+            Ok(DecodedSegment {
+                kind,
+                decoded: output_check.check(decode_group(group, decoder)?)?,
+                charset,
+            })
+        })
+        .collect()
+}
+
+/// Returns the segment of each parsed encoded word, given the byte range of
+/// the token it was parsed from.
+pub fn segments(
+    parsed_encoded_words: &[ParsedEncodedWord],
+    spans: Vec<Range<usize>>,
+) -> Vec<Segment> {
+    parsed_encoded_words
+        .iter()
+        .zip(spans)
+        .map(|(parsed_encoded_word, span)| match parsed_encoded_word {
+            ParsedEncodedWord::ClearText(_) => Segment {
+                span,
+                kind: SegmentKind::Text,
+                charset: None,
+                encoding: None,
+            },
+            ParsedEncodedWord::EncodedWord {
+                charset, encoding, ..
+            } => Segment {
+                span,
+                kind: SegmentKind::Word,
+                charset: charset.map(|charset| charset.name().to_string()),
+                encoding: Some(encoding.clone()),
+            },
+        })
+        .collect()
+}
+
+/// Decodes only the encoded words and returns every intermediate result of them.
+pub fn run_detailed(
+    parsed_encoded_words: ParsedEncodedWords,
+    decoder: &Decoder,
+) -> Result<Vec<DecodedWord>> {
+    parsed_encoded_words
+        .into_iter()
+        .filter_map(|parsed_encoded_word| match parsed_encoded_word {
+            ParsedEncodedWord::ClearText(_) => None,
+            ParsedEncodedWord::EncodedWord {
+                charset,
+                encoding,
+                encoded_text,
+                source,
+            } => Some(decode_parsed_encoded_word_detailed(
+                charset,
+                encoding,
+                encoded_text,
+                source,
+                decoder,
+            )),
+        })
+        .collect()
+}
+
+/// Decodes each parsed encoded word like [run] but only sums up the length
+/// (in bytes) of the decoded strings instead of concatenating them.
+pub fn count_bytes(parsed_encoded_words: ParsedEncodedWords, decoder: &Decoder) -> Result<usize> {
+    if normalizes_nfc(decoder) {
+        return run(parsed_encoded_words, decoder).map(|decoded_str| decoded_str.len());
+    }
+
+    let mut output_check = OutputCheck::new(decoder);
+
+    group_words(parsed_encoded_words, decoder)
+        .into_iter()
+        .map(|group| {
+            decode_group(group, decoder)
+                .and_then(|part| output_check.check(part))
+                .map(|s| s.len())
+        })
+        .sum()
+}
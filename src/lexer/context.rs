@@ -0,0 +1,122 @@
+//! Finds encoded words in places of a header where RFC 2047 doesn't allow them.
+
+use super::{SpannedTokens, Token};
+use crate::{decoder::HeaderContext, Decoder};
+
+/// Returns the index of the first encoded word which isn't allowed in the
+/// [HeaderContext] of the decoder.
+pub(super) fn first_misplaced_encoded_word(
+    spanned_tokens: &SpannedTokens,
+    encoded_bytes: &[u8],
+    decoder: &Decoder,
+) -> Option<usize> {
+    match decoder.header_context {
+        HeaderContext::Unstructured => None,
+        HeaderContext::MessageId => spanned_tokens
+            .iter()
+            .position(|(token, _span)| matches!(token, Token::EncodedWord(_))),
+        HeaderContext::Address => {
+            first_misplaced_in_address(spanned_tokens, encoded_bytes, decoder)
+        }
+    }
+}
+
+/// Removes the backslashes of the quoted pairs (like `\"`) inside the quoted
+/// strings of the clear text, if encoded words are decoded in quoted strings
+/// of addresses (see [Decoder::decode_in_quoted_strings]).
+pub(super) fn unescape_quoted_strings(tokens: SpannedTokens, decoder: &Decoder) -> SpannedTokens {
+    if decoder.header_context != HeaderContext::Address || !decoder.decode_in_quoted_strings {
+        return tokens;
+    }
+
+    let mut state = AddressState::default();
+
+    tokens
+        .into_iter()
+        .map(|(token, span)| match token {
+            Token::ClearText(clear_text) => (
+                Token::ClearText(
+                    clear_text
+                        .into_iter()
+                        .filter(|&byte| !state.update_byte(byte) || !state.in_quoted_string)
+                        .collect(),
+                ),
+                span,
+            ),
+            encoded_word => (encoded_word, span),
+        })
+        .collect()
+}
+
+/// Encoded words are only allowed in the display name (or in a comment) of an
+/// address, but neither in a quoted string (unless
+/// [Decoder::decode_in_quoted_strings] is set) nor in the address itself.
+fn first_misplaced_in_address(
+    spanned_tokens: &SpannedTokens,
+    encoded_bytes: &[u8],
+    decoder: &Decoder,
+) -> Option<usize> {
+    let mut state = AddressState::default();
+    let mut previous_end = 0;
+
+    for (index, (token, span)) in spanned_tokens.iter().enumerate() {
+        state.update(&encoded_bytes[previous_end..span.start]);
+        previous_end = span.end;
+
+        match token {
+            Token::ClearText(_) => state.update(&encoded_bytes[span.clone()]),
+            Token::EncodedWord(_) => {
+                let is_in_addr_spec = span.start.checked_sub(1).map(|i| encoded_bytes[i])
+                    == Some(b'@')
+                    || encoded_bytes.get(span.end) == Some(&b'@');
+
+                let is_in_quoted_string =
+                    state.in_quoted_string && !decoder.decode_in_quoted_strings;
+
+                if is_in_quoted_string || state.angle_depth > 0 || is_in_addr_spec {
+                    return Some(index);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Default)]
+struct AddressState {
+    in_quoted_string: bool,
+    is_escaped: bool,
+    angle_depth: usize,
+    comment_depth: usize,
+}
+
+impl AddressState {
+    fn update(&mut self, clear_text: &[u8]) {
+        for &byte in clear_text {
+            self.update_byte(byte);
+        }
+    }
+
+    /// Returns `true` if the byte is the backslash of a quoted pair.
+    fn update_byte(&mut self, byte: u8) -> bool {
+        if self.is_escaped {
+            self.is_escaped = false;
+            return false;
+        }
+
+        match byte {
+            b'\\' if self.in_quoted_string || self.comment_depth > 0 => self.is_escaped = true,
+            b'"' if self.comment_depth == 0 => self.in_quoted_string = !self.in_quoted_string,
+            _ if self.in_quoted_string => {}
+            b'(' => self.comment_depth += 1,
+            b')' => self.comment_depth = self.comment_depth.saturating_sub(1),
+            _ if self.comment_depth > 0 => {}
+            b'<' => self.angle_depth += 1,
+            b'>' => self.angle_depth = self.angle_depth.saturating_sub(1),
+            _ => {}
+        }
+
+        self.is_escaped
+    }
+}
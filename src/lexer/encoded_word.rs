@@ -1,31 +1,109 @@
-use std::fmt::Display;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::Display,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
-use super::QUESTION_MARK;
+use super::{lex, Error, Token, QUESTION_MARK};
+use crate::Decoder;
 
 pub const PREFIX: &[u8] = "=?".as_bytes();
-pub const SUFFIX: &[u8] = "?=".as_bytes();
 pub const MAX_LENGTH: usize = 75;
+pub const LANGUAGE_SEPARATOR: u8 = b'*';
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A single encoded word like `=?UTF-8?Q?str?=`, split into its parts but not
+/// decoded yet.
+///
+/// It can be parsed from its wire form with [str::parse] and turned back into
+/// it with [ToString::to_string].
+///
+/// An optional language tag after the charset, like in `=?UTF-8*en?Q?str?=`
+/// (see [RFC 2231](https://datatracker.ietf.org/doc/html/rfc2231#section-5)),
+/// is split off into [EncodedWord::language], so only the charset is used for
+/// decoding.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::EncodedWord;
+///
+/// let encoded_word: EncodedWord = "=?UTF-8?Q?str?=".parse().unwrap();
+///
+/// assert_eq!(encoded_word.charset, b"UTF-8");
+/// assert_eq!(encoded_word.encoding, b"Q");
+/// assert_eq!(encoded_word.encoded_text, b"str");
+/// assert_eq!(encoded_word.language, None);
+/// assert_eq!(encoded_word.to_string(), "=?UTF-8?Q?str?=");
+///
+/// let encoded_word: EncodedWord = "=?UTF-8*en?Q?str?=".parse().unwrap();
+///
+/// assert_eq!(encoded_word.charset, b"UTF-8");
+/// assert_eq!(encoded_word.language.as_deref(), Some("en"));
+/// assert_eq!(encoded_word.to_string(), "=?UTF-8*en?Q?str?=");
+/// ```
+#[derive(Debug, Clone)]
 pub struct EncodedWord {
     pub charset: Vec<u8>,
+    /// The language tag after the charset, if there's one.
+    pub language: Option<String>,
     pub encoding: Vec<u8>,
     pub encoded_text: Vec<u8>,
+    /// The encoded word as it's written in the input, if it differs from the
+    /// encoded word built from its parts (like with
+    /// [Decoder::trim_field_whitespace]).
+    pub(super) original: Option<Vec<u8>>,
 }
 
 impl EncodedWord {
+    /// Creates the encoded word from its parts. A language tag at the end of
+    /// the charset (separated by `*`) is split off into
+    /// [EncodedWord::language].
     pub fn new(charset: Vec<u8>, encoding: Vec<u8>, encoded_text: Vec<u8>) -> Self {
+        let (charset, language) = split_language(charset);
+
         Self {
             charset,
+            language,
             encoding,
             encoded_text,
+            original: None,
+        }
+    }
+
+    /// Remembers the bytes of the encoded word in the input, so it can be kept
+    /// byte-exact if it isn't decoded.
+    pub(crate) fn with_original(mut self, original: &[u8], delimiter: u8) -> Self {
+        if self.get_bytes_with_delimiter(delimiter) != original {
+            self.original = Some(original.to_vec());
+        }
+
+        self
+    }
+
+    /// Returns the encoded word as it's written in the input.
+    pub(crate) fn original_bytes(&self, delimiter: u8) -> Vec<u8> {
+        match &self.original {
+            Some(original) => original.clone(),
+            None => self.get_bytes_with_delimiter(delimiter),
         }
     }
 
-    pub fn from_parser(((charset, encoding), encoded_text): ((Vec<u8>, Vec<u8>), Vec<u8>)) -> Self {
+    #[cfg(all(feature = "chumsky", not(feature = "minimal")))]
+    pub(super) fn from_parser(
+        ((charset, encoding), encoded_text): ((Vec<u8>, Vec<u8>), Vec<u8>),
+    ) -> Self {
         Self::new(charset, encoding, encoded_text)
     }
 
+    /// Returns `true` if the encoded word has neither an encoding nor encoded
+    /// text (like `=???=`), so there's nothing to decode.
+    pub fn is_empty(&self) -> bool {
+        self.encoding.is_empty() && self.encoded_text.is_empty()
+    }
+
     /// Returns the amount of `char`s for this encoded word
     pub fn len(&self) -> usize {
         self.get_bytes(true).len()
@@ -35,29 +113,175 @@ impl EncodedWord {
         let mut bytes = Vec::new();
 
         if with_delimiters {
-            bytes.extend(PREFIX);
-            bytes.extend(&self.charset);
-            bytes.extend(&[QUESTION_MARK]);
-            bytes.extend(&self.encoding);
-            bytes.extend(&[QUESTION_MARK]);
-            bytes.extend(&self.encoded_text);
-            bytes.extend(SUFFIX);
+            bytes = self.get_bytes_with_delimiter(QUESTION_MARK);
         } else {
-            bytes.extend(&self.charset);
+            bytes.extend(self.charset_with_language());
             bytes.extend(&self.encoding);
             bytes.extend(&self.encoded_text);
         }
 
         bytes
     }
+
+    /// Returns the encoded word with the given byte instead of `?` between its
+    /// parts and before the closing `=`.
+    pub fn get_bytes_with_delimiter(&self, delimiter: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend(PREFIX);
+        bytes.extend(self.charset_with_language());
+        bytes.push(delimiter);
+        bytes.extend(&self.encoding);
+        bytes.push(delimiter);
+        bytes.extend(&self.encoded_text);
+        bytes.extend(&[delimiter, b'=']);
+
+        bytes
+    }
+
+    /// Returns the charset together with the language tag, as it's written
+    /// in the encoded word.
+    fn charset_with_language(&self) -> Vec<u8> {
+        let mut bytes = self.charset.clone();
+
+        if let Some(language) = &self.language {
+            bytes.push(LANGUAGE_SEPARATOR);
+            bytes.extend(language.as_bytes());
+        }
+
+        bytes
+    }
+}
+
+/// Splits the charset token of an encoded word into the charset and the
+/// language tag after it.
+fn split_language(mut charset: Vec<u8>) -> (Vec<u8>, Option<String>) {
+    match charset.iter().position(|&byte| byte == LANGUAGE_SEPARATOR) {
+        Some(position) => {
+            let language = charset.split_off(position + 1);
+            charset.pop();
+            (
+                charset,
+                Some(String::from_utf8_lossy(&language).into_owned()),
+            )
+        }
+        None => (charset, None),
+    }
+}
+
+// Two encoded words are equal if their parts are, regardless of how they
+// were written in the input.
+impl PartialEq for EncodedWord {
+    fn eq(&self, other: &Self) -> bool {
+        self.charset == other.charset
+            && self.language == other.language
+            && self.encoding == other.encoding
+            && self.encoded_text == other.encoded_text
+    }
+}
+
+impl Eq for EncodedWord {}
+
+impl Hash for EncodedWord {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.charset.hash(state);
+        self.language.hash(state);
+        self.encoding.hash(state);
+        self.encoded_text.hash(state);
+    }
 }
 
 impl Display for EncodedWord {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let charset = String::from_utf8(self.charset.clone()).unwrap();
-        let encoding = String::from_utf8(self.encoding.clone()).unwrap();
-        let encoded_text = String::from_utf8(self.encoded_text.clone()).unwrap();
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Bytes which aren't valid UTF-8 (like a Latin-1 byte in a lenient
+        // encoded text) are written as U+FFFD instead of panicking.
+        let charset = String::from_utf8_lossy(&self.charset_with_language()).into_owned();
+        let encoding = String::from_utf8_lossy(&self.encoding);
+        let encoded_text = String::from_utf8_lossy(&self.encoded_text);
 
         write!(f, "=?{}?{}?{}?=", charset, encoding, encoded_text)
     }
 }
+
+impl FromStr for EncodedWord {
+    type Err = Error;
+
+    /// Parses exactly one encoded word, without any text or whitespace around
+    /// it. The length limit of 75 chars isn't checked.
+    fn from_str(encoded_str: &str) -> Result<Self, Self::Err> {
+        let spanned_tokens = lex(encoded_str.as_bytes(), &Decoder::new())?;
+
+        match spanned_tokens.as_slice() {
+            [(Token::EncodedWord(encoded_word), span)] if span.len() == encoded_str.len() => {
+                Ok(encoded_word.clone())
+            }
+            _ => Err(Error::InvalidEncodedWord(encoded_str.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EncodedWord;
+    use crate::LexerError;
+
+    #[test]
+    fn from_str_round_trip() {
+        for encoded_str in [
+            "=?UTF-8?Q?str?=",
+            "=?ISO-8859-1?B?SWYgeW91IGNhbiByZWFkIHRoaXMgeW8=?=",
+            "=?utf-8?q?a_b=3D?=",
+            "=?UTF-8*en?Q?str?=",
+            "=?ISO-8859-1*en-US?Q?str?=",
+        ] {
+            let encoded_word: EncodedWord = encoded_str.parse().unwrap();
+            assert_eq!(encoded_word.to_string(), encoded_str);
+        }
+
+        let encoded_word = EncodedWord::new(b"UTF-8".to_vec(), b"B".to_vec(), b"c3Ry".to_vec());
+        assert_eq!(encoded_word.to_string().parse(), Ok(encoded_word));
+    }
+
+    #[test]
+    fn language() {
+        let encoded_word: EncodedWord = "=?ISO-8859-1*en-US?Q?str?=".parse().unwrap();
+        assert_eq!(encoded_word.charset, b"ISO-8859-1");
+        assert_eq!(encoded_word.language.as_deref(), Some("en-US"));
+        assert_eq!(encoded_word.len(), "=?ISO-8859-1*en-US?Q?str?=".len());
+
+        let encoded_word = EncodedWord::new(b"UTF-8*".to_vec(), b"Q".to_vec(), b"str".to_vec());
+        assert_eq!(encoded_word.charset, b"UTF-8");
+        assert_eq!(encoded_word.language.as_deref(), Some(""));
+        assert_eq!(encoded_word.to_string(), "=?UTF-8*?Q?str?=");
+    }
+
+    #[test]
+    fn display_non_utf8() {
+        let encoded_word = EncodedWord::new(b"UTF-8".to_vec(), b"Q".to_vec(), b"a\xe9b".to_vec());
+        assert_eq!(encoded_word.to_string(), "=?UTF-8?Q?a\u{FFFD}b?=");
+
+        let encoded_word = EncodedWord::new(b"UTF-8*\xff".to_vec(), b"Q".to_vec(), b"a".to_vec());
+        assert_eq!(encoded_word.to_string(), "=?UTF-8*\u{FFFD}?Q?a?=");
+    }
+
+    #[test]
+    fn from_str_rejects_everything_else() {
+        for encoded_str in [
+            "",
+            "str",
+            " =?UTF-8?Q?str?=",
+            "=?UTF-8?Q?str?= ",
+            "=?UTF-8?Q?str?=x",
+            "=?UTF-8?Q?a?= =?UTF-8?Q?b?=",
+            "=?UTF-8?Q?str",
+            "=?UTF-8?Q?s tr?=",
+        ] {
+            assert_eq!(
+                encoded_str.parse::<EncodedWord>(),
+                Err(LexerError::InvalidEncodedWord(encoded_str.to_string())),
+                "{:?}",
+                encoded_str
+            );
+        }
+    }
+}
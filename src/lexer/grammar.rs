@@ -0,0 +1,214 @@
+//! The lexer grammar written with chumsky.
+
+use alloc::vec::Vec;
+use chumsky::{prelude::Simple, text::whitespace, Parser};
+
+#[cfg(test)]
+use super::Tokens;
+use super::{
+    encoded_word, get_especials, is_field_whitespace, trim_field, EncodedWord, Error, Result,
+    SpannedTokens, Token, SPACE,
+};
+use crate::Decoder;
+
+pub(super) fn lex(encoded_bytes: &[u8], decoder: &Decoder) -> Result<SpannedTokens> {
+    get_spanned_parser(decoder)
+        .parse(encoded_bytes)
+        .map_err(Error::ParseBytesError)
+}
+
+#[cfg(test)]
+pub(super) fn get_parser(decoder: &Decoder) -> impl Parser<u8, Tokens, Error = Simple<u8>> {
+    get_spanned_parser(decoder).map(|spanned_tokens| {
+        spanned_tokens
+            .into_iter()
+            .map(|(token, _span)| token)
+            .collect()
+    })
+}
+
+fn get_spanned_parser(decoder: &Decoder) -> impl Parser<u8, SpannedTokens, Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    let with_span = |token, span| (token, span);
+
+    let encoded_words_in_a_row = {
+        let following_encoded_word =
+            whitespace().ignore_then(encoded_word_parser(decoder).rewind());
+        encoded_word_parser(decoder)
+            .map_with_span(with_span)
+            .then_ignore(following_encoded_word)
+    };
+
+    let single_encoded_word = encoded_word_parser(decoder).map_with_span(with_span);
+    let single_clear_text = clear_text_parser(decoder).map_with_span(with_span);
+
+    encoded_words_in_a_row
+        .or(single_encoded_word)
+        .or(single_clear_text)
+        .repeated()
+}
+
+fn clear_text_parser(decoder: &Decoder) -> impl Parser<u8, Token, Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    const DEFAULT_EMPTY_INPUT_ERROR_MESSAGE: &str = "got empty input";
+
+    take_until(encoded_word_parser(decoder).rewind().ignored().or(end())).try_map(
+        |(chars, ()), span| {
+            if chars.is_empty() {
+                Err(Simple::custom(span, DEFAULT_EMPTY_INPUT_ERROR_MESSAGE))
+            } else {
+                Ok(Token::ClearText(chars))
+            }
+        },
+    )
+}
+
+fn encoded_word_parser(decoder: &Decoder) -> impl Parser<u8, Token, Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    let is_especial = |c: u8| get_especials().contains(&c);
+
+    let delimiter = decoder.delimiter;
+
+    let trim_field_whitespace = decoder.trim_field_whitespace;
+
+    let token = filter(move |&c: &u8| {
+        (trim_field_whitespace && is_field_whitespace(c))
+            || (c != SPACE && c != delimiter && !c.is_ascii_control() && !is_especial(c))
+    });
+    let min_length = if decoder.lenient_encoded_text { 0 } else { 1 };
+    let charset = token
+        .repeated()
+        .at_least(min_length)
+        .collect::<Vec<u8>>()
+        .map(trim_field);
+    let encoding = token
+        .repeated()
+        .at_least(min_length)
+        .collect::<Vec<u8>>()
+        .map(trim_field);
+    let encoded_text = next_prefix_guard(decoder)
+        .ignore_then(
+            filter(move |&c: &u8| c != delimiter && c != SPACE).or(lone_delimiter_parser(decoder)),
+        )
+        .repeated()
+        .collect::<Vec<u8>>();
+
+    let suffix = just([delimiter, b'=']);
+
+    just(encoded_word::PREFIX)
+        .ignore_then(charset)
+        .then_ignore(just(delimiter))
+        .then(encoding)
+        .then(
+            just(delimiter)
+                .ignore_then(encoded_text)
+                .then_ignore(suffix)
+                .or(missing_encoded_text_parser(decoder).then_ignore(suffix))
+                .or(just(delimiter).ignore_then(unterminated_encoded_text_parser(decoder))),
+        )
+        .map(EncodedWord::from_parser)
+        .map(Token::EncodedWord)
+}
+
+/// Parses nothing as empty encoded text, so the delimiter in front of the
+/// encoded text may be missing (like in `=???=`), if the decoder allows it.
+fn missing_encoded_text_parser(decoder: &Decoder) -> impl Parser<u8, Vec<u8>, Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    const DEFAULT_STRICT_ERROR_MESSAGE: &str = "encoded text is missing";
+
+    let lenient_encoded_text = decoder.lenient_encoded_text;
+
+    empty().try_map(move |(), span| {
+        if lenient_encoded_text {
+            Ok(Vec::new())
+        } else {
+            Err(Simple::custom(span, DEFAULT_STRICT_ERROR_MESSAGE))
+        }
+    })
+}
+
+/// Parses the encoded text of an encoded word without the closing `?=`, which
+/// runs up to the end of the input or to the next encoded word, if the decoder
+/// allows it.
+fn unterminated_encoded_text_parser(
+    decoder: &Decoder,
+) -> impl Parser<u8, Vec<u8>, Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    const DEFAULT_STRICT_ERROR_MESSAGE: &str = "encoded word isn't terminated";
+
+    let allow_missing_suffix = decoder.allow_missing_suffix;
+    let delimiter = decoder.delimiter;
+
+    // fails fast, so the rest of the input isn't scanned for nothing
+    let allowed = empty().try_map(move |(), span| {
+        if allow_missing_suffix {
+            Ok(())
+        } else {
+            Err(Simple::custom(span, DEFAULT_STRICT_ERROR_MESSAGE))
+        }
+    });
+
+    allowed
+        .ignore_then(take_until(
+            just(encoded_word::PREFIX).rewind().ignored().or(end()),
+        ))
+        .try_map(move |(encoded_text, ()), span| {
+            if encoded_text.is_empty()
+                || encoded_text.contains(&delimiter)
+                || encoded_text.contains(&SPACE)
+            {
+                Err(Simple::custom(span, DEFAULT_STRICT_ERROR_MESSAGE))
+            } else {
+                Ok(encoded_text)
+            }
+        })
+}
+
+/// Fails in front of the prefix of another encoded word inside the encoded
+/// text, if the decoder is lenient. Otherwise every following prefix would be
+/// parsed up to the end of the input again, which takes quadratic time.
+fn next_prefix_guard(decoder: &Decoder) -> impl Parser<u8, (), Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    const GUARD_DISABLED_MESSAGE: &str = "the guard is disabled for strict encoded text";
+
+    let lenient_encoded_text = decoder.lenient_encoded_text;
+
+    // succeeds right away for strict encoded text, which can't contain a prefix
+    let guard_disabled = empty().try_map(move |(), span| {
+        if lenient_encoded_text {
+            Err(Simple::custom(span, GUARD_DISABLED_MESSAGE))
+        } else {
+            Ok(())
+        }
+    });
+    let next_prefix = just(encoded_word::PREFIX).then(none_of([b'=']));
+
+    guard_disabled.or(next_prefix.not().rewind().ignored())
+}
+
+/// Parses a delimiter (usually `?`) inside the encoded text which isn't followed
+/// by a `=`, if the decoder allows it. Otherwise it always fails, so the
+/// delimiter ends the encoded text.
+fn lone_delimiter_parser(decoder: &Decoder) -> impl Parser<u8, u8, Error = Simple<u8>> {
+    use chumsky::prelude::*;
+
+    const DEFAULT_STRICT_ERROR_MESSAGE: &str = "question marks aren't allowed in encoded text";
+
+    let lenient_encoded_text = decoder.lenient_encoded_text;
+
+    just(decoder.delimiter)
+        .then_ignore(none_of([b'=']).rewind())
+        .try_map(move |delimiter, span| {
+            if lenient_encoded_text {
+                Ok(delimiter)
+            } else {
+                Err(Simple::custom(span, DEFAULT_STRICT_ERROR_MESSAGE))
+            }
+        })
+}
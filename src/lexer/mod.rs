@@ -1,17 +1,41 @@
+mod context;
 pub mod encoded_word;
-
-use chumsky::{prelude::Simple, text::whitespace, Parser};
-use std::{collections::HashSet, fmt::Display, result};
+#[cfg(all(feature = "chumsky", not(feature = "minimal")))]
+mod grammar;
+#[cfg(any(feature = "minimal", not(feature = "chumsky")))]
+mod scanner;
+
+use alloc::{
+    borrow::Cow,
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "chumsky")]
+use chumsky::prelude::Simple;
+use core::{fmt::Display, ops::Range, result};
 use thiserror::Error;
 
-use crate::{decoder::RecoverStrategy, Decoder};
+use crate::{decoder::RecoverStrategy, parser, Decoder};
 
 use self::encoded_word::EncodedWord;
+#[cfg(all(feature = "chumsky", not(feature = "minimal")))]
+use self::grammar::lex;
+#[cfg(any(feature = "minimal", not(feature = "chumsky")))]
+use self::scanner::lex;
 
 pub const QUESTION_MARK: u8 = b'?';
 const SPACE: u8 = b' ';
-
-/// A helper struct which implements [std::fmt::Display] for `Vec<String>` and
+/// The UTF-8 encoded zero width chars which are removed with
+/// [Decoder::strip_zero_width]: the zero width space and the zero width
+/// no-break space (BOM).
+const ZERO_WIDTH_CHARS: [&[u8]; 2] = ["\u{200B}".as_bytes(), "\u{FEFF}".as_bytes()];
+/// The line breaks which are removed with [Decoder::unfold], if they are
+/// followed by whitespace.
+const LINE_BREAKS: [&[u8]; 2] = [b"\r\n", b"\n"];
+
+/// A helper struct which implements [core::fmt::Display] for `Vec<String>` and
 /// which contains the encoded words which are too long as a `String`.
 ///
 /// # Example
@@ -25,7 +49,7 @@ const SPACE: u8 = b' ';
 ///     "among us",
 ///     "=?utf-8?B?aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa==?=",
 /// ];
-
+///
 /// let result = decode(message).unwrap_err();
 /// if let rfc2047_decoder::Error::Lexer(LexerError::ParseEncodedWordTooLongError(invalid_encoded_words)) = result {
 ///     assert_eq!(invalid_encoded_words.0[0], "=?utf-8?B?bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb==?=");
@@ -44,7 +68,7 @@ impl TooLongEncodedWords {
 }
 
 impl Display for TooLongEncodedWords {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut message = String::new();
 
         if !self.0.is_empty() {
@@ -62,19 +86,71 @@ impl Display for TooLongEncodedWords {
 /// All errors which the lexer can throw.
 #[derive(Error, Debug, Clone, PartialEq)]
 pub enum Error {
+    #[cfg(feature = "chumsky")]
     #[error("cannot parse bytes into tokens")]
     ParseBytesError(Vec<Simple<u8>>),
     #[error("Cannot parse the following encoded words, because they are too long: {0}")]
     ParseEncodedWordTooLongError(TooLongEncodedWords),
+    #[error("the encoded word {0} isn't allowed in this part of the header")]
+    MisplacedEncodedWord(String),
+    #[error("{0:?} isn't exactly one encoded word")]
+    InvalidEncodedWord(String),
+    #[error("{0:?} doesn't start with a header field name and a colon")]
+    MissingFieldName(String),
+}
+
+impl Error {
+    /// Returns the errors of [Error::ParseBytesError] as [LexError]s, which
+    /// don't depend on the types of the parser library. Returns an empty list
+    /// for all other errors.
+    pub fn lex_errors(&self) -> Vec<LexError> {
+        #[cfg(feature = "chumsky")]
+        if let Self::ParseBytesError(errors) = self {
+            return errors.iter().map(LexError::from).collect();
+        }
+
+        Vec::new()
+    }
+}
+
+/// A single error of the lexer, see [Error::lex_errors].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct LexError {
+    /// The byte range of the input at which the error occurred.
+    pub span: Range<usize>,
+
+    /// The byte which has been found, `None` at the end of the input.
+    pub found: Option<u8>,
+
+    /// The bytes which would have been valid instead, in ascending order.
+    /// The end of the input isn't listed.
+    pub expected: Vec<u8>,
+}
+
+#[cfg(feature = "chumsky")]
+impl From<&Simple<u8>> for LexError {
+    fn from(error: &Simple<u8>) -> Self {
+        let expected: BTreeSet<u8> = error.expected().filter_map(|byte| *byte).collect();
+
+        Self {
+            span: error.span(),
+            found: error.found().copied(),
+            expected: expected.into_iter().collect(),
+        }
+    }
 }
 
 type Result<T> = result::Result<T, Error>;
 
 pub type Tokens = Vec<Token>;
 
+/// A part of the input as it's split by the lexer. See [crate::tokenize].
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub enum Token {
+    /// The bytes between the encoded words, as they are in the input.
     ClearText(Vec<u8>),
+    /// An encoded word, split into its parts but not decoded yet.
     EncodedWord(EncodedWord),
 }
 
@@ -86,90 +162,475 @@ impl Token {
             Self::EncodedWord(encoded_word) => encoded_word.len(),
         }
     }
+
+    /// Returns `true` if the token holds no bytes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-pub fn run(encoded_bytes: &[u8], decoder: Decoder) -> Result<Tokens> {
-    let tokens = get_parser(&decoder)
-        .parse(encoded_bytes)
-        .map_err(Error::ParseBytesError)?;
+/// Tokens with the byte range they cover in the input. The whitespace between
+/// two encoded words in a row isn't covered by any token.
+pub type SpannedTokens = Vec<(Token, Range<usize>)>;
 
-    validate_tokens(tokens, &decoder)
+pub fn run(encoded_bytes: &[u8], decoder: &Decoder) -> Result<Tokens> {
+    let spanned_tokens = run_spanned(encoded_bytes, decoder)?;
+    Ok(spanned_tokens
+        .into_iter()
+        .map(|(token, _span)| token)
+        .collect())
 }
 
-fn get_parser(decoder: &Decoder) -> impl Parser<u8, Tokens, Error = Simple<u8>> {
-    use chumsky::prelude::*;
+/// Like [run], but keeps the byte range of each token in the input. Clear text
+/// which replaces an encoded word (see [Decoder::replacement_for_undecodable])
+/// covers the range of the encoded word.
+pub fn run_spanned(input: &[u8], decoder: &Decoder) -> Result<SpannedTokens> {
+    let preprocessed_bytes = preprocess(input, decoder);
+    let encoded_bytes = &*preprocessed_bytes;
+    let spanned_tokens = lex(encoded_bytes, decoder)?;
+
+    if let Some(index) =
+        context::first_misplaced_encoded_word(&spanned_tokens, encoded_bytes, decoder)
+    {
+        let (_token, span) = &spanned_tokens[index];
+        let encoded_word = String::from_utf8_lossy(&encoded_bytes[span.clone()]).into_owned();
+        return Err(Error::MisplacedEncodedWord(encoded_word));
+    }
 
-    let encoded_words_in_a_row = {
-        let following_encoded_word =
-            whitespace().ignore_then(encoded_word_parser(decoder).rewind());
-        encoded_word_parser(decoder).then_ignore(following_encoded_word)
+    let tokens = resolve_encoded_words(spanned_tokens, encoded_bytes, decoder);
+    let tokens = context::unescape_quoted_strings(tokens, decoder);
+    let tokens = match preprocessed_bytes {
+        Cow::Borrowed(_) => tokens,
+        Cow::Owned(_) => map_spans_to_input(tokens, input, decoder),
     };
 
-    let single_encoded_word = encoded_word_parser(decoder);
-    let single_clear_text = clear_text_parser(decoder);
+    validate_tokens(tokens, decoder)
+}
 
-    encoded_words_in_a_row
-        .or(single_encoded_word)
-        .or(single_clear_text)
-        .repeated()
+/// Returns the length of the leading phrase of the input: everything up to the
+/// first special char of an address (like `<` or `,`) outside of an encoded
+/// word, without the whitespace in front of it.
+pub fn leading_phrase_len(encoded_bytes: &[u8], decoder: &Decoder) -> Result<usize> {
+    const PHRASE_ENDS: &[u8] = b"<>@,;:";
+
+    let phrase_end = lex(encoded_bytes, decoder)?
+        .into_iter()
+        .filter(|(token, _span)| matches!(token, Token::ClearText(_)))
+        .find_map(|(_token, span)| {
+            encoded_bytes[span.clone()]
+                .iter()
+                .position(|byte| PHRASE_ENDS.contains(byte))
+                .map(|offset| span.start + offset)
+        })
+        .unwrap_or(encoded_bytes.len());
+
+    let phrase_len = encoded_bytes[..phrase_end]
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(0, |index| index + 1);
+
+    Ok(phrase_len)
 }
 
-fn clear_text_parser(decoder: &Decoder) -> impl Parser<u8, Token, Error = Simple<u8>> {
-    use chumsky::prelude::*;
+/// Splits a header line at the first colon outside of a quoted string into the
+/// field name and the value, without the whitespace around them. The field
+/// name has to consist of printable ASCII chars.
+pub fn split_header_field(line: &[u8]) -> Result<(&[u8], &[u8])> {
+    let mut is_quoted = false;
+    let colon = line.iter().position(|&byte| {
+        if byte == b'"' {
+            is_quoted = !is_quoted;
+        }
+        byte == b':' && !is_quoted
+    });
+
+    let (field_name, value) = match colon {
+        Some(colon) => (line[..colon].trim_ascii_end(), &line[colon + 1..]),
+        None => (&line[..0], line),
+    };
 
-    const DEFAULT_EMPTY_INPUT_ERROR_MESSAGE: &str = "got empty input";
+    let is_field_name_char = |byte: &u8| byte.is_ascii_graphic() && *byte != b':';
+    if field_name.is_empty() || !field_name.iter().all(is_field_name_char) {
+        let line = String::from_utf8_lossy(line).into_owned();
+        return Err(Error::MissingFieldName(line));
+    }
+
+    Ok((field_name, value.trim_ascii_start()))
+}
 
-    take_until(encoded_word_parser(decoder).rewind().ignored().or(end())).try_map(
-        |(chars, ()), span| {
-            if chars.is_empty() {
-                Err(Simple::custom(span, DEFAULT_EMPTY_INPUT_ERROR_MESSAGE))
-            } else {
-                Ok(Token::ClearText(chars))
+/// Splits an address list (like the value of a `To` header) at the commas
+/// outside of angle brackets and quoted strings into the display names and
+/// the addresses, without the whitespace around them. The display name is
+/// empty if the address isn't in angle brackets, and empty entries are
+/// dropped.
+pub fn split_address_list(line: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut entries = Vec::new();
+    let mut entry_start = 0;
+    let mut is_quoted = false;
+    let mut is_escaped = false;
+    let mut is_in_angle_brackets = false;
+
+    for (index, &byte) in line.iter().enumerate() {
+        match byte {
+            _ if is_escaped => is_escaped = false,
+            b'\\' if is_quoted => is_escaped = true,
+            b'"' if !is_in_angle_brackets => is_quoted = !is_quoted,
+            b'<' if !is_quoted => is_in_angle_brackets = true,
+            b'>' if !is_quoted => is_in_angle_brackets = false,
+            b',' if !is_quoted && !is_in_angle_brackets => {
+                entries.push(&line[entry_start..index]);
+                entry_start = index + 1;
             }
-        },
-    )
+            _ => {}
+        }
+    }
+    entries.push(&line[entry_start..]);
+
+    entries
+        .into_iter()
+        .map(<[u8]>::trim_ascii)
+        .filter(|entry| !entry.is_empty())
+        .map(split_address)
+        .collect()
 }
 
-fn encoded_word_parser(decoder: &Decoder) -> impl Parser<u8, Token, Error = Simple<u8>> {
-    use chumsky::prelude::*;
+/// Splits an entry of an address list into the display name and the address
+/// in angle brackets, if there are angle brackets outside of a quoted string.
+fn split_address(entry: &[u8]) -> (&[u8], &[u8]) {
+    let mut is_quoted = false;
+    let mut is_escaped = false;
+    let opening_bracket = entry.iter().position(|&byte| {
+        match byte {
+            _ if is_escaped => is_escaped = false,
+            b'\\' if is_quoted => is_escaped = true,
+            b'"' => is_quoted = !is_quoted,
+            _ => {}
+        }
+        byte == b'<' && !is_quoted
+    });
+
+    match opening_bracket {
+        Some(opening_bracket) => {
+            let address = &entry[opening_bracket + 1..];
+            let address = match address.iter().position(|&byte| byte == b'>') {
+                Some(closing_bracket) => &address[..closing_bracket],
+                None => address,
+            };
+
+            (entry[..opening_bracket].trim_ascii(), address.trim_ascii())
+        }
+        None => (&entry[..0], entry),
+    }
+}
 
-    let skip_encoded_word_length = decoder.too_long_encoded_word;
+/// Removes the quotes around the display name and the backslashes of its
+/// quoted pairs, if the whole display name is a quoted string.
+pub fn unquote_display_name(display_name: &[u8]) -> Cow<'_, [u8]> {
+    let quoted_text = match display_name {
+        [b'"', quoted_text @ .., b'"'] if !quoted_text.ends_with(b"\\") => quoted_text,
+        _ => return Cow::Borrowed(display_name),
+    };
 
-    let convert_to_token = move |encoded_word: EncodedWord| {
-        if encoded_word.len() > encoded_word::MAX_LENGTH
-            && skip_encoded_word_length == RecoverStrategy::Skip
-        {
-            Token::ClearText(encoded_word.get_bytes(true))
-        } else {
-            Token::EncodedWord(encoded_word)
+    let mut unquoted_text = Vec::with_capacity(quoted_text.len());
+    let mut bytes = quoted_text.iter();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\\' => unquoted_text.extend(bytes.next()),
+            b'"' => return Cow::Borrowed(display_name),
+            _ => unquoted_text.push(byte),
         }
+    }
+
+    Cow::Owned(unquoted_text)
+}
+
+/// Like [run], but returns the tokens up to the first one which can't be
+/// lexed instead of an error. The flag is `true` if the tokens were cut off.
+pub fn run_until_error(encoded_bytes: &[u8], decoder: &Decoder) -> (Tokens, bool) {
+    let encoded_bytes = &*preprocess(encoded_bytes, decoder);
+    let mut spanned_tokens = match lex(encoded_bytes, decoder) {
+        Ok(spanned_tokens) => spanned_tokens,
+        Err(_) => return (Tokens::new(), true),
     };
 
-    let is_especial = |c: u8| get_especials().contains(&c);
+    let misplaced_encoded_word =
+        context::first_misplaced_encoded_word(&spanned_tokens, encoded_bytes, decoder);
+    if let Some(index) = misplaced_encoded_word {
+        spanned_tokens.truncate(index);
+    }
 
-    let token = filter(move |&c: &u8| c != SPACE && !c.is_ascii_control() && !is_especial(c));
-    let charset = token.repeated().at_least(1).collect::<Vec<u8>>();
-    let encoding = token.repeated().at_least(1).collect::<Vec<u8>>();
-    let encoded_text = filter(|&c: &u8| c != QUESTION_MARK && c != SPACE)
-        .repeated()
-        .collect::<Vec<u8>>();
+    let tokens = resolve_encoded_words(spanned_tokens, encoded_bytes, decoder);
+    let mut tokens: Tokens = context::unescape_quoted_strings(tokens, decoder)
+        .into_iter()
+        .map(|(token, _span)| token)
+        .collect();
+    let first_too_long_encoded_word = tokens
+        .iter()
+        .position(|token| is_too_long_encoded_word(token, decoder));
+
+    match first_too_long_encoded_word {
+        Some(index) => {
+            tokens.truncate(index);
+            (tokens, true)
+        }
+        None => (tokens, misplaced_encoded_word.is_some()),
+    }
+}
+
+/// Applies the preprocessing of the decoder to the input, which is done before
+/// it's lexed: removing the zero width chars if [Decoder::strip_zero_width] is
+/// set and the line breaks of folded lines if [Decoder::unfold] is set.
+pub fn preprocess<'a>(encoded_bytes: &'a [u8], decoder: &Decoder) -> Cow<'a, [u8]> {
+    if !decoder.strip_zero_width && !decoder.unfold {
+        return Cow::Borrowed(encoded_bytes);
+    }
+
+    let mut stripped_bytes = Vec::with_capacity(encoded_bytes.len());
+    let mut rest = encoded_bytes;
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        match removed_len(rest, decoder) {
+            Some(len) => rest = &rest[len..],
+            None => {
+                stripped_bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    Cow::Owned(stripped_bytes)
+}
 
-    just(encoded_word::PREFIX)
-        .ignore_then(charset)
-        .then_ignore(just(QUESTION_MARK))
-        .then(encoding)
-        .then_ignore(just(QUESTION_MARK))
-        .then(encoded_text)
-        .then_ignore(just(encoded_word::SUFFIX))
-        .map(EncodedWord::from_parser)
-        .map(convert_to_token)
+/// Returns the length of the bytes at the start of the input which are
+/// removed by the preprocessing, if there are any.
+fn removed_len(rest: &[u8], decoder: &Decoder) -> Option<usize> {
+    let zero_width_char = ZERO_WIDTH_CHARS
+        .iter()
+        .find(|zero_width_char| rest.starts_with(zero_width_char))
+        .filter(|_| decoder.strip_zero_width);
+
+    // a line break is only removed if it's followed by whitespace, the
+    // whitespace itself is kept
+    let line_break = LINE_BREAKS
+        .iter()
+        .find(|line_break| {
+            rest.starts_with(line_break)
+                && rest
+                    .get(line_break.len())
+                    .is_some_and(|&byte| is_field_whitespace(byte))
+        })
+        .filter(|_| decoder.unfold);
+
+    zero_width_char.or(line_break).map(|removed| removed.len())
 }
 
-fn get_especials() -> HashSet<u8> {
+/// Maps the spans of the tokens of the preprocessed input back to the
+/// original input, so they also cover the bytes which were removed.
+fn map_spans_to_input(
+    spanned_tokens: SpannedTokens,
+    input: &[u8],
+    decoder: &Decoder,
+) -> SpannedTokens {
+    // the position in the input of each byte of the preprocessed input, and of
+    // its end
+    let mut positions = Vec::with_capacity(input.len() + 1);
+    let mut position = 0;
+
+    while position < input.len() {
+        match removed_len(&input[position..], decoder) {
+            Some(len) => position += len,
+            None => {
+                positions.push(position);
+                position += 1;
+            }
+        }
+    }
+    positions.push(input.len());
+
+    spanned_tokens
+        .into_iter()
+        .map(|(token, span)| {
+            let end = match span.end.checked_sub(1) {
+                Some(last) if !span.is_empty() => positions[last] + 1,
+                _ => positions[span.start],
+            };
+            (token, positions[span.start]..end)
+        })
+        .collect()
+}
+
+/// Converts the encoded words which won't be decoded into clear text: the ones
+/// which aren't at a word boundary (if [Decoder::strict_word_boundaries] or
+/// [Decoder::require_whitespace_after_word] is set), the ones with a charset
+/// which shouldn't be decoded and the invalid ones which should be skipped.
+/// The whitespace between two encoded words is only dropped if both of them
+/// are still encoded words and [Decoder::collapse_adjacent_whitespace] is set.
+fn resolve_encoded_words(
+    spanned_tokens: SpannedTokens,
+    encoded_bytes: &[u8],
+    decoder: &Decoder,
+) -> SpannedTokens {
+    let mut tokens: SpannedTokens = Vec::with_capacity(spanned_tokens.len());
+    let mut previous_end = 0;
+    let mut follows_encoded_word = false;
+
+    for (token, span) in spanned_tokens {
+        let gap_span = previous_end..span.start;
+        let gap = &encoded_bytes[gap_span.clone()];
+        let original_bytes = &encoded_bytes[span.clone()];
+        previous_end = span.end;
+
+        follows_encoded_word = match token {
+            Token::EncodedWord(encoded_word)
+                if (decoder.strict_word_boundaries
+                    && !is_at_word_boundary(encoded_bytes, span.start))
+                    || (decoder.require_whitespace_after_word
+                        && !is_at_word_end(encoded_bytes, span.end))
+                    || !is_decodable_charset(&encoded_word, decoder) =>
+            {
+                push_clear_text(&mut tokens, gap, gap_span);
+                push_clear_text(&mut tokens, original_bytes, span);
+                false
+            }
+            Token::EncodedWord(encoded_word) if is_undecodable(&encoded_word, decoder) => {
+                push_clear_text(&mut tokens, gap, gap_span);
+                let strategy = undecodable_strategy(&encoded_word, decoder);
+                let clear_text = decoder
+                    .replacement_text(strategy)
+                    .map_or(original_bytes, str::as_bytes);
+                push_clear_text(&mut tokens, clear_text, span);
+                false
+            }
+            Token::EncodedWord(encoded_word) => {
+                if !decoder.collapse_adjacent_whitespace || !follows_encoded_word {
+                    push_clear_text(&mut tokens, gap, gap_span);
+                }
+                // the evaluator keeps the encoded word byte-exact if it skips it
+                let encoded_word = encoded_word.with_original(original_bytes, decoder.delimiter);
+                tokens.push((Token::EncodedWord(encoded_word), span));
+                true
+            }
+            Token::ClearText(clear_text) => {
+                push_clear_text(&mut tokens, &clear_text, span);
+                false
+            }
+        };
+    }
+
+    tokens
+}
+
+/// Returns `true` if an encoded word may start at the given position: at the
+/// start of the input, after whitespace or after the opening parenthesis of a
+/// comment.
+fn is_at_word_boundary(encoded_bytes: &[u8], start: usize) -> bool {
+    match start.checked_sub(1).map(|index| encoded_bytes[index]) {
+        None => true,
+        Some(byte) => byte.is_ascii_whitespace() || byte == b'(',
+    }
+}
+
+/// Returns `true` if an encoded word may end at the given position: at the end
+/// of the input, before whitespace or before the closing parenthesis of a
+/// comment.
+fn is_at_word_end(encoded_bytes: &[u8], end: usize) -> bool {
+    match encoded_bytes.get(end) {
+        None => true,
+        Some(&byte) => byte.is_ascii_whitespace() || byte == b')',
+    }
+}
+
+/// Appends the clear text to the tokens, merging it with the previous clear text.
+fn push_clear_text(tokens: &mut SpannedTokens, clear_text: &[u8], span: Range<usize>) {
+    if clear_text.is_empty() {
+        return;
+    }
+
+    match tokens.last_mut() {
+        Some((Token::ClearText(previous_clear_text), previous_span)) => {
+            previous_clear_text.extend(clear_text);
+            previous_span.end = span.end;
+        }
+        _ => tokens.push((Token::ClearText(clear_text.to_vec()), span)),
+    }
+}
+
+/// Returns `true` if the charset of the encoded word may be decoded according
+/// to [Decoder::decode_only_charsets].
+fn is_decodable_charset(encoded_word: &EncodedWord, decoder: &Decoder) -> bool {
+    match &decoder.decodable_charsets {
+        Some(decodable_charsets) => decodable_charsets.iter().any(|decodable_charset| {
+            decodable_charset
+                .as_bytes()
+                .eq_ignore_ascii_case(&encoded_word.charset)
+        }),
+        None => true,
+    }
+}
+
+/// Returns `true` if the encoded word is invalid and shouldn't be decoded
+/// according to the recover strategies of the decoder.
+fn is_undecodable(encoded_word: &EncodedWord, decoder: &Decoder) -> bool {
+    undecodable_strategy(encoded_word, decoder).is_some()
+}
+
+/// Returns the strategy because of which the encoded word isn't decoded, see
+/// [is_undecodable].
+fn undecodable_strategy<'a>(
+    encoded_word: &EncodedWord,
+    decoder: &'a Decoder,
+) -> Option<&'a RecoverStrategy> {
+    if encoded_word.is_empty() {
+        return None;
+    }
+
+    let is_skipped = |strategy: &RecoverStrategy| {
+        matches!(
+            strategy,
+            RecoverStrategy::Skip | RecoverStrategy::Replace(_)
+        )
+    };
+    let is_too_long = encoded_word.len() > decoder.max_encoded_word_length;
+    let has_invalid_encoding = parser::encoding_for_label(&encoded_word.encoding, decoder).is_err();
+    let has_unknown_charset = has_unknown_charset(encoded_word, decoder);
+
+    if is_too_long && is_skipped(&decoder.too_long_encoded_word) {
+        Some(&decoder.too_long_encoded_word)
+    } else if has_invalid_encoding && decoder.invalid_encoding != RecoverStrategy::Abort {
+        Some(&decoder.invalid_encoding)
+    } else if has_unknown_charset && is_skipped(&decoder.unknown_charset) {
+        Some(&decoder.unknown_charset)
+    } else {
+        None
+    }
+}
+
+fn has_unknown_charset(encoded_word: &EncodedWord, decoder: &Decoder) -> bool {
+    let charset_label = decoder
+        .charset_override
+        .as_ref()
+        .map_or(&encoded_word.charset[..], |label| label.as_bytes());
+
+    !parser::is_known_charset(charset_label, decoder)
+}
+
+fn get_especials() -> BTreeSet<u8> {
     "()<>@,;:/[]?.=".bytes().collect()
 }
 
-fn validate_tokens(tokens: Tokens, decoder: &Decoder) -> Result<Tokens> {
+/// Returns `true` if the byte is whitespace which is allowed around the
+/// charset and the encoding with [Decoder::trim_field_whitespace].
+fn is_field_whitespace(byte: u8) -> bool {
+    byte == SPACE || byte == b'\t'
+}
+
+/// Removes the whitespace around the charset or the encoding of an encoded
+/// word.
+fn trim_field(field: Vec<u8>) -> Vec<u8> {
+    field.trim_ascii().to_vec()
+}
+
+fn validate_tokens(tokens: SpannedTokens, decoder: &Decoder) -> Result<SpannedTokens> {
     if let Some(too_long_encoded_words) = get_too_long_encoded_words(&tokens, decoder) {
         return Err(Error::ParseEncodedWordTooLongError(too_long_encoded_words));
     }
@@ -177,13 +638,23 @@ fn validate_tokens(tokens: Tokens, decoder: &Decoder) -> Result<Tokens> {
     Ok(tokens)
 }
 
-fn get_too_long_encoded_words(tokens: &Tokens, decoder: &Decoder) -> Option<TooLongEncodedWords> {
-    let strategy = decoder.too_long_encoded_word;
+/// Returns `true` if the token is an encoded word which is too long and the
+/// decoder should abort because of it.
+fn is_too_long_encoded_word(token: &Token, decoder: &Decoder) -> bool {
+    matches!(token, Token::EncodedWord(_))
+        && token.len() > decoder.max_encoded_word_length
+        && decoder.too_long_encoded_word == RecoverStrategy::Abort
+}
+
+fn get_too_long_encoded_words(
+    tokens: &SpannedTokens,
+    decoder: &Decoder,
+) -> Option<TooLongEncodedWords> {
     let mut too_long_encoded_words: Vec<String> = Vec::new();
 
-    for token in tokens.iter() {
+    for (token, _span) in tokens.iter() {
         if let Token::EncodedWord(encoded_word) = token {
-            if token.len() > encoded_word::MAX_LENGTH && strategy == RecoverStrategy::Abort {
+            if is_too_long_encoded_word(token, decoder) {
                 too_long_encoded_words.push(encoded_word.to_string());
             }
         }
@@ -196,15 +667,41 @@ fn get_too_long_encoded_words(tokens: &Tokens, decoder: &Decoder) -> Option<TooL
     }
 }
 
+/// Returns the encoded words which are longer than
+/// [Decoder::max_encoded_word_length] with their byte ranges in the input,
+/// regardless of [Decoder::too_long_encoded_word].
+pub fn find_too_long_encoded_words(
+    input: &[u8],
+    decoder: &Decoder,
+) -> Result<Vec<(String, Range<usize>)>> {
+    let decoder = decoder
+        .clone()
+        .too_long_encoded_word_strategy(RecoverStrategy::Decode);
+
+    let too_long_encoded_words = run_spanned(input, &decoder)?
+        .into_iter()
+        .filter(|(token, _span)| token.len() > decoder.max_encoded_word_length)
+        .filter_map(|(token, span)| match token {
+            Token::EncodedWord(encoded_word) => Some((encoded_word.to_string(), span)),
+            Token::ClearText(_) => None,
+        })
+        .collect();
+
+    Ok(too_long_encoded_words)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        lexer::{encoded_word::EncodedWord, run, Token},
-        Decoder,
+        lexer::{encoded_word::EncodedWord, find_too_long_encoded_words, run, run_spanned, Token},
+        Decoder, RecoverStrategy,
     };
 
-    use super::{get_parser, Error, TooLongEncodedWords};
-    use chumsky::Parser;
+    #[cfg(any(feature = "minimal", not(feature = "chumsky")))]
+    use super::scanner::get_parser;
+    use super::{Error, TooLongEncodedWords};
+    #[cfg(all(feature = "chumsky", not(feature = "minimal")))]
+    use {super::grammar::get_parser, chumsky::Parser};
 
     #[test]
     fn encoded_word() {
@@ -217,6 +714,8 @@ mod tests {
             parsed,
             vec![Token::EncodedWord(EncodedWord {
                 charset: "ISO-8859-1".as_bytes().to_vec(),
+                language: None,
+                original: None,
                 encoding: "Q".as_bytes().to_vec(),
                 encoded_text: "Yeet".as_bytes().to_vec(),
             })]
@@ -251,6 +750,8 @@ mod tests {
             parsed,
             vec![Token::EncodedWord(EncodedWord {
                 charset: "ISO-8859-1".as_bytes().to_vec(),
+                language: None,
+                original: None,
                 encoding: "Q".as_bytes().to_vec(),
                 encoded_text: "a".as_bytes().to_vec()
             })]
@@ -270,6 +771,8 @@ mod tests {
             vec![
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "a".as_bytes().to_vec(),
                 }),
@@ -291,11 +794,15 @@ mod tests {
             vec![
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "a".as_bytes().to_vec(),
                 }),
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "b".as_bytes().to_vec()
                 })
@@ -317,16 +824,22 @@ mod tests {
             vec![
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "a".as_bytes().to_vec(),
                 }),
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "b".as_bytes().to_vec()
                 }),
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "c".as_bytes().to_vec()
                 })
@@ -347,11 +860,15 @@ mod tests {
             vec![
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "a".as_bytes().to_vec(),
                 }),
                 Token::EncodedWord(EncodedWord {
                     charset: "ISO-8859-1".as_bytes().to_vec(),
+                    language: None,
+                    original: None,
                     encoding: "Q".as_bytes().to_vec(),
                     encoded_text: "b".as_bytes().to_vec()
                 })
@@ -368,23 +885,359 @@ mod tests {
         let message =
             "=?ISO-8859-1?Q?aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa?="
                 .as_bytes();
-        let parsed = run(message, Decoder::new());
+        let parsed = run(message, &Decoder::new());
 
         assert_eq!(
             parsed,
             Err(Error::ParseEncodedWordTooLongError(
-                TooLongEncodedWords::new(vec![EncodedWord {
-                    charset: "ISO-8859-1".as_bytes().to_vec(),
-                    encoding: "Q".as_bytes().to_vec(),
-                    encoded_text: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+                TooLongEncodedWords::new(vec![EncodedWord::new(
+                    "ISO-8859-1".as_bytes().to_vec(),
+                    "Q".as_bytes().to_vec(),
+                    "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
                         .as_bytes()
                         .to_vec()
-                }
+                )
                 .to_string()])
             ))
         );
     }
 
+    #[test]
+    fn too_long_encoded_word_spans() {
+        let too_long_word =
+            "=?ISO-8859-1?Q?aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa?=";
+        let message = format!("a \u{200B}{} b", too_long_word);
+        let start = "a \u{200B}".len();
+
+        // the spans point into the input, even if zero width chars are removed
+        let decoder = Decoder::new().strip_zero_width(true);
+        assert_eq!(
+            find_too_long_encoded_words(message.as_bytes(), &decoder).unwrap(),
+            vec![(
+                too_long_word.to_string(),
+                start..start + too_long_word.len()
+            )]
+        );
+
+        // also with a strategy which doesn't abort
+        let decoder = decoder.too_long_encoded_word_strategy(RecoverStrategy::Skip);
+        assert_eq!(
+            find_too_long_encoded_words(message.as_bytes(), &decoder)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            find_too_long_encoded_words(b"=?UTF-8?Q?a?= b", &decoder).unwrap(),
+            vec![]
+        );
+    }
+
+    #[cfg(feature = "chumsky")]
+    #[test]
+    fn lex_errors() {
+        use chumsky::{prelude::Simple, Error as _};
+
+        use super::LexError;
+
+        let err = Error::ParseBytesError(vec![
+            Simple::expected_input_found(
+                3..4,
+                [Some(b'?'), None, Some(b'='), Some(b'?')],
+                Some(b'x'),
+            ),
+            Simple::expected_input_found(7..7, [Some(b'=')], None),
+        ]);
+
+        assert_eq!(
+            err.lex_errors(),
+            vec![
+                LexError {
+                    span: 3..4,
+                    found: Some(b'x'),
+                    expected: vec![b'=', b'?'],
+                },
+                LexError {
+                    span: 7..7,
+                    found: None,
+                    expected: vec![b'='],
+                },
+            ]
+        );
+        assert_eq!(
+            Error::InvalidEncodedWord("a".to_string()).lex_errors(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn empty_encoded_word() {
+        let message = "=???=".as_bytes();
+
+        let strict = get_parser(&Decoder::new()).parse(message).unwrap();
+        assert_eq!(strict, vec![Token::ClearText(message.to_vec())]);
+
+        let lenient = get_parser(&Decoder::new().lenient_encoded_text(true))
+            .parse(message)
+            .unwrap();
+        assert_eq!(
+            lenient,
+            vec![Token::EncodedWord(EncodedWord {
+                charset: Vec::new(),
+                language: None,
+                original: None,
+                encoding: Vec::new(),
+                encoded_text: Vec::new(),
+            })]
+        );
+    }
+
+    #[test]
+    fn whitespace_around_fields() {
+        let message = "=? UTF-8 ?\tQ ?x?=".as_bytes();
+
+        let strict = get_parser(&Decoder::new()).parse(message).unwrap();
+        assert_eq!(strict, vec![Token::ClearText(message.to_vec())]);
+
+        let lenient = get_parser(&Decoder::new().trim_field_whitespace(true))
+            .parse(message)
+            .unwrap();
+        assert_eq!(
+            lenient,
+            vec![Token::EncodedWord(EncodedWord::new(
+                b"UTF-8".to_vec(),
+                b"Q".to_vec(),
+                b"x".to_vec()
+            ))]
+        );
+
+        // the encoded text may still not contain whitespace
+        let message = "=? UTF-8 ?Q?x y?=".as_bytes();
+        let lenient = get_parser(&Decoder::new().trim_field_whitespace(true))
+            .parse(message)
+            .unwrap();
+        assert_eq!(lenient, vec![Token::ClearText(message.to_vec())]);
+    }
+
+    #[test]
+    fn question_mark_in_encoded_text() {
+        let message = "=?UTF-8?Q?a?b?=".as_bytes();
+
+        let strict = get_parser(&Decoder::new()).parse(message).unwrap();
+        assert_eq!(strict, vec![Token::ClearText(message.to_vec())]);
+
+        let lenient = get_parser(&Decoder::new().lenient_encoded_text(true))
+            .parse(message)
+            .unwrap();
+        assert_eq!(
+            lenient,
+            vec![Token::EncodedWord(EncodedWord {
+                charset: "UTF-8".as_bytes().to_vec(),
+                language: None,
+                original: None,
+                encoding: "Q".as_bytes().to_vec(),
+                encoded_text: "a?b".as_bytes().to_vec(),
+            })]
+        );
+    }
+
+    #[test]
+    fn strict_word_boundaries() {
+        let decoder = Decoder::new().strict_word_boundaries(true);
+
+        assert_eq!(
+            run(b"foo =?UTF-8?Q?x?=", &decoder).unwrap(),
+            vec![
+                Token::ClearText(b"foo ".to_vec()),
+                Token::EncodedWord(EncodedWord::new(
+                    b"UTF-8".to_vec(),
+                    b"Q".to_vec(),
+                    b"x".to_vec()
+                )),
+            ]
+        );
+        assert_eq!(
+            run(b"foo=?UTF-8?Q?x?=", &decoder).unwrap(),
+            vec![Token::ClearText(b"foo=?UTF-8?Q?x?=".to_vec())]
+        );
+        assert_eq!(
+            run(b"foo=?UTF-8?Q?x?= =?UTF-8?Q?y?=", &decoder).unwrap(),
+            vec![
+                Token::ClearText(b"foo=?UTF-8?Q?x?= ".to_vec()),
+                Token::EncodedWord(EncodedWord::new(
+                    b"UTF-8".to_vec(),
+                    b"Q".to_vec(),
+                    b"y".to_vec()
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn require_whitespace_after_word() {
+        let decoder = Decoder::new().require_whitespace_after_word(true);
+        let x = || EncodedWord::new(b"UTF-8".to_vec(), b"Q".to_vec(), b"x".to_vec());
+
+        assert_eq!(
+            run(b"=?UTF-8?Q?x?=:", &decoder).unwrap(),
+            vec![Token::ClearText(b"=?UTF-8?Q?x?=:".to_vec())]
+        );
+        assert_eq!(
+            run(b"=?UTF-8?Q?x?= :", &decoder).unwrap(),
+            vec![Token::EncodedWord(x()), Token::ClearText(b" :".to_vec())]
+        );
+        assert_eq!(
+            run(b"(=?UTF-8?Q?x?=)", &decoder).unwrap(),
+            vec![
+                Token::ClearText(b"(".to_vec()),
+                Token::EncodedWord(x()),
+                Token::ClearText(b")".to_vec()),
+            ]
+        );
+        assert_eq!(
+            run(b"=?UTF-8?Q?x?=:", &Decoder::new()).unwrap(),
+            vec![Token::EncodedWord(x()), Token::ClearText(b":".to_vec())]
+        );
+    }
+
+    #[test]
+    fn incomplete_encoded_word_at_end() {
+        for message in [
+            "hello =?",
+            "hello =?UTF-8",
+            "hello =?UTF-8?",
+            "hello =?UTF-8?Q?",
+            "hello =?UTF-8?Q?x",
+            "hello =?UTF-8?Q?x?",
+        ] {
+            for decoder in [Decoder::new(), Decoder::new().lenient_encoded_text(true)] {
+                assert_eq!(
+                    run(message.as_bytes(), &decoder).unwrap(),
+                    vec![Token::ClearText(message.as_bytes().to_vec())],
+                    "{}",
+                    message
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn missing_suffix() {
+        let decoder = Decoder::new().allow_missing_suffix(true);
+        let encoded_word = |encoded_text: &[u8]| {
+            Token::EncodedWord(EncodedWord::new(
+                b"UTF-8".to_vec(),
+                b"Q".to_vec(),
+                encoded_text.to_vec(),
+            ))
+        };
+
+        assert_eq!(
+            get_parser(&decoder)
+                .parse(b"hello =?UTF-8?Q?a=?UTF-8?Q?b?= =?UTF-8?Q?c")
+                .unwrap(),
+            vec![
+                Token::ClearText(b"hello ".to_vec()),
+                encoded_word(b"a"),
+                encoded_word(b"b"),
+                encoded_word(b"c"),
+            ]
+        );
+        assert_eq!(
+            run_spanned(b"=?UTF-8?Q?a=?UTF-8?Q?b", &decoder).unwrap(),
+            vec![(encoded_word(b"a"), 0..11), (encoded_word(b"b"), 11..22)]
+        );
+
+        // the encoded text may contain neither spaces nor the delimiter
+        for message in ["=?UTF-8?Q?a b", "=?UTF-8?Q?a?b", "=?UTF-8?Q?"] {
+            assert_eq!(
+                get_parser(&decoder).parse(message.as_bytes()).unwrap(),
+                vec![Token::ClearText(message.as_bytes().to_vec())],
+                "{}",
+                message
+            );
+        }
+
+        let message = "=?UTF-8?Q?a";
+        assert_eq!(
+            get_parser(&Decoder::new())
+                .parse(message.as_bytes())
+                .unwrap(),
+            vec![Token::ClearText(message.as_bytes().to_vec())]
+        );
+    }
+
+    #[test]
+    fn lenient_encoded_text_ends_before_next_prefix() {
+        let decoder = Decoder::new().lenient_encoded_text(true);
+
+        assert_eq!(
+            get_parser(&decoder)
+                .parse(b"=?UTF-8?Q?a?b=?UTF-8?Q?c?=")
+                .unwrap(),
+            vec![
+                Token::ClearText(b"=?UTF-8?Q?a?b".to_vec()),
+                Token::EncodedWord(EncodedWord::new(
+                    b"UTF-8".to_vec(),
+                    b"Q".to_vec(),
+                    b"c".to_vec(),
+                )),
+            ]
+        );
+
+        // the padding of the encoded text is still followed by the suffix
+        assert_eq!(
+            get_parser(&decoder).parse(b"=?UTF-8?B?YQ=?=").unwrap(),
+            vec![Token::EncodedWord(EncodedWord::new(
+                b"UTF-8".to_vec(),
+                b"B".to_vec(),
+                b"YQ=".to_vec(),
+            ))]
+        );
+    }
+
+    #[test]
+    fn line_endings_between_encoded_words() {
+        let decoder = Decoder::new();
+        let expected = vec![
+            Token::EncodedWord(EncodedWord::new(
+                b"A".to_vec(),
+                b"Q".to_vec(),
+                b"x".to_vec(),
+            )),
+            Token::EncodedWord(EncodedWord::new(
+                b"A".to_vec(),
+                b"Q".to_vec(),
+                b"y".to_vec(),
+            )),
+        ];
+
+        for separator in ["\r", "\n", "\r\n", "\r\n \t  ", "\r \t", "\n\t\t"] {
+            let message = format!("=?A?Q?x?={}=?A?Q?y?=", separator);
+            assert_eq!(
+                run(message.as_bytes(), &decoder).unwrap(),
+                expected,
+                "{:?}",
+                separator
+            );
+        }
+    }
+
+    #[test]
+    fn lenient_word_boundaries() {
+        assert_eq!(
+            run(b"foo=?UTF-8?Q?x?=", &Decoder::new()).unwrap(),
+            vec![
+                Token::ClearText(b"foo".to_vec()),
+                Token::EncodedWord(EncodedWord::new(
+                    b"UTF-8".to_vec(),
+                    b"Q".to_vec(),
+                    b"x".to_vec()
+                )),
+            ]
+        );
+    }
+
     #[test]
     fn encoded_word_has_especials() {
         let parser = get_parser(&Decoder::new());
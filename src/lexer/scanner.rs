@@ -0,0 +1,237 @@
+//! A hand-written lexer which doesn't depend on chumsky. It produces the same
+//! tokens as the chumsky grammar.
+
+#[cfg(test)]
+use super::Tokens;
+use alloc::vec::Vec;
+
+use super::{
+    encoded_word, get_especials, is_field_whitespace, trim_field, EncodedWord, Result,
+    SpannedTokens, Token, SPACE,
+};
+use crate::Decoder;
+
+pub(super) fn lex(encoded_bytes: &[u8], decoder: &Decoder) -> Result<SpannedTokens> {
+    Ok(Scanner::new(encoded_bytes, decoder).scan())
+}
+
+/// Mirrors the chumsky parser of the grammar, so the lexer tests can be run
+/// against both lexers.
+#[cfg(test)]
+pub(super) struct TestParser(Decoder);
+
+#[cfg(test)]
+impl TestParser {
+    pub(super) fn parse(&self, encoded_bytes: &[u8]) -> Result<Tokens> {
+        let spanned_tokens = lex(encoded_bytes, &self.0)?;
+        Ok(spanned_tokens
+            .into_iter()
+            .map(|(token, _span)| token)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub(super) fn get_parser(decoder: &Decoder) -> TestParser {
+    TestParser(decoder.clone())
+}
+
+struct Scanner<'a> {
+    encoded_bytes: &'a [u8],
+    decoder: &'a Decoder,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(encoded_bytes: &'a [u8], decoder: &'a Decoder) -> Self {
+        Self {
+            encoded_bytes,
+            decoder,
+        }
+    }
+
+    fn scan(&self) -> SpannedTokens {
+        let mut spanned_tokens = SpannedTokens::new();
+        let mut position = 0;
+
+        while position < self.encoded_bytes.len() {
+            match self.encoded_word_at(position) {
+                Some((encoded_word, end)) => {
+                    spanned_tokens.push((Token::EncodedWord(encoded_word), position..end));
+                    position = self.skip_whitespace_before_encoded_word(end);
+                }
+                None => {
+                    let end = (position + 1..self.encoded_bytes.len())
+                        .find(|&start| self.encoded_word_at(start).is_some())
+                        .unwrap_or(self.encoded_bytes.len());
+                    let clear_text = self.encoded_bytes[position..end].to_vec();
+
+                    spanned_tokens.push((Token::ClearText(clear_text), position..end));
+                    position = end;
+                }
+            }
+        }
+
+        spanned_tokens
+    }
+
+    /// Returns the position after the whitespace if it's followed by another
+    /// encoded word, since the whitespace between encoded words is dropped.
+    fn skip_whitespace_before_encoded_word(&self, position: usize) -> usize {
+        let next_position = self.encoded_bytes[position..]
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .map_or(self.encoded_bytes.len(), |offset| position + offset);
+
+        if self.encoded_word_at(next_position).is_some() {
+            next_position
+        } else {
+            position
+        }
+    }
+
+    /// Returns the encoded word which starts at the given position and the
+    /// position after it, if there's one.
+    fn encoded_word_at(&self, start: usize) -> Option<(EncodedWord, usize)> {
+        if !self.encoded_bytes[start..].starts_with(encoded_word::PREFIX) {
+            return None;
+        }
+
+        let delimiter = self.decoder.delimiter;
+        let mut position = start + encoded_word::PREFIX.len();
+
+        let charset = self.take_token(&mut position)?;
+        self.take_byte(&mut position, delimiter)?;
+        let encoding = self.take_token(&mut position)?;
+
+        let mut text_position = position;
+        let encoded_text = match self.take_byte(&mut text_position, delimiter) {
+            Some(()) => self.take_encoded_text(&mut text_position),
+            None => None,
+        };
+
+        let encoded_text = match encoded_text {
+            Some(encoded_text) => {
+                position = text_position;
+                encoded_text
+            }
+            // the encoded text (and the delimiter in front of it) may be missing
+            None if self.decoder.lenient_encoded_text && self.is_suffix_at(position) => Vec::new(),
+            None => {
+                self.take_byte(&mut position, delimiter)?;
+                self.take_unterminated_encoded_text(&mut position)?
+            }
+        };
+
+        let encoded_word = EncodedWord::new(charset, encoding, encoded_text);
+        let suffix_length = if self.is_suffix_at(position) { 2 } else { 0 };
+        Some((encoded_word, position + suffix_length))
+    }
+
+    /// Takes the encoded text of an encoded word without the closing `?=`,
+    /// which runs up to the end of the input or to the next encoded word, if
+    /// the decoder allows it.
+    fn take_unterminated_encoded_text(&self, position: &mut usize) -> Option<Vec<u8>> {
+        if !self.decoder.allow_missing_suffix {
+            return None;
+        }
+
+        let rest = &self.encoded_bytes[*position..];
+        let mut length = 0;
+        while length < rest.len() && !rest[length..].starts_with(encoded_word::PREFIX) {
+            if rest[length] == self.decoder.delimiter || rest[length] == SPACE {
+                return None;
+            }
+            length += 1;
+        }
+
+        if length == 0 {
+            return None;
+        }
+
+        let encoded_text = rest[..length].to_vec();
+        *position += length;
+        Some(encoded_text)
+    }
+
+    /// Takes the charset or the encoding.
+    fn take_token(&self, position: &mut usize) -> Option<Vec<u8>> {
+        let delimiter = self.decoder.delimiter;
+        let especials = get_especials();
+
+        let length = self.encoded_bytes[*position..]
+            .iter()
+            .take_while(|&&c| {
+                (self.decoder.trim_field_whitespace && is_field_whitespace(c))
+                    || (c != SPACE
+                        && c != delimiter
+                        && !c.is_ascii_control()
+                        && !especials.contains(&c))
+            })
+            .count();
+
+        let min_length = if self.decoder.lenient_encoded_text {
+            0
+        } else {
+            1
+        };
+        if length < min_length {
+            return None;
+        }
+
+        let token = self.encoded_bytes[*position..*position + length].to_vec();
+        *position += length;
+        Some(trim_field(token))
+    }
+
+    /// Takes the encoded text and the suffix after it.
+    fn take_encoded_text(&self, position: &mut usize) -> Option<Vec<u8>> {
+        let delimiter = self.decoder.delimiter;
+        let mut end = *position;
+
+        while let Some(&byte) = self.encoded_bytes.get(end) {
+            // otherwise every following prefix would be scanned up to the end
+            // of the input again, which takes quadratic time
+            if self.decoder.lenient_encoded_text && self.is_next_prefix_at(end) {
+                break;
+            }
+
+            let is_lone_delimiter = byte == delimiter
+                && self.decoder.lenient_encoded_text
+                && matches!(self.encoded_bytes.get(end + 1), Some(&next) if next != b'=');
+
+            if (byte != delimiter && byte != SPACE) || is_lone_delimiter {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+
+        if !self.is_suffix_at(end) {
+            return None;
+        }
+
+        let encoded_text = self.encoded_bytes[*position..end].to_vec();
+        *position = end;
+        Some(encoded_text)
+    }
+
+    fn take_byte(&self, position: &mut usize, byte: u8) -> Option<()> {
+        if self.encoded_bytes.get(*position) == Some(&byte) {
+            *position += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the prefix of another encoded word starts at the given
+    /// position, rather than a `=` which is followed by the suffix.
+    fn is_next_prefix_at(&self, position: usize) -> bool {
+        self.encoded_bytes[position..].starts_with(encoded_word::PREFIX)
+            && matches!(self.encoded_bytes.get(position + 2), Some(&next) if next != b'=')
+    }
+
+    fn is_suffix_at(&self, position: usize) -> bool {
+        self.encoded_bytes[position..].starts_with(&[self.decoder.delimiter, b'='])
+    }
+}
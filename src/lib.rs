@@ -7,17 +7,48 @@
 //! # Where to start looking
 //! You will likely want to start looking into [Decoder] and/or the [decode]
 //! to use this crate.
+//!
+//! # Safety
+//! The crate doesn't contain any `unsafe` code, which is enforced by
+//! `#![forbid(unsafe_code)]`.
+//!
+//! # `no_std`
+//! The crate only needs `alloc` if the default `std` feature is disabled
+//! (`default-features = false`). Everything stays available except:
+//!
+//! - `Decoder::decode_to_writer_with_limit`, which writes to a
+//!   `std::io::Write`
+//! - the `async`, `arbitrary` and `rayon` features, which enable `std` again
+//!
+//! The errors implement [core::error::Error] either way.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+use alloc::{string::String, vec, vec::Vec};
 
 mod decoder;
-pub use decoder::{Decoder, Error, RecoverStrategy};
+pub use decoder::{
+    CharsetDecoder, ControlCharPolicy, Decoder, Error, ErrorClass, HeaderContext,
+    MalformedEscapePolicy, RecoverStrategy, TransferDecoder,
+};
 
+mod encoder;
 mod evaluator;
 mod lexer;
 mod parser;
+mod report;
+mod validator;
 
-pub use evaluator::Error as EvaluatorError;
-pub use lexer::{Error as LexerError, TooLongEncodedWords};
-pub use parser::Error as ParserError;
+pub use encoder::{Encoder, Error as EncoderError};
+pub use evaluator::{DecodedSegment, DecodedWord, Error as EvaluatorError, Segment, SegmentKind};
+pub use lexer::{
+    encoded_word::EncodedWord, Error as LexerError, LexError, Token, TooLongEncodedWords,
+};
+pub use parser::{Encoding, Error as ParserError, ParsedCharset, ParsedEncodedWord};
+pub use report::{DecodeOutcome, DecodeReport, RecoveredWord};
+pub use validator::ValidationIssue;
 
 /// Decodes the given RFC 2047 MIME Message Header encoded string
 /// using a default decoder.
@@ -40,3 +71,151 @@ pub use parser::Error as ParserError;
 pub fn decode<T: AsRef<[u8]>>(encoded_str: T) -> Result<String, Error> {
     Decoder::new().decode(encoded_str)
 }
+
+/// Splits the given RFC 2047 MIME Message Header encoded string into its
+/// tokens, the clear texts and the encoded words, without decoding them.
+///
+/// The tokens are the ones which the decoder would decode: the settings of
+/// the decoder which affect the lexing (like [Decoder::delimiter] or the
+/// strategy for too long encoded words) apply, the whitespace between two
+/// encoded words is dropped and encoded words which won't be decoded are
+/// clear text.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{tokenize, Decoder, Token};
+///
+/// let tokens = tokenize("Hello =?UTF-8?Q?W=C3=B6rld?= =?UTF-8?B?IQ==?=", &Decoder::new()).unwrap();
+///
+/// assert_eq!(tokens.len(), 3);
+/// assert_eq!(tokens[0], Token::ClearText(b"Hello ".to_vec()));
+///
+/// let encoded_words = tokens
+///     .iter()
+///     .filter(|token| matches!(token, Token::EncodedWord(_)))
+///     .count();
+/// assert_eq!(encoded_words, 2);
+/// ```
+pub fn tokenize<T: AsRef<[u8]>>(
+    encoded_str: T,
+    decoder: &Decoder,
+) -> Result<Vec<Token>, LexerError> {
+    lexer::run(encoded_str.as_ref(), decoder)
+}
+
+/// Looks up the encodings and the charsets of the given tokens (see
+/// [tokenize]) without decoding them, so they can be evaluated in a custom
+/// way.
+///
+/// The settings of the decoder which affect the parsing (like
+/// [Decoder::unknown_charset] or [Decoder::charset_overrides]) apply.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{parse, tokenize, Decoder, Encoding, ParsedEncodedWord};
+///
+/// let decoder = Decoder::new();
+/// let tokens = tokenize("Hello =?ISO-8859-1?Q?W=F6rld?=", &decoder).unwrap();
+/// let parsed_encoded_words = parse(tokens, &decoder).unwrap();
+///
+/// assert_eq!(parsed_encoded_words[0], ParsedEncodedWord::ClearText(b"Hello ".to_vec()));
+/// match &parsed_encoded_words[1] {
+///     ParsedEncodedWord::EncodedWord { charset, encoding, encoded_text, .. } => {
+///         assert_eq!(charset.unwrap().name(), "windows-1252");
+///         assert_eq!(*encoding, Encoding::Q);
+///         assert_eq!(encoded_text, b"W=F6rld");
+///     }
+///     ParsedEncodedWord::ClearText(_) => unreachable!(),
+/// }
+/// ```
+pub fn parse(tokens: Vec<Token>, decoder: &Decoder) -> Result<Vec<ParsedEncodedWord>, ParserError> {
+    parser::run(tokens, decoder)
+}
+
+/// Checks the encoded words of the given RFC 2047 MIME Message Header encoded
+/// string for violations of the RFC, without decoding them. All issues are
+/// collected instead of stopping at the first one.
+///
+/// The input is lexed like a default [Decoder] does, except that too long
+/// encoded words are kept, so they can be reported. Text which doesn't form
+/// an encoded word (like one with a space in its encoded text) is clear text
+/// and isn't checked.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{validate, ValidationIssue};
+///
+/// assert_eq!(validate("Hello =?UTF-8?Q?W=C3=B6rld?="), Ok(()));
+///
+/// let issues = validate("=?x-unknown?Q?a=?= =?UTF-8?X?b?=").unwrap_err();
+/// assert_eq!(
+///     issues,
+///     vec![
+///         ValidationIssue::UnknownCharset { span: 0..18 },
+///         ValidationIssue::MalformedEscape { span: 15..16 },
+///         ValidationIssue::InvalidEncoding { span: 19..32 },
+///     ]
+/// );
+/// ```
+pub fn validate<T: AsRef<[u8]>>(encoded_str: T) -> Result<(), Vec<ValidationIssue>> {
+    let issues = validator::run(encoded_str.as_ref());
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Encodes the given string into a RFC 2047 MIME Message Header encoded string
+/// with the given charset and encoding, using a default encoder.
+///
+/// This function equals doing `Encoder::new().charset(charset).encoding(encoding).encode`.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{decode, encode, Encoding};
+///
+/// let encoded_str = encode("Grüße aus Köln", "UTF-8", Encoding::B).unwrap();
+///
+/// assert_eq!(encoded_str, "=?UTF-8?B?R3LDvMOfZQ==?= aus =?UTF-8?B?S8O2bG4=?=");
+/// assert_eq!(decode(&encoded_str).unwrap(), "Grüße aus Köln");
+/// ```
+pub fn encode(input: &str, charset: &str, encoding: Encoding) -> Result<String, EncoderError> {
+    Encoder::new()
+        .charset(charset)
+        .encoding(encoding)
+        .encode(input)
+}
+
+/// Decodes a single encoded word which has already been split into its
+/// `charset`, `encoding` and `encoded_text` fields.
+///
+/// No lexing is done here, only the transfer decoding (B or Q) and the charset
+/// decoding are applied, which is useful if another parser already extracted
+/// the fields of the encoded word.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::decode_encoded_word;
+///
+/// // equals "=?UTF-8?B?c3Ry?="
+/// assert_eq!(decode_encoded_word(b"UTF-8", b'B', b"c3Ry").unwrap(), "str");
+/// ```
+pub fn decode_encoded_word(
+    charset: &[u8],
+    encoding: u8,
+    encoded_text: &[u8],
+) -> Result<String, Error> {
+    let encoded_word = lexer::encoded_word::EncodedWord::new(
+        charset.to_vec(),
+        vec![encoding],
+        encoded_text.to_vec(),
+    );
+    let decoder = Decoder::new();
+    let parsed_encoded_word =
+        parser::ParsedEncodedWord::convert_encoded_word(encoded_word, &decoder)?;
+    let decoded_str = evaluator::run(vec![parsed_encoded_word], &decoder)?;
+
+    Ok(decoded_str)
+}
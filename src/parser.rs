@@ -1,17 +1,26 @@
+use alloc::{string::String, vec::Vec};
 use charset::Charset;
-use std::{convert::TryFrom, result};
+use core::{convert::TryFrom, result};
 
-use crate::lexer::{encoded_word, Token, Tokens};
+use crate::{
+    decoder::RecoverStrategy,
+    lexer::{encoded_word, Token, Tokens},
+    Decoder,
+};
 
 /// All errors which the parser can throw.
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum Error {
-    #[error("cannot parse encoding: encoding is bigger than a char")]
-    ParseEncodingTooBigError,
+    #[error("cannot parse encoding {0}: encoding is bigger than a char")]
+    ParseEncodingTooBigError(String),
     #[error("cannot parse encoding: encoding is empty")]
     ParseEncodingEmptyError,
     #[error("cannot parse encoding {0}: B or Q is expected")]
     ParseEncodingError(char),
+    #[error("cannot parse charset {0}: charset is unknown")]
+    UnknownCharset(String),
+    #[error("cannot parse charset: charset is empty")]
+    EmptyCharset,
 }
 
 type Result<T> = result::Result<T, Error>;
@@ -19,10 +28,16 @@ type Result<T> = result::Result<T, Error>;
 pub type ClearText = Vec<u8>;
 pub type ParsedEncodedWords = Vec<ParsedEncodedWord>;
 
+/// The encoding of an encoded word.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Encoding {
+    /// The "B" encoding which is base64.
     B,
+    /// The "Q" encoding which is similar to quoted-printable.
     Q,
+    /// Another encoding (the upper case letter) for which a transfer decoder
+    /// has been registered with [crate::Decoder::with_transfer_decoder].
+    Custom(u8),
 }
 
 impl Encoding {
@@ -36,7 +51,8 @@ impl TryFrom<Vec<u8>> for Encoding {
 
     fn try_from(token: Vec<u8>) -> Result<Self> {
         if token.len() > Self::MAX_LENGTH {
-            return Err(Error::ParseEncodingTooBigError);
+            let encoding = String::from_utf8_lossy(&token).into_owned();
+            return Err(Error::ParseEncodingTooBigError(encoding));
         }
 
         let encoding = token.first().ok_or(Error::ParseEncodingEmptyError)?;
@@ -50,54 +66,281 @@ impl TryFrom<Vec<u8>> for Encoding {
     }
 }
 
+/// The charset of an encoded word after its label has been looked up, see
+/// [ParsedEncodedWord].
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct ParsedCharset(Charset);
+
+impl ParsedCharset {
+    /// Returns the name of the charset, like `windows-1252` for the label
+    /// `ISO-8859-1`.
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    pub(crate) fn charset(self) -> Charset {
+        self.0
+    }
+}
+
+/// A token after its encoding and charset have been looked up, see
+/// [crate::parse].
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub enum ParsedEncodedWord {
+    /// The clear text, which is kept as it is.
     ClearText(ClearText),
+    /// The encoded word, whose encoded text is still encoded.
     EncodedWord {
-        charset: Option<Charset>,
+        /// The charset of the encoded word, or `None` if it's unknown and
+        /// [Decoder::unknown_charset] doesn't abort.
+        charset: Option<ParsedCharset>,
         encoding: Encoding,
         encoded_text: Vec<u8>,
+        /// The encoded word as it was lexed, used to keep it verbatim if it
+        /// can't be decoded.
+        source: encoded_word::EncodedWord,
     },
 }
 
 impl ParsedEncodedWord {
-    pub fn convert_encoded_word(encoded_word: encoded_word::EncodedWord) -> Result<Self> {
-        let encoding = Encoding::try_from(encoded_word.encoding)?;
-        let charset = Charset::for_label(&encoded_word.charset);
+    /// Looks up the encoding and the charset of the encoded word, according
+    /// to the settings of the decoder. An empty encoded word (like `=???=`)
+    /// becomes empty clear text.
+    pub fn convert_encoded_word(
+        encoded_word: encoded_word::EncodedWord,
+        decoder: &Decoder,
+    ) -> Result<Self> {
+        Self::convert_encoded_word_with_charset(encoded_word, None, decoder)
+    }
+
+    /// Converts the encoded word like [ParsedEncodedWord::convert_encoded_word],
+    /// but looks up the given charset label instead of the one of the encoded
+    /// word, if any.
+    fn convert_encoded_word_with_charset(
+        encoded_word: encoded_word::EncodedWord,
+        charset_override: Option<&str>,
+        decoder: &Decoder,
+    ) -> Result<Self> {
+        if encoded_word.is_empty() {
+            return Ok(Self::ClearText(ClearText::new()));
+        }
+
+        let encoding = encoding_for_label(&encoded_word.encoding, decoder)?;
+        let label = charset_override
+            .map(str::as_bytes)
+            .unwrap_or(&encoded_word.charset);
+
+        // only the lenient lexer lets an empty charset through
+        if label.is_empty() {
+            if decoder.unknown_charset == RecoverStrategy::Abort {
+                return Err(Error::EmptyCharset);
+            }
+
+            return Ok(Self::EncodedWord {
+                charset: Charset::for_label(b"UTF-8").map(ParsedCharset),
+                encoding,
+                encoded_text: encoded_word.encoded_text.clone(),
+                source: encoded_word,
+            });
+        }
+
+        let charset = charset_for_label(label, decoder);
+
+        if charset.is_none()
+            && !is_claimed_by_charset_decoder(label, decoder)
+            && decoder.unknown_charset == RecoverStrategy::Abort
+        {
+            let label = String::from_utf8_lossy(label).into_owned();
+            return Err(Error::UnknownCharset(label));
+        }
 
         Ok(Self::EncodedWord {
-            charset,
+            charset: charset.map(ParsedCharset),
             encoding,
-            encoded_text: encoded_word.encoded_text,
+            encoded_text: encoded_word.encoded_text.clone(),
+            source: encoded_word,
         })
     }
 }
 
-pub fn run(tokens: Tokens) -> Result<ParsedEncodedWords> {
-    let parsed_encoded_words = convert_tokens_to_encoded_words(tokens)?;
-    Ok(parsed_encoded_words)
-}
+/// Common nonstandard charset labels and the charsets they stand for, which
+/// [Decoder::charset_aliases] contains by default. The aliases are normalized
+/// like [charset_alias_key] does.
+pub const DEFAULT_CHARSET_ALIASES: &[(&str, &str)] = &[
+    ("cp-1252", "windows-1252"),
+    ("cp874", "windows-874"),
+    ("cp932", "Shift_JIS"),
+    ("cp936", "GBK"),
+    ("cp949", "EUC-KR"),
+    ("cp950", "Big5"),
+    ("koi8r", "KOI8-R"),
+    ("latin-1", "ISO-8859-1"),
+    ("latin-9", "ISO-8859-15"),
+    ("latin9", "ISO-8859-15"),
+    ("shiftjis", "Shift_JIS"),
+    ("utf8mb4", "UTF-8"),
+    ("win-1252", "windows-1252"),
+];
 
-fn convert_tokens_to_encoded_words(tokens: Tokens) -> Result<ParsedEncodedWords> {
-    tokens
-        .into_iter()
-        .map(|token: Token| match token {
-            Token::ClearText(clear_text) => Ok(ParsedEncodedWord::ClearText(clear_text)),
-            Token::EncodedWord(encoded_word) => {
-                ParsedEncodedWord::convert_encoded_word(encoded_word)
-            }
+/// Normalizes the charset label for the lookup in [Decoder::charset_aliases]:
+/// it's lowercased and underscores are replaced with hyphens.
+pub fn charset_alias_key(label: &[u8]) -> String {
+    label
+        .iter()
+        .map(|&byte| match byte {
+            b'_' => '-',
+            byte => char::from(byte.to_ascii_lowercase()),
         })
         .collect()
 }
 
+/// IANA MIB enum numbers of common charsets and their labels.
+const MIB_CHARSETS: &[(u16, &str)] = &[
+    (3, "US-ASCII"),
+    (4, "ISO-8859-1"),
+    (5, "ISO-8859-2"),
+    (6, "ISO-8859-3"),
+    (7, "ISO-8859-4"),
+    (8, "ISO-8859-5"),
+    (9, "ISO-8859-6"),
+    (10, "ISO-8859-7"),
+    (11, "ISO-8859-8"),
+    (12, "ISO-8859-9"),
+    (13, "ISO-8859-10"),
+    (17, "Shift_JIS"),
+    (18, "EUC-JP"),
+    (38, "EUC-KR"),
+    (39, "ISO-2022-JP"),
+    (106, "UTF-8"),
+    (109, "ISO-8859-13"),
+    (110, "ISO-8859-14"),
+    (111, "ISO-8859-15"),
+    (112, "ISO-8859-16"),
+    (113, "GBK"),
+    (114, "GB18030"),
+    (1012, "UTF-7"),
+    (1013, "UTF-16BE"),
+    (1014, "UTF-16LE"),
+    (1015, "UTF-16"),
+    (2025, "GB2312"),
+    (2026, "Big5"),
+    (2027, "macintosh"),
+    (2084, "KOI8-R"),
+    (2086, "IBM866"),
+    (2088, "KOI8-U"),
+    (2250, "windows-1250"),
+    (2251, "windows-1251"),
+    (2252, "windows-1252"),
+    (2253, "windows-1253"),
+    (2254, "windows-1254"),
+    (2255, "windows-1255"),
+    (2256, "windows-1256"),
+    (2257, "windows-1257"),
+    (2258, "windows-1258"),
+];
+
+/// Returns the charset label of the given IANA MIB enum number, if it's known.
+fn mib_charset_label(mib: &[u8]) -> Option<&'static str> {
+    let mib: u16 = core::str::from_utf8(mib).ok()?.parse().ok()?;
+
+    MIB_CHARSETS
+        .iter()
+        .find(|(known_mib, _)| *known_mib == mib)
+        .map(|(_, label)| *label)
+}
+
+/// Looks up the charset of the given label, according to the settings of the decoder.
+pub fn charset_for_label(label: &[u8], decoder: &Decoder) -> Option<Charset> {
+    let label = decoder
+        .charset_aliases
+        .get(&charset_alias_key(label))
+        .map_or(label, |charset| charset.as_bytes());
+
+    if decoder.allow_mib_charset && !label.is_empty() && label.iter().all(u8::is_ascii_digit) {
+        return mib_charset_label(label).and_then(|label| Charset::for_label(label.as_bytes()));
+    }
+
+    Charset::for_label(label).or_else(|| {
+        // some systems can't use hyphens, like in `ISO_8859_1` or `UTF_8`
+        let label = label
+            .iter()
+            .map(|&byte| if byte == b'_' { b'-' } else { byte })
+            .collect::<Vec<u8>>();
+        Charset::for_label(&label)
+    })
+}
+
+/// Returns `true` if the charset of the given label is known, either by its
+/// label or by the charset decoder of the decoder.
+pub fn is_known_charset(label: &[u8], decoder: &Decoder) -> bool {
+    charset_for_label(label, decoder).is_some() || is_claimed_by_charset_decoder(label, decoder)
+}
+
+/// Returns `true` if the charset decoder of the decoder handles the charset of
+/// the given label, see [crate::CharsetDecoder::claims].
+fn is_claimed_by_charset_decoder(label: &[u8], decoder: &Decoder) -> bool {
+    decoder
+        .charset_decoder
+        .as_ref()
+        .is_some_and(|charset_decoder| charset_decoder.claims(&String::from_utf8_lossy(label)))
+}
+
+/// Returns the encoding of the given label: either B, Q or one for which a
+/// transfer decoder has been registered.
+pub fn encoding_for_label(label: &[u8], decoder: &Decoder) -> Result<Encoding> {
+    Encoding::try_from(label.to_vec()).or_else(|err| match label {
+        [letter]
+            if decoder
+                .transfer_decoders
+                .contains_key(&letter.to_ascii_uppercase()) =>
+        {
+            Ok(Encoding::Custom(letter.to_ascii_uppercase()))
+        }
+        _ => Err(err),
+    })
+}
+
+pub fn run(tokens: Tokens, decoder: &Decoder) -> Result<ParsedEncodedWords> {
+    let parsed_encoded_words =
+        convert_tokens_to_encoded_words(tokens, decoder).collect::<Result<_>>()?;
+    Ok(parsed_encoded_words)
+}
+
+/// Converts the tokens lazily, one by one, so the caller may stop at the
+/// first token which can't be converted.
+pub fn convert_tokens_to_encoded_words(
+    tokens: Tokens,
+    decoder: &Decoder,
+) -> impl Iterator<Item = Result<ParsedEncodedWord>> + '_ {
+    let mut encoded_word_index = 0;
+
+    tokens.into_iter().map(move |token: Token| match token {
+        Token::ClearText(clear_text) => Ok(ParsedEncodedWord::ClearText(clear_text)),
+        Token::EncodedWord(encoded_word) => {
+            let charset_override = decoder
+                .charset_overrides
+                .get(&encoded_word_index)
+                .or(decoder.charset_override.as_ref())
+                .map(String::as_str);
+            encoded_word_index += 1;
+
+            ParsedEncodedWord::convert_encoded_word_with_charset(
+                encoded_word,
+                charset_override,
+                decoder,
+            )
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use charset::Charset;
 
     use crate::{
-        lexer,
-        parser::{self, Encoding, ParsedEncodedWord},
-        Decoder,
+        lexer::{self, encoded_word::EncodedWord},
+        parser::{self, Encoding, Error, ParsedCharset, ParsedEncodedWord},
+        Decoder, RecoverStrategy,
     };
 
     /// Example taken from:
@@ -107,13 +350,18 @@ mod tests {
     #[test]
     fn test_parse1() {
         let message = "=?US-ASCII?Q?Keith_Moore?=".as_bytes();
-        let tokens = lexer::run(&message, Decoder::new()).unwrap();
-        let parsed = parser::run(tokens).unwrap();
+        let tokens = lexer::run(message, &Decoder::new()).unwrap();
+        let parsed = parser::run(tokens, &Decoder::new()).unwrap();
 
         let expected = vec![ParsedEncodedWord::EncodedWord {
-            charset: Charset::for_label("US-ASCII".as_bytes()),
+            charset: Charset::for_label("US-ASCII".as_bytes()).map(ParsedCharset),
             encoding: Encoding::Q,
             encoded_text: "Keith_Moore".as_bytes().to_vec(),
+            source: EncodedWord::new(
+                "US-ASCII".as_bytes().to_vec(),
+                "Q".as_bytes().to_vec(),
+                "Keith_Moore".as_bytes().to_vec(),
+            ),
         }];
 
         assert_eq!(parsed, expected);
@@ -126,13 +374,18 @@ mod tests {
     #[test]
     fn test_parse2() {
         let message = "=?ISO-8859-1?Q?Keld_J=F8rn_Simonsen?=".as_bytes();
-        let tokens = lexer::run(&message, Decoder::new()).unwrap();
-        let parsed = parser::run(tokens).unwrap();
+        let tokens = lexer::run(message, &Decoder::new()).unwrap();
+        let parsed = parser::run(tokens, &Decoder::new()).unwrap();
 
         let expected = vec![ParsedEncodedWord::EncodedWord {
-            charset: Charset::for_label("ISO-8859-1".as_bytes()),
+            charset: Charset::for_label("ISO-8859-1".as_bytes()).map(ParsedCharset),
             encoding: Encoding::Q,
             encoded_text: "Keld_J=F8rn_Simonsen".as_bytes().to_vec(),
+            source: EncodedWord::new(
+                "ISO-8859-1".as_bytes().to_vec(),
+                "Q".as_bytes().to_vec(),
+                "Keld_J=F8rn_Simonsen".as_bytes().to_vec(),
+            ),
         }];
 
         assert_eq!(parsed, expected);
@@ -145,13 +398,18 @@ mod tests {
     #[test]
     fn test_parse3() {
         let message = "=?ISO-8859-1?Q?Andr=E9?=".as_bytes();
-        let tokens = lexer::run(&message, Decoder::new()).unwrap();
-        let parsed = parser::run(tokens).unwrap();
+        let tokens = lexer::run(message, &Decoder::new()).unwrap();
+        let parsed = parser::run(tokens, &Decoder::new()).unwrap();
 
         let expected = vec![ParsedEncodedWord::EncodedWord {
-            charset: Charset::for_label("ISO-8859-1".as_bytes()),
+            charset: Charset::for_label("ISO-8859-1".as_bytes()).map(ParsedCharset),
             encoding: Encoding::Q,
             encoded_text: "Andr=E9".as_bytes().to_vec(),
+            source: EncodedWord::new(
+                "ISO-8859-1".as_bytes().to_vec(),
+                "Q".as_bytes().to_vec(),
+                "Andr=E9".as_bytes().to_vec(),
+            ),
         }];
 
         assert_eq!(parsed, expected);
@@ -164,15 +422,52 @@ mod tests {
     #[test]
     fn test_parse4() {
         let message = "=?ISO-8859-1?B?SWYgeW91IGNhbiByZWFkIHRoaXMgeW8=?=".as_bytes();
-        let tokens = lexer::run(&message, Decoder::new()).unwrap();
-        let parsed = parser::run(tokens).unwrap();
+        let tokens = lexer::run(message, &Decoder::new()).unwrap();
+        let parsed = parser::run(tokens, &Decoder::new()).unwrap();
 
         let expected = vec![ParsedEncodedWord::EncodedWord {
-            charset: Charset::for_label("ISO-8859-1".as_bytes()),
+            charset: Charset::for_label("ISO-8859-1".as_bytes()).map(ParsedCharset),
             encoding: Encoding::B,
             encoded_text: "SWYgeW91IGNhbiByZWFkIHRoaXMgeW8=".as_bytes().to_vec(),
+            source: EncodedWord::new(
+                "ISO-8859-1".as_bytes().to_vec(),
+                "B".as_bytes().to_vec(),
+                "SWYgeW91IGNhbiByZWFkIHRoaXMgeW8=".as_bytes().to_vec(),
+            ),
         }];
 
         assert_eq!(parsed, expected);
     }
+
+    #[test]
+    fn invalid_encoding() {
+        let decode = |message: &str, decoder: &Decoder| {
+            let tokens = lexer::run(message.as_bytes(), decoder).unwrap();
+            parser::run(tokens, decoder)
+        };
+
+        assert_eq!(
+            decode("=?UTF-8?Q2?a?=", &Decoder::new()),
+            Err(Error::ParseEncodingTooBigError("Q2".to_string()))
+        );
+        assert_eq!(
+            decode("=?UTF-8?Q2?a?=", &Decoder::new())
+                .unwrap_err()
+                .to_string(),
+            "cannot parse encoding Q2: encoding is bigger than a char"
+        );
+        assert_eq!(
+            decode("=?UTF-8?X?a?=", &Decoder::new()),
+            Err(Error::ParseEncodingError('X'))
+        );
+
+        // with another strategy the encoded word is kept as clear text
+        let decoder = Decoder::new().invalid_encoding_strategy(RecoverStrategy::Skip);
+        assert_eq!(
+            decode("=?UTF-8?Q2?a?=", &decoder),
+            Ok(vec![ParsedEncodedWord::ClearText(
+                b"=?UTF-8?Q2?a?=".to_vec()
+            )])
+        );
+    }
 }
@@ -0,0 +1,136 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{evaluator, DecodedSegment, Decoder, ErrorClass, RecoverStrategy};
+
+/// The decoded string together with some information about it.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::Decoder;
+///
+/// let report = Decoder::new().decode_report("Hello =?UTF-8?Q?W=C3=B6rld?=").unwrap();
+///
+/// assert_eq!(report.decoded, "Hello Wörld");
+/// assert_eq!(report.first_non_ascii, Some(7));
+/// assert_eq!(report.dominant_charset.as_deref(), Some("UTF-8"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DecodeReport {
+    /// The decoded string, equals the output of [crate::Decoder::decode].
+    pub decoded: String,
+
+    /// The index of the first non-ASCII `char` in the decoded string (counted
+    /// in `char`s, not bytes), `None` if the decoded string is pure ASCII.
+    pub first_non_ascii: Option<usize>,
+
+    /// The charset label of the encoded words which produced the most `char`s
+    /// of the decoded string, `None` if there are no encoded words. Labels
+    /// are compared case-insensitively and on a tie the first charset wins.
+    pub dominant_charset: Option<String>,
+}
+
+impl DecodeReport {
+    pub(crate) fn from_segments(segments: Vec<DecodedSegment>, decoder: &Decoder) -> Self {
+        let decoded: String = segments
+            .iter()
+            .map(|segment| segment.decoded.as_str())
+            .collect();
+        let decoded = evaluator::normalize(decoded, decoder);
+
+        let first_non_ascii = decoded.chars().position(|c| !c.is_ascii());
+        let dominant_charset = dominant_charset(&segments);
+
+        Self {
+            decoded,
+            first_non_ascii,
+            dominant_charset,
+        }
+    }
+}
+
+/// The decoded string together with the encoded words which couldn't be
+/// decoded as described in the RFC and had to be recovered.
+///
+/// # Example
+/// ```
+/// use rfc2047_decoder::{Decoder, ErrorClass, RecoverStrategy};
+///
+/// let decoder = Decoder::new().recover_for(ErrorClass::InvalidBase64, RecoverStrategy::Skip);
+/// let outcome = decoder.decode_verbose("=?UTF-8?B?YQ==?= =?UTF-8?B?!?=").unwrap();
+///
+/// assert_eq!(outcome.text, "a=?UTF-8?B?!?=");
+/// assert_eq!(outcome.recovered.len(), 1);
+/// assert_eq!(outcome.recovered[0].encoded_word, "=?UTF-8?B?!?=");
+/// assert_eq!(outcome.recovered[0].class, ErrorClass::InvalidBase64);
+/// assert_eq!(outcome.recovered[0].strategy, RecoverStrategy::Skip);
+/// assert!(!outcome.is_fully_decoded());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DecodeOutcome {
+    /// The decoded string, equals the output of [crate::Decoder::decode].
+    pub text: String,
+
+    /// The encoded words which had to be recovered, in the order of the
+    /// input.
+    pub recovered: Vec<RecoveredWord>,
+}
+
+impl DecodeOutcome {
+    /// Returns `true` if no encoded word had to be recovered.
+    pub fn is_fully_decoded(&self) -> bool {
+        self.recovered.is_empty()
+    }
+}
+
+/// An encoded word for which a [RecoverStrategy] has been applied. See
+/// [DecodeOutcome].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct RecoveredWord {
+    /// The encoded word as it's in the input.
+    pub encoded_word: String,
+
+    /// The error which made the encoded word invalid.
+    pub class: ErrorClass,
+
+    /// The strategy which has been applied to the encoded word.
+    pub strategy: RecoverStrategy,
+}
+
+/// Tallies the decoded `char`s per charset and returns the charset with the
+/// most of them.
+fn dominant_charset(segments: &[DecodedSegment]) -> Option<String> {
+    let mut char_counts: Vec<(&str, usize)> = Vec::new();
+
+    for segment in segments {
+        let charset = match &segment.charset {
+            Some(charset) => charset.as_str(),
+            None => continue,
+        };
+        let char_count = segment.decoded.chars().count();
+
+        match char_counts
+            .iter_mut()
+            .find(|(known_charset, _)| known_charset.eq_ignore_ascii_case(charset))
+        {
+            Some((_, count)) => *count += char_count,
+            None => char_counts.push((charset, char_count)),
+        }
+    }
+
+    char_counts
+        .into_iter()
+        .fold(
+            None,
+            |dominant: Option<(&str, usize)>, (charset, count)| match dominant {
+                Some((_, dominant_count)) if dominant_count >= count => dominant,
+                _ => Some((charset, count)),
+            },
+        )
+        .map(|(charset, _)| charset.to_string())
+}
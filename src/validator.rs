@@ -0,0 +1,199 @@
+use alloc::vec::Vec;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use core::ops::Range;
+use thiserror::Error;
+
+use crate::{
+    evaluator,
+    lexer::{self, encoded_word, Token},
+    parser, Decoder, Encoding, RecoverStrategy,
+};
+
+/// A violation of the RFC in an encoded word, see [crate::validate].
+///
+/// Each issue holds the byte range in the input of the encoded word or, for
+/// the issues of the encoded text, of the offending char.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The encoded word is longer than 75 chars.
+    #[error("the encoded word at {span:?} is longer than 75 chars")]
+    TooLong { span: Range<usize> },
+
+    /// The encoding of the encoded word is neither `B` nor `Q`.
+    #[error("the encoded word at {span:?} is neither B nor Q encoded")]
+    InvalidEncoding { span: Range<usize> },
+
+    /// The charset of the encoded word is unknown.
+    #[error("the charset of the encoded word at {span:?} is unknown")]
+    UnknownCharset { span: Range<usize> },
+
+    /// The encoded text contains a byte which isn't printable ASCII.
+    #[error("the byte {byte:#04x} at {span:?} isn't allowed in encoded text")]
+    IllegalChar { span: Range<usize>, byte: u8 },
+
+    /// The encoded text of the B encoded word isn't valid base64.
+    #[error("the encoded text of the encoded word at {span:?} isn't valid base64")]
+    InvalidBase64 { span: Range<usize> },
+
+    /// The `=` in the Q encoded text isn't followed by two hex digits.
+    #[error("the `=` at {span:?} isn't followed by two hex digits")]
+    MalformedEscape { span: Range<usize> },
+}
+
+impl ValidationIssue {
+    /// Returns the byte range of the encoded word or of the offending char in
+    /// the input.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::TooLong { span }
+            | Self::InvalidEncoding { span }
+            | Self::UnknownCharset { span }
+            | Self::IllegalChar { span, .. }
+            | Self::InvalidBase64 { span }
+            | Self::MalformedEscape { span } => span.clone(),
+        }
+    }
+}
+
+/// Collects the issues of all encoded words of the input.
+pub fn run(input: &[u8]) -> Vec<ValidationIssue> {
+    // keeps the too long encoded words, so they can be reported
+    let decoder = Decoder::new().too_long_encoded_word_strategy(RecoverStrategy::Decode);
+
+    // the lexer only fails for settings of the decoder which aren't used here
+    let spanned_tokens = lexer::run_spanned(input, &decoder).unwrap_or_default();
+
+    let mut issues = Vec::new();
+    for (token, span) in spanned_tokens {
+        if let Token::EncodedWord(encoded_word) = token {
+            validate_encoded_word(&encoded_word, span, &decoder, &mut issues);
+        }
+    }
+
+    issues
+}
+
+fn validate_encoded_word(
+    encoded_word: &encoded_word::EncodedWord,
+    span: Range<usize>,
+    decoder: &Decoder,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    if span.len() > encoded_word::MAX_LENGTH {
+        issues.push(ValidationIssue::TooLong { span: span.clone() });
+    }
+
+    if !evaluator::is_supported_charset(&encoded_word.charset, decoder) {
+        issues.push(ValidationIssue::UnknownCharset { span: span.clone() });
+    }
+
+    let encoded_text = &encoded_word.encoded_text;
+    // the encoded text is followed by the `?=` of the encoded word
+    let text_start = span.end - 2 - encoded_text.len();
+
+    let mut has_illegal_char = false;
+    for (offset, &byte) in encoded_text.iter().enumerate() {
+        if !byte.is_ascii_graphic() {
+            let position = text_start + offset;
+            issues.push(ValidationIssue::IllegalChar {
+                span: position..position + 1,
+                byte,
+            });
+            has_illegal_char = true;
+        }
+    }
+
+    match parser::encoding_for_label(&encoded_word.encoding, decoder) {
+        Ok(Encoding::B) if !has_illegal_char && STANDARD.decode(encoded_text).is_err() => {
+            issues.push(ValidationIssue::InvalidBase64 { span });
+        }
+        Ok(Encoding::Q) => {
+            for (offset, _) in encoded_text
+                .iter()
+                .enumerate()
+                .filter(|(_, &byte)| byte == b'=')
+            {
+                let is_escape = matches!(
+                    encoded_text.get(offset + 1..offset + 3),
+                    Some([high, low]) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit()
+                );
+                if !is_escape {
+                    let position = text_start + offset;
+                    issues.push(ValidationIssue::MalformedEscape {
+                        span: position..position + 1,
+                    });
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(_) => issues.push(ValidationIssue::InvalidEncoding { span }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::{run, ValidationIssue};
+
+    #[test]
+    fn valid() {
+        assert_eq!(run(b"plain text"), vec![]);
+        assert_eq!(
+            run(b"=?UTF-8?B?w6k=?= and =?ISO-8859-1?Q?caf=E9_au_lait?="),
+            vec![]
+        );
+        assert_eq!(run(b"=?HZ-GB-2312?Q?~{Ab~}?="), vec![]);
+    }
+
+    #[test]
+    fn too_long() {
+        let too_long_word = format!("=?UTF-8?Q?{}?=", "a".repeat(64));
+        let message = format!("a {}", too_long_word);
+
+        assert_eq!(
+            run(message.as_bytes()),
+            vec![ValidationIssue::TooLong { span: 2..78 }]
+        );
+    }
+
+    #[test]
+    fn illegal_chars() {
+        let issues = run(b"=?UTF-8?B?w6\x7fk=?= =?UTF-8?Q?\xe9t\xe9?=");
+
+        assert_eq!(
+            issues,
+            vec![
+                ValidationIssue::IllegalChar {
+                    span: 12..13,
+                    byte: 0x7f
+                },
+                ValidationIssue::IllegalChar {
+                    span: 28..29,
+                    byte: 0xe9
+                },
+                ValidationIssue::IllegalChar {
+                    span: 30..31,
+                    byte: 0xe9
+                },
+            ]
+        );
+        assert_eq!(issues[1].span(), 28..29);
+        assert_eq!(
+            issues[1].to_string(),
+            "the byte 0xe9 at 28..29 isn't allowed in encoded text"
+        );
+    }
+
+    #[test]
+    fn invalid_base64() {
+        assert_eq!(
+            run(b"=?UTF-8?B?w6k?= =?UTF-8?B?!!!!?="),
+            vec![
+                ValidationIssue::InvalidBase64 { span: 0..15 },
+                ValidationIssue::InvalidBase64 { span: 16..32 },
+            ]
+        );
+    }
+}